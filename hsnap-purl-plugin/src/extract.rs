@@ -0,0 +1,423 @@
+use packageurl::PackageUrl;
+
+/// The longest line, in bytes, that `purls_from_lines` matches; longer ones, such as a
+/// minified file's, are no package's and are skipped.
+pub const MAX_LINE_LEN: usize = 4096;
+
+/// What `purls_from_lines` takes a component's `name`, `version` and `arch` from on each
+/// line: either `LinePattern::FIRST_VERSION` or a template, see `LinePattern::template`.
+#[derive(Debug)]
+pub struct LinePattern {
+    /// The template's text and fields, none for `FIRST_VERSION`.
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug)]
+enum Piece {
+    /// Literal text, with each run of whitespace as a single space.
+    Text(String),
+    /// A field, `None` for one that is skipped.
+    Field(Option<Field>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Name,
+    Version,
+    Arch,
+}
+
+/// The fields `LinePattern::fields` found on a line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Fields<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub arch: Option<&'a str>,
+}
+
+impl LinePattern {
+    /// The first version-like word of a `--version` output as the version, e.g. `24.0.7`
+    /// from `Docker version 24.0.7, build afdd53b`, `1.7.2` from
+    /// `containerd github.com/containerd/containerd v1.7.2 8fc6bcff` or `1.21.5` from
+    /// `go version go1.21.5 linux/amd64`. That is the first word starting with a digit,
+    /// once leading punctuation and a `go` or `v` prefix are dropped, up to the first
+    /// character other than a letter, digit, `.`, `_`, `+` or `-`.
+    pub const FIRST_VERSION: LinePattern = LinePattern { pieces: Vec::new() };
+
+    /// Parses a template of literal text and `{name}`, `{version}` and `{arch}` fields,
+    /// with `{}` for a field that is skipped, e.g. `{name}/{} {version} {arch} [` for
+    /// `apt list` output. `{{` and `}}` are a literal brace.
+    ///
+    /// A line, with surrounding whitespace trimmed, matches if it starts with the template.
+    /// Text matches exactly, except that a run of whitespace matches any run of whitespace.
+    /// A field takes the shortest non-empty text up to where the text after it matches,
+    /// or the rest of the line if it comes last, so fields need text between them.
+    pub fn template(template: &str) -> Result<LinePattern, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("unclosed {{{}", field)),
+                        }
+                    }
+                    let field = match field.as_str() {
+                        "name" => Some(Field::Name),
+                        "version" => Some(Field::Version),
+                        "arch" => Some(Field::Arch),
+                        "" => None,
+                        other => {
+                            return Err(format!(
+                                "unknown field {{{}}}, expected {{name}}, {{version}}, {{arch}} or {{}}",
+                                other
+                            ))
+                        }
+                    };
+                    if text.is_empty() && matches!(pieces.last(), Some(Piece::Field(_))) {
+                        return Err("fields need text between them".to_string());
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("unmatched }".to_string()),
+                c if c.is_whitespace() => {
+                    if !text.ends_with(' ') {
+                        text.push(' ');
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        if !pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Field(Some(_))))
+        {
+            return Err("no {name}, {version} or {arch} field".to_string());
+        }
+        Ok(LinePattern { pieces })
+    }
+
+    /// Whether a line's name comes from the template.
+    pub fn has_name(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Field(Some(Field::Name))))
+    }
+
+    /// The fields of `line`, or `None` if it doesn't match.
+    pub fn fields<'a>(&self, line: &'a str) -> Option<Fields<'a>> {
+        let line = line.trim();
+        if self.pieces.is_empty() {
+            return first_version(line).map(|version| Fields {
+                version: Some(version),
+                ..Fields::default()
+            });
+        }
+        let mut fields = Fields::default();
+        let mut at = 0;
+        let mut pieces = self.pieces.iter();
+        while let Some(piece) = pieces.next() {
+            let field = match piece {
+                Piece::Text(text) => {
+                    at = match_text(text, line, at)?;
+                    continue;
+                }
+                Piece::Field(field) => field,
+            };
+            let start = at;
+            let end = match pieces.next() {
+                Some(Piece::Text(text)) => {
+                    let (end, after) = line[at..]
+                        .char_indices()
+                        .skip(1)
+                        .find_map(|(i, _)| match_text(text, line, at + i).map(|to| (at + i, to)))?;
+                    at = after;
+                    end
+                }
+                _ => {
+                    at = line.len();
+                    at
+                }
+            };
+            if start == end {
+                return None;
+            }
+            let value = Some(&line[start..end]);
+            match field {
+                Some(Field::Name) => fields.name = value,
+                Some(Field::Version) => fields.version = value,
+                Some(Field::Arch) => fields.arch = value,
+                None => {}
+            }
+        }
+        Some(fields)
+    }
+}
+
+/// Where `text` ends if it matches `line` at `at`.
+fn match_text(text: &str, line: &str, mut at: usize) -> Option<usize> {
+    for c in text.chars() {
+        let rest = &line[at..];
+        if c == ' ' {
+            let trimmed = rest.trim_start();
+            if trimmed.len() == rest.len() {
+                return None;
+            }
+            at += rest.len() - trimmed.len();
+        } else {
+            if !rest.starts_with(c) {
+                return None;
+            }
+            at += c.len_utf8();
+        }
+    }
+    Some(at)
+}
+
+/// The version `LinePattern::FIRST_VERSION` describes.
+fn first_version(line: &str) -> Option<&str> {
+    line.split_whitespace().find_map(|word| {
+        let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let word = word
+            .strip_prefix("go")
+            .or_else(|| word.strip_prefix('v'))
+            .unwrap_or(word);
+        let end = word
+            .find(|c: char| !(c.is_alphanumeric() || "._+-".contains(c)))
+            .unwrap_or(word.len());
+        let version = &word[..end];
+        version
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then_some(version)
+    })
+}
+
+/// Builds a purl of type `ty` from each line of `text` that `pattern` matches, from its
+/// `name`, `version` and `arch` fields. `name` is used for lines the pattern takes no
+/// name from; lines with no name either way, or over `MAX_LINE_LEN`, are skipped. `arch`
+/// becomes a qualifier.
+pub fn purls_from_lines(
+    pattern: &LinePattern,
+    ty: &str,
+    name: Option<&str>,
+    text: &str,
+) -> Vec<PackageUrl<'static>> {
+    text.lines()
+        .filter(|line| line.len() <= MAX_LINE_LEN)
+        .filter_map(|line| {
+            let fields = pattern.fields(line)?;
+            let name = fields.name.or(name)?;
+            let mut purl = PackageUrl::new(ty.to_string(), name.to_string()).ok()?;
+            if let Some(version) = fields.version {
+                purl.with_version(version.to_string());
+            }
+            if let Some(arch) = fields.arch {
+                let _ = purl.add_qualifier("arch", arch.to_string());
+            }
+            Some(purl)
+        })
+        .collect()
+}
+
+/// Splits a CSV row whose fields may be quoted, with `""` for a quote inside quotes.
+pub fn parse_csv_row(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if !line.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Matches a file name against a pattern where `*` is any run of characters and `?` any one.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has taken
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn purl_strings(purls: Vec<PackageUrl>) -> Vec<String> {
+        purls.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn extracts_name_version_and_arch_from_each_line() {
+        let output = "Listing...\n\
+                      curl/stable,now 7.88.1-10+deb12u5 amd64 [installed]\n\
+                      \n\
+                      libc6/stable,now 2.36-9+deb12u4 arm64 [installed,automatic]\n\
+                      tzdata/stable,now 2024a-0+deb12u1 all [installed]\n";
+        let pattern = LinePattern::template("{name}/{} {version} {arch} [").unwrap();
+
+        assert_eq!(
+            purl_strings(purls_from_lines(&pattern, "deb", None, output)),
+            [
+                "pkg:deb/curl@7.88.1-10%2Bdeb12u5?arch=amd64",
+                "pkg:deb/libc6@2.36-9%2Bdeb12u4?arch=arm64",
+                "pkg:deb/tzdata@2024a-0%2Bdeb12u1?arch=all",
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_matches_any_run_of_whitespace() {
+        let output = "requests   2.31.0\n\
+                      \turllib3\t 2.2.1 \n";
+        let pattern = LinePattern::template("{name}  {version}").unwrap();
+        assert_eq!(
+            purl_strings(purls_from_lines(&pattern, "generic", None, output)),
+            ["pkg:generic/requests@2.31.0", "pkg:generic/urllib3@2.2.1"]
+        );
+    }
+
+    #[test]
+    fn fields_take_the_shortest_text_before_what_follows() {
+        let pattern = LinePattern::template("{name} ({version})").unwrap();
+        assert_eq!(
+            pattern.fields("libssl (3.0.2) (security)"),
+            Some(Fields {
+                name: Some("libssl"),
+                version: Some("3.0.2"),
+                arch: None,
+            })
+        );
+        // A field can't be empty, and the text after one has to be there
+        assert_eq!(pattern.fields("libssl ()"), None);
+        assert_eq!(pattern.fields("libssl 3.0.2"), None);
+        assert_eq!(pattern.fields(""), None);
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for (template, error) in [
+            ("{name}{version}", "fields need text between them"),
+            ("{name} {release}", "unknown field {release}"),
+            ("{name", "unclosed {name"),
+            ("name} {version}", "unmatched }"),
+            ("name {}", "no {name}, {version} or {arch} field"),
+            ("^(?P<name>\\S+)$", "no {name}, {version} or {arch} field"),
+        ] {
+            let message = LinePattern::template(template).unwrap_err();
+            assert!(message.starts_with(error), "{}: {}", template, message);
+        }
+        let braces = LinePattern::template("{{{name}}} {version}").unwrap();
+        assert_eq!(braces.fields("{curl} 8.5.0").unwrap().name, Some("curl"));
+        assert!(braces.has_name());
+        assert!(!LinePattern::FIRST_VERSION.has_name());
+    }
+
+    #[test]
+    fn falls_back_to_given_name() {
+        let output = "Docker version 24.0.7, build afdd53b\nServer: Docker Engine\n";
+        let pattern = LinePattern::FIRST_VERSION;
+        assert_eq!(
+            purl_strings(purls_from_lines(
+                &pattern,
+                "generic",
+                Some("docker"),
+                output
+            )),
+            ["pkg:generic/docker@24.0.7"]
+        );
+        // No name found and none given
+        assert!(purls_from_lines(&pattern, "generic", None, output).is_empty());
+    }
+
+    #[test]
+    fn skips_overlong_lines() {
+        let minified = format!("{} 9.9.9", "x".repeat(MAX_LINE_LEN));
+        let output = format!("{}\nlibfoo 1.2.3\n", minified);
+        let pattern = LinePattern::template("{name} {version}").unwrap();
+        assert_eq!(
+            purl_strings(purls_from_lines(&pattern, "generic", None, &output)),
+            ["pkg:generic/libfoo@1.2.3"]
+        );
+    }
+
+    #[test]
+    fn first_version_strips_go_and_v_prefixes() {
+        for (line, version) in [
+            ("go version go1.21.5 linux/amd64", Some("1.21.5")),
+            ("v20.10.0", Some("20.10.0")),
+            (
+                "containerd github.com/containerd/containerd v1.7.2 8fc6bcff",
+                Some("1.7.2"),
+            ),
+            ("ruby 3.2.2 (2023-03-30 revision e51014f9c0)", Some("3.2.2")),
+            ("openjdk version \"17.0.8\" 2023-07-18", Some("17.0.8")),
+            ("Server: Docker Engine", None),
+        ] {
+            assert_eq!(
+                LinePattern::FIRST_VERSION
+                    .fields(line)
+                    .and_then(|fields| fields.version),
+                version,
+                "{}",
+                line
+            );
+        }
+    }
+}
@@ -1,20 +1,130 @@
+use chrono::{DateTime, Utc};
 use packageurl::PackageUrl;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bound on how much of a file a `Probe::FileContents` reads.
+pub const MAX_FILE_CONTENTS_BYTES: u64 = 1024 * 1024;
+
+/// How much of a command probe's output is kept, unless `PluginOptions::max_command_output` says otherwise.
+pub const MAX_COMMAND_OUTPUT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How long a `Probe::TcpConnect` waits for each of the host's addresses to accept.
+pub const TCP_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "details")]
 pub enum SoftwareComponent {
-    Purl(PackageUrl<'static>),
+    Purl(#[serde(serialize_with = "serialize_purl")] PackageUrl<'static>),
     WindowsComponent {
         name: String,
         version: String,
         publisher: Option<String>,
+        /// As the installer recorded it, usually `YYYYMMDD`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        install_date: Option<String>,
+        /// `install_date` as a timestamp, when it is in a form we recognize.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        installed_at: Option<DateTime<Utc>>,
+        /// In KiB.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        estimated_size: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url_info_about: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uninstall_string: Option<String>,
+    },
+    BrowserExtension {
+        browser: String,
+        id: String,
+        name: String,
+        version: String,
+        /// The browser profile directory the extension is installed in.
+        profile: String,
+        user: Option<String>,
     },
+    WindowsDriver {
+        /// The driver's service name, e.g. `ACPI`.
+        name: String,
+        display_name: String,
+        /// `Kernel` or `File System`.
+        driver_type: String,
+        /// e.g. `Boot`, `System` or `Manual`.
+        start_mode: String,
+        /// e.g. `Running` or `Stopped`.
+        state: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        /// Whether the driver file has a valid Authenticode signature, `None` if it
+        /// couldn't be checked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signed: Option<bool>,
+        /// The subject of the signing certificate.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signer: Option<String>,
+    },
+}
+
+/// Serializes a purl as `PackageUrl` does, but with its qualifiers sorted, so the same
+/// component always serializes to the same JSON.
+fn serialize_purl<S: serde::Serializer>(
+    purl: &PackageUrl<'static>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct SortedPurl<'a> {
+        ty: &'a str,
+        namespace: Option<&'a str>,
+        name: &'a str,
+        version: Option<&'a str>,
+        qualifiers: BTreeMap<&'a str, &'a str>,
+        subpath: Option<&'a str>,
+    }
+    SortedPurl {
+        ty: purl.ty(),
+        namespace: purl.namespace(),
+        name: purl.name(),
+        version: purl.version(),
+        qualifiers: purl
+            .qualifiers()
+            .iter()
+            .map(|(key, value)| (key.as_ref(), value.as_ref()))
+            .collect(),
+        subpath: purl.subpath(),
+    }
+    .serialize(serializer)
+}
+
+/// A component and the plugin that found it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentWithSource {
+    #[serde(flatten)]
+    pub component: SoftwareComponent,
+    /// The `name()` of the plugin, or `extra-components` for those from `--extra-components`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source: String,
+    /// SPDX license expression, only set by `--enrich`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Only set by `--enrich`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
 }
 
+pub mod extract;
+mod locks;
+mod os_release;
 pub mod plugins;
+#[cfg(test)]
+mod test_support;
+pub mod users;
+mod version_info;
 
 #[derive(Debug, Clone, Serialize, PartialEq)]
 pub enum FileLocation {
@@ -30,10 +140,34 @@ pub enum FileLocation {
 pub enum Probe {
     /// Check for a file existence
     File(FileLocation),
+    /// Read a file's contents, up to `MAX_FILE_CONTENTS_BYTES`
+    FileContents(FileLocation),
     /// Check for a Windows Registry Key existence (Windows only)
     WindowsRegistry(String),
+    /// List the names of a Windows Registry Key's subkeys (Windows only)
+    WindowsRegistrySubkeys(String),
     /// Execute a command and check for success
     Command(String),
+    /// Run a program directly, without a shell, and check for success. Each argument is
+    /// passed as is, so nothing needs quoting or escaping.
+    Exec { program: String, args: Vec<String> },
+    /// Run a program directly, as `Exec` does, but pass each line of its standard output
+    /// to `Plugin::extract_line` as it is read rather than buffering it all, for commands
+    /// listing thousands of packages.
+    ExecLines { program: String, args: Vec<String> },
+    /// Check whether something listens on a TCP port, e.g. a database on `localhost:5432`.
+    /// Skipped when scanning a `root`, as the ports are the live host's.
+    TcpConnect { host: String, port: u16 },
+}
+
+impl Probe {
+    /// Lists the sources registered with the Windows Application event log. Software
+    /// that writes to the event log can be detected by the presence of its source.
+    pub fn event_log_sources() -> Probe {
+        Probe::WindowsRegistrySubkeys(
+            "HKLM\\SYSTEM\\CurrentControlSet\\Services\\EventLog\\Application".to_string(),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -41,16 +175,44 @@ pub struct RegistryEntry {
     pub display_name: Option<String>,
     pub display_version: Option<String>,
     pub publisher: Option<String>,
+    pub install_location: Option<String>,
+    /// Often the path of the program's main executable.
+    pub display_icon: Option<String>,
+    pub install_date: Option<String>,
+    /// In KiB.
+    pub estimated_size: Option<u64>,
+    pub url_info_about: Option<String>,
+    pub uninstall_string: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub enum ProbeData {
     /// For file probes, provides the path to the found file.
     File(PathBuf),
-    /// For command probes, provides the standard output.
-    CommandOutput(String),
+    /// For file contents probes, provides the (possibly truncated) text of the file.
+    FileContents(String),
+    /// For command probes, provides the standard output and standard error. Some tools,
+    /// such as `java -version`, write what they report to stderr.
+    CommandOutput { stdout: String, stderr: String },
     /// For registry probes, provides the value/data found.
     RegistryEntries(Vec<RegistryEntry>),
+    /// For registry subkey probes, provides the subkey names.
+    RegistrySubkeys(Vec<String>),
+    /// For TCP connect probes, whether the port accepted a connection.
+    TcpOpen(bool),
+}
+
+impl ProbeData {
+    /// Whether a registry subkey probe found a subkey called `name`, e.g. an event log
+    /// source. Registry key names are case-insensitive.
+    pub fn has_subkey(&self, name: &str) -> bool {
+        match self {
+            ProbeData::RegistrySubkeys(subkeys) => subkeys
+                .iter()
+                .any(|subkey| subkey.eq_ignore_ascii_case(name)),
+            _ => false,
+        }
+    }
 }
 
 /// Represents the result of a successful probe
@@ -67,22 +229,872 @@ pub enum Os {
     Unknown,
 }
 
+/// Runtime options that configure which plugins run and what they scan.
+#[derive(Debug, Clone, Default)]
+pub struct PluginOptions {
+    /// Project roots to search for a `composer.lock`.
+    pub composer_roots: Vec<PathBuf>,
+    /// Directories to search for Python virtualenvs.
+    pub venv_roots: Vec<PathBuf>,
+    /// Detect the installed fonts.
+    pub fonts: bool,
+    /// Stop collecting once this many components have been found.
+    pub max_components: Option<usize>,
+    /// Only run plugins producing one of these component kinds. Empty runs every plugin.
+    pub component_kinds: Vec<String>,
+    /// How long package manager plugins wait for a locked package database before skipping it.
+    pub package_lock_timeout: Duration,
+    /// Qualifiers to add to every purl from a plugin, keyed by plugin name. These take
+    /// precedence over the plugin's own `default_qualifiers`.
+    pub extra_qualifiers: HashMap<String, Vec<(String, String)>>,
+    /// Scan the filesystem mounted here, e.g. an extracted container image, instead of the
+    /// live host. Absolute probe paths are resolved under it.
+    pub root: Option<PathBuf>,
+    /// Never run external programs: the dpkg status file is read directly, rpm packages
+    /// aren't collected and command probes are skipped.
+    pub no_shell: bool,
+    /// Bytes of a command probe's output to keep, `MAX_COMMAND_OUTPUT_BYTES` if unset.
+    pub max_command_output: Option<u64>,
+    /// Directories of JSON plugin manifests, each run as a `DeclarativePlugin`.
+    pub plugin_dirs: Vec<PathBuf>,
+    /// Stop running plugins at the first error, rather than carrying on without the
+    /// plugin's components.
+    pub fail_fast: bool,
+    /// Which programs command probes may run.
+    pub command_policy: CommandPolicy,
+}
+
+/// Which programs plugins' command probes may run. A program matches an entry naming its
+/// path as given or its file name, with or without `.exe`.
+///
+/// Only `Probe::Exec` and `Probe::ExecLines` probes can be checked program by program. A
+/// shell command could run anything (`env curl`, `true; curl`), so it's judged by the
+/// shell (`sh`, or `cmd` on Windows) alone: with an allowlist the shell has to be on it,
+/// which lets shell commands run whatever they say, and with only a denylist shell
+/// commands never run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandPolicy {
+    /// When set, only these programs run. Empty runs no command at all.
+    pub allow: Option<Vec<String>>,
+    /// Programs that never run.
+    pub deny: Vec<String>,
+}
+
+impl CommandPolicy {
+    /// Whether `program` may run.
+    pub fn permits(&self, program: &str) -> bool {
+        self.denial(program).is_none()
+    }
+
+    /// Why `program` may not run, if it may not.
+    fn denial(&self, program: &str) -> Option<&'static str> {
+        let path = Path::new(program);
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(program);
+        let matches = |entry: &String| {
+            entry == program
+                || entry == file_name
+                || file_name
+                    .get(..file_name.len().saturating_sub(4))
+                    .filter(|_| file_name.to_ascii_lowercase().ends_with(".exe"))
+                    .is_some_and(|stem| entry == stem)
+        };
+        match &self.allow {
+            Some(allow) if allow.is_empty() => Some("commands are disabled"),
+            _ if self.deny.iter().any(matches) => Some("in the probe denylist"),
+            Some(allow) if !allow.iter().any(matches) => Some("not in the probe allowlist"),
+            _ => None,
+        }
+    }
+
+    /// Why `probe` may not run, if it is a command probe the policy denies.
+    fn probe_denial(&self, probe: &Probe) -> Option<&'static str> {
+        match probe {
+            Probe::Command(_) => {
+                let shell = if cfg!(target_os = "windows") {
+                    "cmd"
+                } else {
+                    "sh"
+                };
+                self.denial(shell).or_else(|| {
+                    (self.allow.is_none() && !self.deny.is_empty())
+                        .then_some("shell commands can't be checked against the probe denylist")
+                })
+            }
+            Probe::Exec { program, .. } | Probe::ExecLines { program, .. } => self.denial(program),
+            _ => None,
+        }
+    }
+}
+
+/// A command probe that `PluginOptions::command_policy` kept from running.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SkippedProbe {
+    pub plugin: String,
+    /// The shell command, or the program and its arguments.
+    pub command: String,
+    /// e.g. `not in the probe allowlist`.
+    pub reason: String,
+}
+
+/// The components found by `run_plugins`.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    pub components: Vec<ComponentWithSource>,
+    /// Set when `PluginOptions::max_components` was reached and further components were dropped.
+    pub truncated: bool,
+    /// What went wrong running the plugins. Their components may be missing or incomplete.
+    pub errors: Vec<PluginError>,
+    /// The command probes the `CommandPolicy` denied, whose components are missing.
+    pub skipped: Vec<SkippedProbe>,
+    /// What went wrong without failing a plugin, such as a command's output cut short at
+    /// `PluginOptions::max_command_output`. Its components may be incomplete.
+    pub warnings: Vec<PluginError>,
+}
+
+/// An error of a plugin, or for a plugin manifest that couldn't be loaded, of its file.
+#[derive(Debug, Clone)]
+pub struct PluginError {
+    pub plugin: String,
+    pub error: Error,
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} plugin: {}", self.plugin, self.error)
+    }
+}
+
+impl std::error::Error for PluginError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// What went wrong running a plugin. Files and commands that aren't there aren't errors,
+/// as most plugins look for software the host doesn't have.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A command probe whose program is installed but couldn't be run, or exited with an
+    /// error, e.g. `exit status: 1: dpkg-query: error: parsing file '/var/lib/dpkg/status'`.
+    Probe { probe: Probe, message: String },
+    /// A plugin manifest that isn't valid JSON or isn't a valid manifest.
+    Parse { path: PathBuf, message: String },
+    /// A file or directory that exists but couldn't be read.
+    Io {
+        path: PathBuf,
+        source: Arc<std::io::Error>,
+    },
+    /// The plugin panicked, losing the components it had found.
+    Panic(String),
+    /// The package database stayed locked, by a package manager changing it, for longer
+    /// than `PluginOptions::package_lock_timeout`, so it wasn't read.
+    Busy { lock: PathBuf },
+    /// A command probe wrote more than `max` bytes, so it was killed and its output cut
+    /// short. Only ever a warning: what it wrote up to then is kept.
+    OutputTruncated { probe: Probe, max: u64 },
+}
+
+impl Error {
+    pub(crate) fn io(path: &Path, source: std::io::Error) -> Error {
+        Error::Io {
+            path: path.to_path_buf(),
+            source: Arc::new(source),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Probe { probe, message } => write!(f, "{:?} failed: {}", probe, message),
+            Error::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Panic(message) => write!(f, "panicked: {}", message),
+            Error::Busy { lock } => {
+                write!(f, "package database busy ({} is locked)", lock.display())
+            }
+            Error::OutputTruncated { probe, max } => {
+                write!(f, "{:?}: output exceeded {} bytes, truncated", probe, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Inventory {
+    /// Adds components found by the plugin `source` until `limit` is reached, marking the
+    /// inventory as truncated if any are left over.
+    fn extend_capped(
+        &mut self,
+        components: impl IntoIterator<Item = SoftwareComponent>,
+        source: &str,
+        limit: usize,
+    ) {
+        for component in components {
+            if self.components.len() >= limit {
+                self.truncated = true;
+                return;
+            }
+            self.components.push(ComponentWithSource {
+                component,
+                source: source.to_string(),
+                license: None,
+                homepage: None,
+            });
+        }
+    }
+}
+
 pub trait Plugin {
     fn name(&self) -> &str;
     fn supported_os(&self) -> Option<Vec<Os>>;
+
+    /// The kinds of component this plugin produces: the purl type (e.g. `deb`, `pypi`) for
+    /// purls, or `windows-component`/`browser-extension`/`windows-driver` for the other
+    /// component types.
+    /// Plugins that declare none are run whatever `PluginOptions::component_kinds` is.
+    fn component_kinds(&self) -> &[&str] {
+        &[]
+    }
+    /// Checks the plugin can run now, e.g. that its package database isn't locked. An
+    /// error skips the plugin and is listed in the inventory.
+    fn ready(&self) -> Result<(), Error> {
+        Ok(())
+    }
     fn probes(&self) -> Vec<Probe>;
     fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent>;
+
+    /// The components `extract` finds, or why the found probes couldn't be read, e.g. a
+    /// corrupt package database. The error is listed in the inventory.
+    fn try_extract(&self, found_probes: &[ProbeResult]) -> Result<Vec<SoftwareComponent>, Error> {
+        Ok(self.extract(found_probes))
+    }
+
+    /// Builds a component from a line of a `Probe::ExecLines` program's output, as soon as
+    /// it is read. Its components are added before those `extract` returns.
+    fn extract_line(&self, _probe: &Probe, _line: &str) -> Option<SoftwareComponent> {
+        None
+    }
+
+    /// Qualifiers added to every purl this plugin extracts, unless `extract` already set them.
+    fn default_qualifiers(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Probes to run against each user's home directory, for software installed per user.
+    /// Components extracted from these are tagged with the owning user.
+    fn user_probes(&self, _home: &Path) -> Vec<Probe> {
+        Vec::new()
+    }
+}
+
+/// The plugins to run. Plugin manifests that couldn't be loaded are added to `errors`.
+fn get_plugins(options: &PluginOptions, errors: &mut Vec<PluginError>) -> Vec<Box<dyn Plugin>> {
+    // Where dpkg-query may not run, the status file is read directly instead
+    let mut plugins: Vec<Box<dyn Plugin>> = vec![
+        Box::new(plugins::RhelPlugin {
+            lock_timeout: options.package_lock_timeout,
+            root: options.root.clone(),
+            no_shell: options.no_shell,
+        }),
+        Box::new(plugins::DebianPlugin {
+            lock_timeout: options.package_lock_timeout,
+            root: options.root.clone(),
+            no_shell: options.no_shell || !options.command_policy.permits("dpkg-query"),
+        }),
+        Box::new(plugins::BrowserExtensionPlugin),
+        Box::new(plugins::ComposerPlugin {
+            roots: options.composer_roots.clone(),
+        }),
+        Box::new(plugins::PythonVenvPlugin {
+            roots: options.venv_roots.clone(),
+        }),
+    ];
+    if options.fonts {
+        plugins.push(Box::new(plugins::FontPlugin));
+    }
+    // These read the live registry and run the host's binaries, which say nothing
+    // about a mounted root
+    if options.root.is_none() {
+        plugins.insert(0, Box::new(plugins::WindowsRegistryPlugin));
+        plugins.insert(1, Box::new(plugins::WindowsDriverPlugin));
+        plugins.push(Box::new(plugins::ContainerRuntimePlugin));
+        plugins.push(Box::new(plugins::RuntimePlugin));
+        plugins.push(Box::new(plugins::K8sNodePlugin));
+    }
+    for dir in &options.plugin_dirs {
+        let (loaded, load_errors) = plugins::declarative::load_dir(dir, options.root.as_deref());
+        plugins.extend(
+            loaded
+                .into_iter()
+                .map(|plugin| Box::new(plugin) as Box<dyn Plugin>),
+        );
+        errors.extend(load_errors);
+    }
+    plugins
+}
+
+/// Where an absolute path of the scanned system is found: under `root` if one is set.
+pub(crate) fn under_root(root: Option<&Path>, path: &Path) -> PathBuf {
+    match root {
+        Some(root) => root.join(path.strip_prefix("/").unwrap_or(path)),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Whether a program can be found in `$PATH`.
+pub(crate) fn on_path(program: &str) -> bool {
+    resolve_location(&FileLocation::Path(program.to_string()), None).is_some()
+}
+
+/// Directories searched for `FileLocation::Path` binaries under a root, in place of `$PATH`.
+const ROOT_BIN_DIRS: [&str; 6] = [
+    "/usr/local/sbin",
+    "/usr/local/bin",
+    "/usr/sbin",
+    "/usr/bin",
+    "/sbin",
+    "/bin",
+];
+
+fn resolve_location(loc: &FileLocation, root: Option<&Path>) -> Option<PathBuf> {
+    match loc {
+        FileLocation::AbsolutePath(p) => Some(under_root(root, Path::new(p))),
+        FileLocation::RelativePath(p) => std::env::current_dir().ok().map(|cwd| cwd.join(p)),
+        FileLocation::Path(bin_name) if root.is_some() => ROOT_BIN_DIRS
+            .iter()
+            .map(|dir| under_root(root, &Path::new(dir).join(bin_name)))
+            .find(|path| path.exists()),
+        FileLocation::Path(bin_name) => {
+            if let Ok(paths) = std::env::var("PATH") {
+                std::env::split_paths(&paths).find_map(|p| {
+                    let full_path = p.join(bin_name);
+                    if full_path.exists() {
+                        Some(full_path)
+                    } else {
+                        None
+                    }
+                })
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Opens a registry key given as `HKLM\\...` or `HKCU\\...`. Other roots are not supported.
+#[cfg(target_os = "windows")]
+fn open_registry_key(key: &str) -> Option<winreg::RegKey> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    if let Some(path) = key.strip_prefix("HKLM\\") {
+        RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(path).ok()
+    } else if let Some(path) = key.strip_prefix("HKCU\\") {
+        RegKey::predef(HKEY_CURRENT_USER).open_subkey(path).ok()
+    } else {
+        None
+    }
+}
+
+/// A value under an uninstall key. Installers don't agree on types, so the same name can be
+/// a string on one entry and a DWORD or QWORD on another.
+#[cfg(any(target_os = "windows", test))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegistryValue {
+    String(String),
+    Dword(u32),
+    Qword(u64),
+}
+
+#[cfg(any(target_os = "windows", test))]
+impl RegistryValue {
+    /// A number (e.g. an InstallDate of 20240131) is formatted in decimal.
+    fn into_string(self) -> String {
+        match self {
+            RegistryValue::String(value) => value,
+            RegistryValue::Dword(value) => value.to_string(),
+            RegistryValue::Qword(value) => value.to_string(),
+        }
+    }
+
+    fn into_number(self) -> Option<u64> {
+        match self {
+            RegistryValue::String(value) => value.trim().parse().ok(),
+            RegistryValue::Dword(value) => Some(u64::from(value)),
+            RegistryValue::Qword(value) => Some(value),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn registry_value(key: &winreg::RegKey, name: &str) -> Option<RegistryValue> {
+    key.get_value::<String, _>(name)
+        .map(RegistryValue::String)
+        .or_else(|_| key.get_value::<u32, _>(name).map(RegistryValue::Dword))
+        .or_else(|_| key.get_value::<u64, _>(name).map(RegistryValue::Qword))
+        .ok()
+}
+
+/// Builds the entry for one uninstall subkey from `value`, which looks up a value by name.
+/// Subkeys without a DisplayName (updates, components of a bundle) are skipped.
+#[cfg(any(target_os = "windows", test))]
+fn registry_entry(value: impl Fn(&str) -> Option<RegistryValue>) -> Option<RegistryEntry> {
+    let string = |name| value(name).map(RegistryValue::into_string);
+    Some(RegistryEntry {
+        display_name: Some(string("DisplayName")?),
+        display_version: string("DisplayVersion"),
+        publisher: string("Publisher"),
+        install_location: string("InstallLocation"),
+        display_icon: string("DisplayIcon"),
+        install_date: string("InstallDate"),
+        estimated_size: value("EstimatedSize").and_then(RegistryValue::into_number),
+        url_info_about: string("URLInfoAbout"),
+        uninstall_string: string("UninstallString"),
+    })
+}
+
+/// Adds each qualifier a purl doesn't already have.
+fn add_qualifiers(
+    component: SoftwareComponent,
+    qualifiers: &[(String, String)],
+) -> SoftwareComponent {
+    match component {
+        SoftwareComponent::Purl(mut purl) => {
+            for (key, value) in qualifiers {
+                if !purl.qualifiers().contains_key(key.as_str()) {
+                    let _ = purl.add_qualifier(key.clone(), value.clone());
+                }
+            }
+            SoftwareComponent::Purl(purl)
+        }
+        other => other,
+    }
+}
+
+/// Records the owning user on a component found by `Plugin::user_probes`.
+fn tag_user(component: SoftwareComponent, user: &str) -> SoftwareComponent {
+    match component {
+        SoftwareComponent::Purl(mut purl) => {
+            let _ = purl.add_qualifier("user", user.to_string());
+            SoftwareComponent::Purl(purl)
+        }
+        SoftwareComponent::BrowserExtension {
+            browser,
+            id,
+            name,
+            version,
+            profile,
+            ..
+        } => SoftwareComponent::BrowserExtension {
+            browser,
+            id,
+            name,
+            version,
+            profile,
+            user: Some(user.to_string()),
+        },
+        other => other,
+    }
+}
+
+/// Reads at most `MAX_FILE_CONTENTS_BYTES` of a file as (lossy) UTF-8.
+fn read_capped(path: &Path) -> std::io::Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut buf = Vec::new();
+    file.take(MAX_FILE_CONTENTS_BYTES).read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// Runs a command probe, returning its output if it exited successfully, nothing if the
+/// program isn't installed, and an error if it failed. Rather than
+/// buffer everything a runaway command writes, a command writing more than `max` bytes to
+/// either stream is killed, its output cut at the last complete line before the cap and an
+/// `Error::OutputTruncated` added to `warnings`.
+fn run_command(
+    probe: &Probe,
+    mut command: Command,
+    max: u64,
+    warnings: &mut Vec<Error>,
+) -> Result<Option<ProbeResult>, Error> {
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::Probe {
+                probe: probe.clone(),
+                message: e.to_string(),
+            })
+        }
+    };
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Ok(None);
+    };
+    let child = Arc::new(Mutex::new(child));
+
+    // Both streams are read at once, so a command can't block on a full stderr pipe while
+    // stdout is read
+    let stderr = {
+        let child = child.clone();
+        std::thread::spawn(move || read_output(stderr, max, &child))
+    };
+    let stdout = read_output(stdout, max, &child);
+    let stderr = stderr.join().ok().flatten();
+    let Some(status) = child.lock().ok().and_then(|mut child| child.wait().ok()) else {
+        return Ok(None);
+    };
+
+    let (Some((stdout, stdout_truncated)), Some((stderr, stderr_truncated))) = (stdout, stderr)
+    else {
+        return Ok(None);
+    };
+    let truncated = stdout_truncated || stderr_truncated;
+    if truncated {
+        warnings.push(Error::OutputTruncated {
+            probe: probe.clone(),
+            max,
+        });
+    }
+    // Killing it is what made a truncated command fail
+    if !(status.success() || truncated) {
+        // The shell's (or cmd's) exit code for a command it couldn't find
+        if matches!(probe, Probe::Command(_)) && matches!(status.code(), Some(127) | Some(9009)) {
+            return Ok(None);
+        }
+        return Err(command_failed(probe, status, &stderr));
+    }
+    Ok(Some(ProbeResult {
+        probe: probe.clone(),
+        data: ProbeData::CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+        },
+    }))
+}
+
+/// Runs a `Probe::ExecLines` program, passing each line of its stdout to `on_line` as it
+/// is read, so only one line is held at a time. The program is killed once `on_line`
+/// returns false, or once it has written more than `max` bytes, which adds an
+/// `Error::OutputTruncated` to `warnings`.
+fn stream_command(
+    probe: &Probe,
+    mut command: Command,
+    max: u64,
+    warnings: &mut Vec<Error>,
+    on_line: &mut dyn FnMut(&str) -> bool,
+) -> Result<(), Error> {
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(Error::Probe {
+                probe: probe.clone(),
+                message: e.to_string(),
+            })
+        }
+    };
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Ok(());
+    };
+    let child = Arc::new(Mutex::new(child));
+    let stderr = {
+        let child = child.clone();
+        std::thread::spawn(move || read_output(stderr, max, &child))
+    };
+
+    let mut stdout = BufReader::new(stdout);
+    let mut line = Vec::new();
+    let mut read = 0;
+    let mut stopped = false;
+    loop {
+        line.clear();
+        // Even a single line can't grow past the cap
+        match (&mut stdout)
+            .take(max.saturating_sub(read).saturating_add(1))
+            .read_until(b'\n', &mut line)
+        {
+            Ok(0) | Err(_) => break,
+            Ok(len) => read += len as u64,
+        }
+        if read > max {
+            warnings.push(Error::OutputTruncated {
+                probe: probe.clone(),
+                max,
+            });
+            stopped = true;
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if !on_line(text.trim_end_matches(['\n', '\r'])) {
+            stopped = true;
+            break;
+        }
+    }
+    if stopped {
+        if let Ok(mut child) = child.lock() {
+            let _ = child.kill();
+        }
+    }
+    drop(stdout);
+
+    let stderr = stderr.join().ok().flatten();
+    let Some(status) = child.lock().ok().and_then(|mut child| child.wait().ok()) else {
+        return Ok(());
+    };
+    let Some((stderr, stderr_truncated)) = stderr else {
+        return Ok(());
+    };
+    // Killing it is what made a stopped command fail
+    if !(status.success() || stopped || stderr_truncated) {
+        return Err(command_failed(probe, status, &stderr));
+    }
+    Ok(())
+}
+
+/// The error for a command that exited with `status`, quoting the first line of its stderr.
+fn command_failed(probe: &Probe, status: std::process::ExitStatus, stderr: &[u8]) -> Error {
+    let stderr = String::from_utf8_lossy(stderr);
+    let message = match stderr.lines().find(|line| !line.trim().is_empty()) {
+        Some(line) => format!("{}: {}", status, line.trim()),
+        None => status.to_string(),
+    };
+    Error::Probe {
+        probe: probe.clone(),
+        message,
+    }
+}
+
+/// Reads up to `max` bytes of one of a command's output streams, and whether there was
+/// more. A command writing more is killed.
+fn read_output(mut stream: impl Read, max: u64, child: &Mutex<Child>) -> Option<(Vec<u8>, bool)> {
+    let mut buf = Vec::new();
+    (&mut stream).take(max).read_to_end(&mut buf).ok()?;
+    let truncated = stream.read(&mut [0]).is_ok_and(|len| len > 0);
+    if truncated {
+        if let Ok(mut child) = child.lock() {
+            let _ = child.kill();
+        }
+        buf.truncate(
+            buf.iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |end| end + 1),
+        );
+    }
+    Some((buf, truncated))
+}
+
+/// Runs each probe, returning the results of those that found something. The lines of
+/// `Probe::ExecLines` programs are passed to `on_line` instead, which returns false to stop
+/// the program. Command probes the `CommandPolicy` denies are added to `denied`, with why,
+/// without running, and those whose output was cut short to `warnings`.
+fn run_probes(
+    probes: Vec<Probe>,
+    options: &PluginOptions,
+    errors: &mut Vec<Error>,
+    warnings: &mut Vec<Error>,
+    denied: &mut Vec<(Probe, &'static str)>,
+    on_line: &mut dyn FnMut(&Probe, &str) -> bool,
+) -> Vec<ProbeResult> {
+    let root = options.root.as_deref();
+    let max_command_output = options
+        .max_command_output
+        .unwrap_or(MAX_COMMAND_OUTPUT_BYTES);
+    let mut probe_results = Vec::new();
+
+    for probe in probes {
+        // With no_shell commands are left out as a matter of course, not denied
+        if !options.no_shell {
+            if let Some(reason) = options.command_policy.probe_denial(&probe) {
+                denied.push((probe, reason));
+                continue;
+            }
+        }
+        match &probe {
+            Probe::File(loc) => {
+                if let Some(path) = resolve_location(loc, root) {
+                    if path.exists() {
+                        probe_results.push(ProbeResult {
+                            probe: probe.clone(),
+                            data: ProbeData::File(path),
+                        });
+                    }
+                }
+            }
+            Probe::FileContents(loc) => {
+                if let Some(path) = resolve_location(loc, root) {
+                    match read_capped(&path) {
+                        Ok(contents) => probe_results.push(ProbeResult {
+                            probe: probe.clone(),
+                            data: ProbeData::FileContents(contents),
+                        }),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => errors.push(Error::io(&path, e)),
+                    }
+                }
+            }
+            Probe::WindowsRegistry(key) => {
+                #[cfg(not(target_os = "windows"))]
+                let _ = key;
+                if cfg!(target_os = "windows") {
+                    #[cfg(target_os = "windows")]
+                    {
+                        if let Some(parent_key) = open_registry_key(key) {
+                            let mut entries = Vec::new();
+                            for name in parent_key.enum_keys().map(|x| x.unwrap_or_default()) {
+                                if let Ok(subkey) = parent_key.open_subkey(&name) {
+                                    entries.extend(registry_entry(|value| {
+                                        registry_value(&subkey, value)
+                                    }));
+                                }
+                            }
+
+                            if !entries.is_empty() {
+                                probe_results.push(ProbeResult {
+                                    probe: probe.clone(),
+                                    data: ProbeData::RegistryEntries(entries),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            Probe::WindowsRegistrySubkeys(key) => {
+                #[cfg(not(target_os = "windows"))]
+                let _ = key;
+                if cfg!(target_os = "windows") {
+                    #[cfg(target_os = "windows")]
+                    {
+                        if let Some(parent_key) = open_registry_key(key) {
+                            let subkeys: Vec<String> = parent_key
+                                .enum_keys()
+                                .filter_map(|name| name.ok())
+                                .collect();
+                            if !subkeys.is_empty() {
+                                probe_results.push(ProbeResult {
+                                    probe: probe.clone(),
+                                    data: ProbeData::RegistrySubkeys(subkeys),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            Probe::Command(_) | Probe::Exec { .. } | Probe::ExecLines { .. }
+                if options.no_shell => {}
+            Probe::Command(cmd_str) => {
+                let command = if cfg!(target_os = "windows") {
+                    let mut command = Command::new("cmd");
+                    command.args(["/C", cmd_str]);
+                    command
+                } else {
+                    let mut command = Command::new("sh");
+                    command.arg("-c").arg(cmd_str);
+                    command
+                };
+                match run_command(&probe, command, max_command_output, warnings) {
+                    Ok(result) => probe_results.extend(result),
+                    Err(e) => errors.push(e),
+                }
+            }
+            Probe::Exec { program, args } => {
+                let mut command = Command::new(program);
+                command.args(args);
+                match run_command(&probe, command, max_command_output, warnings) {
+                    Ok(result) => probe_results.extend(result),
+                    Err(e) => errors.push(e),
+                }
+            }
+            Probe::ExecLines { program, args } => {
+                let mut command = Command::new(program);
+                command.args(args);
+                if let Err(e) =
+                    stream_command(&probe, command, max_command_output, warnings, &mut |line| {
+                        on_line(&probe, line)
+                    })
+                {
+                    errors.push(e);
+                }
+            }
+            Probe::TcpConnect { .. } if root.is_some() => {}
+            Probe::TcpConnect { host, port } => {
+                let open = tcp_port_open(host, *port);
+                probe_results.push(ProbeResult {
+                    probe: probe.clone(),
+                    data: ProbeData::TcpOpen(open),
+                });
+            }
+        }
+    }
+    probe_results
+}
+
+/// Whether any of `host`'s addresses accepts a connection on `port` within
+/// `TCP_CONNECT_TIMEOUT`. A host that doesn't resolve has no open ports.
+fn tcp_port_open(host: &str, port: u16) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, TCP_CONNECT_TIMEOUT).is_ok())
+}
+
+/// Runs the plugins, carrying on without the failed plugin's components, unless
+/// `PluginOptions::fail_fast` is set. The errors and warnings are listed in the inventory,
+/// for the caller to report.
+pub fn run_plugins(options: &PluginOptions) -> Inventory {
+    collect(options)
+}
+
+/// Runs the plugins, stopping at the first error.
+pub fn try_run_plugins(options: &PluginOptions) -> Result<Inventory, PluginError> {
+    let options = PluginOptions {
+        fail_fast: true,
+        ..options.clone()
+    };
+    let mut inventory = collect(&options);
+    match inventory.errors.pop() {
+        Some(error) => Err(error),
+        None => Ok(inventory),
+    }
 }
 
-fn get_plugins() -> Vec<Box<dyn Plugin>> {
-    vec![
-        Box::new(plugins::WindowsRegistryPlugin),
-        Box::new(plugins::RhelPlugin),
-        Box::new(plugins::DebianPlugin),
-    ]
+fn collect(options: &PluginOptions) -> Inventory {
+    let mut inventory = Inventory::default();
+    let plugins = get_plugins(options, &mut inventory.errors);
+    if options.fail_fast && !inventory.errors.is_empty() {
+        return inventory;
+    }
+    collect_from(plugins, options, inventory)
 }
 
-pub fn run_plugins() -> Vec<SoftwareComponent> {
+/// Runs `plugins`, adding what they find to `inventory`.
+fn collect_from(
+    plugins: Vec<Box<dyn Plugin>>,
+    options: &PluginOptions,
+    mut inventory: Inventory,
+) -> Inventory {
     // 1. Determine current OS
     let current_os = if cfg!(target_os = "windows") {
         Os::Windows
@@ -94,10 +1106,16 @@ pub fn run_plugins() -> Vec<SoftwareComponent> {
         Os::Unknown
     };
 
-    let mut all_purls = Vec::new();
-    let plugins = get_plugins();
+    let limit = options.max_components.unwrap_or(usize::MAX);
+    let root = options.root.as_deref();
+    let user_homes = users::home_dirs(root);
 
     for plugin in plugins {
+        // Once the cap is hit there is no point running the remaining probes
+        if inventory.truncated {
+            break;
+        }
+
         // Filter by OS
         if let Some(supported) = plugin.supported_os() {
             if !supported.contains(&current_os) {
@@ -105,123 +1123,986 @@ pub fn run_plugins() -> Vec<SoftwareComponent> {
             }
         }
 
-        let mut probe_results = Vec::new();
+        // Filter by component kind
+        let kinds = plugin.component_kinds();
+        if !options.component_kinds.is_empty()
+            && !kinds.is_empty()
+            && !kinds
+                .iter()
+                .any(|kind| options.component_kinds.iter().any(|wanted| wanted == kind))
+        {
+            continue;
+        }
 
-        for probe in plugin.probes() {
-            match &probe {
-                Probe::File(loc) => {
-                    let path_to_check = match loc {
-                        FileLocation::AbsolutePath(p) => Some(PathBuf::from(p)),
-                        FileLocation::RelativePath(p) => {
-                            std::env::current_dir().ok().map(|cwd| cwd.join(p))
-                        }
-                        FileLocation::Path(bin_name) => {
-                            if let Ok(paths) = std::env::var("PATH") {
-                                std::env::split_paths(&paths).find_map(|p| {
-                                    let full_path = p.join(bin_name);
-                                    if full_path.exists() {
-                                        Some(full_path)
-                                    } else {
-                                        None
-                                    }
-                                })
-                            } else {
-                                None
-                            }
-                        }
-                    };
-
-                    if let Some(path) = path_to_check {
-                        if path.exists() {
-                            probe_results.push(ProbeResult {
-                                probe: probe.clone(),
-                                data: ProbeData::File(path),
-                            });
-                        }
+        let mut qualifiers = plugin.default_qualifiers();
+        if let Some(extra) = options.extra_qualifiers.get(plugin.name()) {
+            qualifiers.retain(|(key, _)| !extra.iter().any(|(extra_key, _)| extra_key == key));
+            qualifiers.extend(extra.iter().cloned());
+        }
+
+        if let Err(error) = plugin.ready() {
+            inventory.errors.push(PluginError {
+                plugin: plugin.name().to_string(),
+                error,
+            });
+            if options.fail_fast {
+                break;
+            }
+            continue;
+        }
+
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        let mut denied = Vec::new();
+        // A plugin that panics loses its own components, not everyone else's
+        let panicked = catch_panic(|| {
+            let probe_results = run_probes(
+                plugin.probes(),
+                options,
+                &mut errors,
+                &mut warnings,
+                &mut denied,
+                &mut |probe, line| {
+                    if let Some(component) = plugin.extract_line(probe, line) {
+                        inventory.extend_capped(
+                            [add_qualifiers(component, &qualifiers)],
+                            plugin.name(),
+                            limit,
+                        );
                     }
+                    !inventory.truncated
+                },
+            );
+            if !probe_results.is_empty() {
+                match plugin.try_extract(&probe_results) {
+                    Ok(components) => inventory.extend_capped(
+                        components
+                            .into_iter()
+                            .map(|component| add_qualifiers(component, &qualifiers)),
+                        plugin.name(),
+                        limit,
+                    ),
+                    Err(e) => errors.push(e),
                 }
-                Probe::WindowsRegistry(key) => {
-                    if cfg!(target_os = "windows") {
-                        #[cfg(target_os = "windows")]
-                        {
-                            use winreg::enums::*;
-                            use winreg::RegKey;
-
-                            let (root_key, subkey_path) = if key.starts_with("HKLM\\") {
-                                (
-                                    RegKey::predef(HKEY_LOCAL_MACHINE),
-                                    key.trim_start_matches("HKLM\\"),
-                                )
-                            } else if key.starts_with("HKCU\\") {
-                                (
-                                    RegKey::predef(HKEY_CURRENT_USER),
-                                    key.trim_start_matches("HKCU\\"),
-                                )
-                            } else {
-                                // Unknown root, skip or handle error? For now skip.
-                                (RegKey::predef(HKEY_LOCAL_MACHINE), "")
-                            };
-
-                            if !subkey_path.is_empty() {
-                                if let Ok(parent_key) = root_key.open_subkey(subkey_path) {
-                                    let mut entries = Vec::new();
-                                    for name in
-                                        parent_key.enum_keys().map(|x| x.unwrap_or_default())
-                                    {
-                                        if let Ok(subkey) = parent_key.open_subkey(&name) {
-                                            let display_name: Option<String> =
-                                                subkey.get_value("DisplayName").ok();
-                                            let display_version: Option<String> =
-                                                subkey.get_value("DisplayVersion").ok();
-                                            let publisher: Option<String> =
-                                                subkey.get_value("Publisher").ok();
-
-                                            if display_name.is_some() {
-                                                entries.push(RegistryEntry {
-                                                    display_name,
-                                                    display_version,
-                                                    publisher,
-                                                });
-                                            }
-                                        }
-                                    }
-
-                                    if !entries.is_empty() {
-                                        probe_results.push(ProbeResult {
-                                            probe: probe.clone(),
-                                            data: ProbeData::RegistryEntries(entries),
-                                        });
-                                    }
-                                }
-                            }
+            }
+
+            // Per-user installs, tagged with the owning user
+            for user in &user_homes {
+                if inventory.truncated || (options.fail_fast && !errors.is_empty()) {
+                    break;
+                }
+                let probe_results = run_probes(
+                    plugin.user_probes(&user.home),
+                    options,
+                    &mut errors,
+                    &mut warnings,
+                    &mut denied,
+                    &mut |probe, line| {
+                        if let Some(component) = plugin.extract_line(probe, line) {
+                            let component =
+                                tag_user(add_qualifiers(component, &qualifiers), &user.name);
+                            inventory.extend_capped([component], plugin.name(), limit);
                         }
+                        !inventory.truncated
+                    },
+                );
+                if !probe_results.is_empty() {
+                    match plugin.try_extract(&probe_results) {
+                        Ok(components) => inventory.extend_capped(
+                            components
+                                .into_iter()
+                                .map(|component| add_qualifiers(component, &qualifiers))
+                                .map(|component| tag_user(component, &user.name)),
+                            plugin.name(),
+                            limit,
+                        ),
+                        Err(e) => errors.push(e),
                     }
                 }
-                Probe::Command(cmd_str) => {
-                    let output = if cfg!(target_os = "windows") {
-                        Command::new("cmd").args(["/C", cmd_str]).output()
-                    } else {
-                        Command::new("sh").arg("-c").arg(cmd_str).output()
-                    };
-
-                    if let Ok(out) = output {
-                        if out.status.success() {
-                            probe_results.push(ProbeResult {
-                                probe: probe.clone(),
-                                data: ProbeData::CommandOutput(
-                                    String::from_utf8_lossy(&out.stdout).to_string(),
-                                ),
-                            });
+            }
+        });
+        errors.extend(panicked);
+        inventory
+            .skipped
+            .extend(denied.into_iter().map(|(probe, reason)| {
+                SkippedProbe {
+                    plugin: plugin.name().to_string(),
+                    command: match probe {
+                        Probe::Command(command) => command,
+                        Probe::Exec { program, args } | Probe::ExecLines { program, args } => {
+                            std::iter::once(program)
+                                .chain(args)
+                                .collect::<Vec<_>>()
+                                .join(" ")
                         }
-                    }
+                        probe => format!("{:?}", probe),
+                    },
+                    reason: reason.to_string(),
                 }
+            }));
+        inventory
+            .errors
+            .extend(errors.into_iter().map(|error| PluginError {
+                plugin: plugin.name().to_string(),
+                error,
+            }));
+        inventory
+            .warnings
+            .extend(warnings.into_iter().map(|error| PluginError {
+                plugin: plugin.name().to_string(),
+                error,
+            }));
+        if options.fail_fast && !inventory.errors.is_empty() {
+            break;
+        }
+    }
+    inventory
+}
+
+/// Runs `run`, returning the panic it caused, if any, as an `Error::Panic`.
+fn catch_panic(run: impl FnOnce()) -> Option<Error> {
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(run)).err()?;
+    Some(Error::Panic(
+        panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "no message".to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, run_probes, TempDir};
+
+    fn file_contents(path: &Path) -> Probe {
+        Probe::FileContents(FileLocation::AbsolutePath(
+            path.to_string_lossy().to_string(),
+        ))
+    }
+
+    #[test]
+    fn registry_entries_accept_mixed_value_types() {
+        use std::collections::HashMap;
+        use RegistryValue::{Dword, Qword, String as Sz};
+
+        let sz = |value: &str| Sz(value.to_string());
+        let subkeys: Vec<HashMap<&str, RegistryValue>> = vec![
+            HashMap::from([
+                ("DisplayName", sz("7-Zip 23.01 (x64)")),
+                ("DisplayVersion", sz("23.01")),
+                ("InstallDate", sz("20240131")),
+                ("EstimatedSize", Dword(5646)),
+                ("URLInfoAbout", sz("https://www.7-zip.org/")),
+                (
+                    "UninstallString",
+                    sz("\"C:\\Program Files\\7-Zip\\Uninstall.exe\""),
+                ),
+            ]),
+            HashMap::from([
+                ("DisplayName", sz("Contoso Agent")),
+                ("InstallDate", Dword(20231105)),
+                ("EstimatedSize", sz(" 1024 ")),
+            ]),
+            HashMap::from([
+                ("DisplayName", sz("Fabrikam Tools")),
+                ("InstallDate", Qword(20220301)),
+                ("EstimatedSize", Qword(1 << 33)),
+            ]),
+            HashMap::from([
+                ("DisplayName", sz("Broken Size")),
+                ("EstimatedSize", sz("n/a")),
+            ]),
+            // A KB update with no DisplayName
+            HashMap::from([("EstimatedSize", Dword(12))]),
+        ];
+
+        let entries: Vec<RegistryEntry> = subkeys
+            .iter()
+            .filter_map(|values| registry_entry(|name| values.get(name).cloned()))
+            .collect();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.display_name.as_deref().unwrap(),
+                    entry.install_date.as_deref(),
+                    entry.estimated_size,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("7-Zip 23.01 (x64)", Some("20240131"), Some(5646)),
+                ("Contoso Agent", Some("20231105"), Some(1024)),
+                ("Fabrikam Tools", Some("20220301"), Some(1 << 33)),
+                ("Broken Size", None, None),
+            ]
+        );
+        assert_eq!(entries[0].display_version.as_deref(), Some("23.01"));
+        assert_eq!(
+            entries[0].url_info_about.as_deref(),
+            Some("https://www.7-zip.org/")
+        );
+        assert_eq!(
+            entries[0].uninstall_string.as_deref(),
+            Some("\"C:\\Program Files\\7-Zip\\Uninstall.exe\"")
+        );
+        assert_eq!(entries[1].display_version, None);
+    }
+
+    #[test]
+    fn file_contents_probe_reads_file() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "etc/redhat-release",
+            "Red Hat Enterprise Linux release 9.4 (Plow)\n",
+        );
+
+        let results = run_probes(vec![
+            file_contents(&path),
+            file_contents(&dir.path().join("missing")),
+        ]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].probe, file_contents(&path));
+        match &results[0].data {
+            ProbeData::FileContents(contents) => {
+                assert_eq!(contents, "Red Hat Enterprise Linux release 9.4 (Plow)\n")
+            }
+            other => panic!("unexpected probe data {:?}", other),
+        }
+    }
+
+    #[test]
+    fn file_contents_probe_caps_read() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "huge.log",
+            vec![b'x'; MAX_FILE_CONTENTS_BYTES as usize + 100],
+        );
+
+        match &run_probes(vec![file_contents(&path)])[0].data {
+            ProbeData::FileContents(contents) => {
+                assert_eq!(contents.len() as u64, MAX_FILE_CONTENTS_BYTES)
+            }
+            other => panic!("unexpected probe data {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn truncates_command_output_over_the_cap() {
+        // `yes` writes forever, so this only returns if the command is killed
+        let probe = Probe::Exec {
+            program: "yes".to_string(),
+            args: vec!["hsnap".to_string()],
+        };
+        let options = PluginOptions {
+            max_command_output: Some(1000),
+            ..PluginOptions::default()
+        };
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        let results = super::run_probes(
+            vec![probe.clone()],
+            &options,
+            &mut errors,
+            &mut warnings,
+            &mut Vec::new(),
+            &mut |_, _| true,
+        );
+
+        assert!(errors.is_empty(), "probe errors: {:?}", errors);
+        // Left to the caller to report, not written to stderr
+        match &warnings[..] {
+            [Error::OutputTruncated {
+                probe: truncated,
+                max: 1000,
+            }] => {
+                assert_eq!(truncated, &probe)
+            }
+            other => panic!("unexpected warnings {:?}", other),
+        }
+        match &results[0].data {
+            ProbeData::CommandOutput { stdout, .. } => {
+                // Cut back to the last whole line under 1000 bytes
+                assert_eq!(stdout.len(), 996);
+                assert!(stdout.lines().all(|line| line == "hsnap"));
+                assert!(stdout.ends_with('\n'));
+            }
+            other => panic!("unexpected probe data {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streams_lines_without_a_cap() {
+        let options = PluginOptions {
+            max_command_output: Some(u64::MAX),
+            ..PluginOptions::default()
+        };
+        let mut lines = Vec::new();
+        let mut warnings = Vec::new();
+        super::run_probes(
+            vec![Probe::ExecLines {
+                program: "printf".to_string(),
+                args: vec!["bash\\ncurl\\n".to_string()],
+            }],
+            &options,
+            &mut Vec::new(),
+            &mut warnings,
+            &mut Vec::new(),
+            &mut |_, line| {
+                lines.push(line.to_string());
+                true
+            },
+        );
+
+        assert_eq!(lines, ["bash", "curl"]);
+        assert!(warnings.is_empty(), "warnings: {:?}", warnings);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streams_multi_megabyte_output_into_components() {
+        struct ListingPlugin;
+        impl Plugin for ListingPlugin {
+            fn name(&self) -> &str {
+                "listing"
+            }
+            fn supported_os(&self) -> Option<Vec<Os>> {
+                None
+            }
+            fn probes(&self) -> Vec<Probe> {
+                // About 5MB, one package a line
+                vec![Probe::ExecLines {
+                    program: "seq".to_string(),
+                    args: vec![
+                        "-f".to_string(),
+                        "package-%.0f|1.0".to_string(),
+                        "1".to_string(),
+                        "300000".to_string(),
+                    ],
+                }]
+            }
+            fn extract(&self, _found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+                Vec::new()
+            }
+            fn extract_line(&self, _probe: &Probe, line: &str) -> Option<SoftwareComponent> {
+                let (name, version) = line.split_once('|')?;
+                let mut purl = PackageUrl::new("generic", name.to_string()).ok()?;
+                purl.with_version(version.to_string());
+                Some(SoftwareComponent::Purl(purl))
+            }
+        }
+
+        let inventory = collect_from(
+            vec![Box::new(ListingPlugin)],
+            &PluginOptions::default(),
+            Inventory::default(),
+        );
+
+        assert!(inventory.errors.is_empty(), "{:?}", inventory.errors);
+        assert!(inventory.warnings.is_empty(), "{:?}", inventory.warnings);
+        assert!(!inventory.truncated);
+        assert_eq!(inventory.components.len(), 300_000);
+        assert_eq!(
+            purls(&[inventory.components[299_999].component.clone()]),
+            ["pkg:generic/package-300000@1.0"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn truncates_a_line_longer_than_the_cap() {
+        // A single line that never ends, so this only returns if it isn't buffered whole
+        let probe = Probe::ExecLines {
+            program: "cat".to_string(),
+            args: vec!["/dev/zero".to_string()],
+        };
+        let options = PluginOptions {
+            max_command_output: Some(1000),
+            ..PluginOptions::default()
+        };
+        let mut lines = 0;
+        let mut warnings = Vec::new();
+        super::run_probes(
+            vec![probe.clone()],
+            &options,
+            &mut Vec::new(),
+            &mut warnings,
+            &mut Vec::new(),
+            &mut |_, _| {
+                lines += 1;
+                true
+            },
+        );
+
+        assert_eq!(lines, 0);
+        match &warnings[..] {
+            [Error::OutputTruncated {
+                probe: truncated,
+                max: 1000,
+            }] => assert_eq!(truncated, &probe),
+            other => panic!("unexpected warnings {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_probe_captures_stderr() {
+        let results = run_probes(vec![Probe::Command(
+            "echo 'openjdk version \"21.0.2\" 2024-01-16' >&2".to_string(),
+        )]);
+        match &results[0].data {
+            ProbeData::CommandOutput { stdout, stderr } => {
+                assert_eq!(stdout, "");
+                assert_eq!(stderr, "openjdk version \"21.0.2\" 2024-01-16\n");
             }
+            other => panic!("unexpected probe data {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_probe_passes_each_arg_whole() {
+        let results = run_probes(vec![Probe::Exec {
+            program: "printf".to_string(),
+            args: vec![
+                "[%s]\\n".to_string(),
+                "two words".to_string(),
+                "it's; $HOME".to_string(),
+            ],
+        }]);
+        match &results[0].data {
+            ProbeData::CommandOutput { stdout, .. } => {
+                assert_eq!(stdout, "[two words]\n[it's; $HOME]\n")
+            }
+            other => panic!("unexpected probe data {:?}", other),
+        }
+    }
+
+    // Firefox's profile directory is elsewhere on other platforms
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tags_components_in_each_home_with_its_user() {
+        let root = TempDir::new();
+        root.write(
+            "etc/passwd",
+            "alice:x:1000:1000::/home/alice:/bin/bash\nbob:x:1001:1001::/home/bob:/bin/bash\n",
+        );
+        let profiles = Path::new(".mozilla/firefox/default");
+        let addon = |id: &str| {
+            format!(
+                r#"{{"addons": [{{"id": "{}", "version": "1.0", "type": "extension"}}]}}"#,
+                id
+            )
+        };
+        root.write(
+            &Path::new("home/alice")
+                .join(profiles)
+                .join("extensions.json")
+                .to_string_lossy(),
+            addon("alice-only@example.com"),
+        );
+        root.write(
+            &Path::new("home/bob")
+                .join(profiles)
+                .join("extensions.json")
+                .to_string_lossy(),
+            addon("bob-only@example.com"),
+        );
+        let options = PluginOptions {
+            root: Some(root.path().to_path_buf()),
+            component_kinds: vec!["browser-extension".to_string()],
+            ..PluginOptions::default()
+        };
+
+        let mut found: Vec<_> = run_plugins(&options)
+            .components
+            .into_iter()
+            .filter_map(|found| match found.component {
+                SoftwareComponent::BrowserExtension { id, user, .. } => Some((id, user)),
+                _ => None,
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                (
+                    "alice-only@example.com".to_string(),
+                    Some("alice".to_string())
+                ),
+                ("bob-only@example.com".to_string(), Some("bob".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn caps_components_and_marks_inventory_truncated() {
+        let root = TempDir::new();
+        let packages: String = (0..50)
+            .map(|i| format!("package-{} 1.{}\n", i, i))
+            .collect();
+        root.write("opt/acme/packages.txt", packages);
+        let plugins = TempDir::new();
+        plugins.write(
+            "acme.json",
+            r#"{
+                "name": "acme-packages",
+                "probes": [{ "file": "/opt/acme/packages.txt" }],
+                "component": { "pattern": "{name} {version}" }
+            }"#,
+        );
+        let options = |max_components| PluginOptions {
+            root: Some(root.path().to_path_buf()),
+            plugin_dirs: vec![plugins.path().to_path_buf()],
+            max_components,
+            ..PluginOptions::default()
+        };
+
+        let inventory = run_plugins(&options(None));
+        assert!(inventory.errors.is_empty(), "{:?}", inventory.errors);
+        assert_eq!(inventory.components.len(), 50);
+        assert!(!inventory.truncated);
+
+        let inventory = run_plugins(&options(Some(10)));
+        assert_eq!(inventory.components.len(), 10);
+        assert!(inventory.truncated);
+        assert!(inventory
+            .components
+            .iter()
+            .all(|found| found.source == "acme-packages"));
+    }
+
+    // The Debian plugin only runs on Linux
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn component_kinds_filter_skips_other_plugins() {
+        let root = TempDir::new();
+        root.write(
+            "var/lib/dpkg/status",
+            "Package: bash\nStatus: install ok installed\nVersion: 5.2.15-2\nArchitecture: amd64\n",
+        );
+        let sources = |kinds: &[&str]| -> Vec<String> {
+            let options = PluginOptions {
+                root: Some(root.path().to_path_buf()),
+                component_kinds: kinds.iter().map(|kind| kind.to_string()).collect(),
+                ..PluginOptions::default()
+            };
+            run_plugins(&options)
+                .components
+                .into_iter()
+                .map(|found| found.source)
+                .collect()
+        };
+
+        assert_eq!(sources(&["deb"]), ["debian-dpkg"]);
+        assert!(!sources(&["pypi"]).contains(&"debian-dpkg".to_string()));
+        assert!(sources(&[]).contains(&"debian-dpkg".to_string()));
+    }
+
+    // The Debian plugin only runs on Linux
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tags_debian_components_with_plugin_name() {
+        let root = TempDir::new();
+        root.write(
+            "var/lib/dpkg/status",
+            "Package: bash\nStatus: install ok installed\nVersion: 5.2.15-2\nArchitecture: amd64\n",
+        );
+        let options = PluginOptions {
+            root: Some(root.path().to_path_buf()),
+            ..PluginOptions::default()
+        };
+
+        let inventory = run_plugins(&options);
+        let bash = inventory
+            .components
+            .iter()
+            .find(|found| {
+                purls(std::slice::from_ref(&found.component))
+                    == ["pkg:deb/bash@5.2.15-2?arch=amd64"]
+            })
+            .expect("bash from the dpkg status");
+        assert_eq!(bash.source, "debian-dpkg");
+        let json = serde_json::to_value(bash).unwrap();
+        assert_eq!(json["source"], "debian-dpkg");
+        assert_eq!(json["details"]["name"], "bash");
+    }
+
+    // The Debian plugin only runs on Linux
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn applies_default_and_configured_qualifiers() {
+        let root = TempDir::new();
+        root.write("etc/os-release", "ID=debian\nVERSION_ID=\"12\"\n");
+        root.write(
+            "var/lib/dpkg/status",
+            "Package: bash\nStatus: install ok installed\nVersion: 5.2.15-2\nArchitecture: amd64\n",
+        );
+        let found = |extra_qualifiers: HashMap<String, Vec<(String, String)>>| {
+            let options = PluginOptions {
+                root: Some(root.path().to_path_buf()),
+                component_kinds: vec!["deb".to_string()],
+                extra_qualifiers,
+                ..PluginOptions::default()
+            };
+            let components: Vec<_> = run_plugins(&options)
+                .components
+                .into_iter()
+                .map(|found| found.component)
+                .collect();
+            purls(&components)
+        };
+
+        assert_eq!(
+            found(HashMap::new()),
+            ["pkg:deb/bash@5.2.15-2?arch=amd64&distro=debian-12"]
+        );
+        // Configured qualifiers take the place of the plugin's own
+        let configured = [
+            ("repository_url", "https://deb.debian.org/debian"),
+            ("distro", "debian-12.5"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(
+            found(HashMap::from([("debian-dpkg".to_string(), configured.to_vec())])),
+            ["pkg:deb/bash@5.2.15-2?arch=amd64&distro=debian-12.5&repository_url=https://deb.debian.org/debian"]
+        );
+        assert_eq!(
+            found(HashMap::from([("other".to_string(), configured.to_vec())])),
+            ["pkg:deb/bash@5.2.15-2?arch=amd64&distro=debian-12"]
+        );
+    }
+
+    /// Detects a product by its event log source, as a plugin would.
+    struct EventLogSourcePlugin;
+
+    impl Plugin for EventLogSourcePlugin {
+        fn name(&self) -> &str {
+            "event-log-test"
         }
 
-        if !probe_results.is_empty() {
-            let results = plugin.extract(&probe_results);
-            all_purls.extend(results);
+        fn supported_os(&self) -> Option<Vec<Os>> {
+            Some(vec![Os::Windows])
         }
+
+        fn probes(&self) -> Vec<Probe> {
+            vec![Probe::event_log_sources()]
+        }
+
+        fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+            found_probes
+                .iter()
+                .filter(|result| result.data.has_subkey("MSSQLSERVER"))
+                .filter_map(|_| PackageUrl::new("generic", "mssql-server").ok())
+                .map(SoftwareComponent::Purl)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn detects_software_by_event_log_source() {
+        let subkeys = |names: &[&str]| ProbeResult {
+            probe: Probe::event_log_sources(),
+            data: ProbeData::RegistrySubkeys(names.iter().map(|name| name.to_string()).collect()),
+        };
+
+        assert_eq!(
+            Probe::event_log_sources(),
+            Probe::WindowsRegistrySubkeys(
+                "HKLM\\SYSTEM\\CurrentControlSet\\Services\\EventLog\\Application".to_string()
+            )
+        );
+        assert_eq!(
+            purls(&EventLogSourcePlugin.extract(&[subkeys(&["Application Error", "MSSQLServer"])])),
+            ["pkg:generic/mssql-server"]
+        );
+        assert!(EventLogSourcePlugin
+            .extract(&[subkeys(&["Application Error", "Wininit"])])
+            .is_empty());
+    }
+
+    #[test]
+    fn tcp_probe_reports_whether_port_accepts() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        // Bound and released, so nothing listens on it
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let probe = |port| Probe::TcpConnect {
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+
+        let results = run_probes(vec![probe(open), probe(closed)]);
+        let found: Vec<_> = results
+            .iter()
+            .map(|result| match (&result.probe, &result.data) {
+                (Probe::TcpConnect { port, .. }, ProbeData::TcpOpen(open)) => (*port, *open),
+                _ => panic!("unexpected probe result"),
+            })
+            .collect();
+        assert_eq!(found, [(open, true), (closed, false)]);
+
+        // The ports belong to the live host, not a mounted root
+        let root = TempDir::new();
+        let options = PluginOptions {
+            root: Some(root.path().to_path_buf()),
+            ..PluginOptions::default()
+        };
+        let mut errors = Vec::new();
+        let results = crate::run_probes(
+            vec![probe(open)],
+            &options,
+            &mut errors,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut |_, _| true,
+        );
+        assert!(results.is_empty() && errors.is_empty());
+    }
+
+    /// Runs `probes`, returning the errors they caused.
+    #[cfg(unix)]
+    fn probe_errors(probes: Vec<Probe>) -> Vec<Error> {
+        let mut errors = Vec::new();
+        crate::run_probes(
+            probes,
+            &PluginOptions::default(),
+            &mut errors,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut |_, _| true,
+        );
+        errors
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_command_is_probe_error() {
+        let probe = Probe::Command("echo 'dpkg: database locked' >&2; exit 2".to_string());
+        let errors = probe_errors(vec![probe.clone()]);
+        match &errors[..] {
+            [error @ Error::Probe {
+                probe: failed,
+                message,
+            }] => {
+                assert_eq!(failed, &probe);
+                assert_eq!(message, "exit status: 2: dpkg: database locked");
+                assert!(error
+                    .to_string()
+                    .ends_with("failed: exit status: 2: dpkg: database locked"));
+            }
+            other => panic!("unexpected errors {:?}", other),
+        }
+        // Not installed isn't an error
+        assert!(probe_errors(vec![Probe::Exec {
+            program: "hsnap-no-such-program".to_string(),
+            args: Vec::new(),
+        }])
+        .is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_file_is_io_error() {
+        // A directory can't be read as a file
+        let dir = TempDir::new();
+        let errors = probe_errors(vec![file_contents(dir.path())]);
+        match &errors[..] {
+            [error @ Error::Io { path, .. }] => {
+                assert_eq!(path, dir.path());
+                assert!(std::error::Error::source(error).is_some());
+            }
+            other => panic!("unexpected errors {:?}", other),
+        }
+        // Missing isn't an error
+        assert!(probe_errors(vec![file_contents(&dir.path().join("missing"))]).is_empty());
+    }
+
+    #[test]
+    fn invalid_manifest_is_parse_error() {
+        let dir = TempDir::new();
+        let path = dir.write("broken.json", "{\"name\": ");
+        let options = PluginOptions {
+            plugin_dirs: vec![dir.path().to_path_buf()],
+            component_kinds: vec!["none".to_string()],
+            ..PluginOptions::default()
+        };
+
+        let error = try_run_plugins(&options).unwrap_err();
+        assert_eq!(error.plugin, "broken.json");
+        match &error.error {
+            Error::Parse { path: failed, .. } => assert_eq!(failed, &path),
+            other => panic!("unexpected error {:?}", other),
+        }
+        // Logged and left out, by the convenience run_plugins
+        assert_eq!(run_plugins(&options).errors.len(), 1);
+    }
+
+    #[test]
+    fn panic_is_panic_error() {
+        let error = catch_panic(|| panic!("index out of bounds")).unwrap();
+        assert!(matches!(&error, Error::Panic(message) if message == "index out of bounds"));
+        assert_eq!(error.to_string(), "panicked: index out of bounds");
+        let error = catch_panic(|| panic!("line {}", 3)).unwrap();
+        assert!(matches!(&error, Error::Panic(message) if message == "line 3"));
+        assert!(catch_panic(|| {}).is_none());
+    }
+
+    // An open file description lock conflicts with the test's own process, as a package
+    // manager's would
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn locked_database_is_busy_error() {
+        use nix::fcntl::{fcntl, FcntlArg};
+        use nix::libc;
+
+        struct LockedPlugin(String);
+        impl Plugin for LockedPlugin {
+            fn name(&self) -> &str {
+                "locked"
+            }
+            fn supported_os(&self) -> Option<Vec<Os>> {
+                None
+            }
+            fn ready(&self) -> Result<(), Error> {
+                locks::check_unlocked(&[&self.0], Duration::ZERO)
+            }
+            fn probes(&self) -> Vec<Probe> {
+                panic!("probes run despite the lock")
+            }
+            fn extract(&self, _found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+                Vec::new()
+            }
+        }
+
+        let dir = TempDir::new();
+        let path = dir.write("lock", "");
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = libc::F_WRLCK as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        fcntl(&file, FcntlArg::F_OFD_SETLK(&lock)).unwrap();
+
+        let plugin = LockedPlugin(path.to_string_lossy().to_string());
+        let inventory = collect_from(
+            vec![Box::new(plugin)],
+            &PluginOptions::default(),
+            Inventory::default(),
+        );
+        match &inventory.errors[..] {
+            [PluginError {
+                plugin,
+                error: error @ Error::Busy { lock },
+            }] => {
+                assert_eq!(plugin, "locked");
+                assert_eq!(lock, &path);
+                assert_eq!(
+                    error.to_string(),
+                    format!("package database busy ({} is locked)", path.display())
+                );
+            }
+            other => panic!("unexpected errors {:?}", other),
+        }
+        assert!(inventory.components.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn denied_command_never_spawns() {
+        let dir = TempDir::new();
+        let marker = dir.path().join("spawned");
+        let present = dir.write("present", "");
+        let probes = || {
+            vec![
+                // Not judged by its first word, which would let anything through
+                Probe::Command(format!("env touch {}", marker.display())),
+                Probe::Exec {
+                    program: "/usr/bin/touch".to_string(),
+                    args: vec![marker.to_string_lossy().to_string()],
+                },
+                Probe::File(FileLocation::AbsolutePath(
+                    present.to_string_lossy().to_string(),
+                )),
+            ]
+        };
+
+        for (policy, reasons) in [
+            (
+                CommandPolicy {
+                    allow: Some(Vec::new()),
+                    deny: Vec::new(),
+                },
+                ["commands are disabled"; 2],
+            ),
+            (
+                CommandPolicy {
+                    allow: None,
+                    deny: vec!["touch".to_string()],
+                },
+                [
+                    "shell commands can't be checked against the probe denylist",
+                    "in the probe denylist",
+                ],
+            ),
+            (
+                CommandPolicy {
+                    allow: Some(vec!["env".to_string(), "dpkg-query".to_string()]),
+                    deny: Vec::new(),
+                },
+                ["not in the probe allowlist"; 2],
+            ),
+        ] {
+            let options = PluginOptions {
+                command_policy: policy.clone(),
+                ..PluginOptions::default()
+            };
+            let mut errors = Vec::new();
+            let mut denied = Vec::new();
+            let results = crate::run_probes(
+                probes(),
+                &options,
+                &mut errors,
+                &mut Vec::new(),
+                &mut denied,
+                &mut |_, _| true,
+            );
+
+            assert!(!marker.exists(), "{:?} spawned touch", policy);
+            assert!(errors.is_empty(), "{:?}", errors);
+            let denied: Vec<_> = denied.into_iter().map(|(_, reason)| reason).collect();
+            assert_eq!(denied, reasons, "{:?}", policy);
+            // File probes still run
+            assert!(
+                matches!(&results[..], [ProbeResult { data: ProbeData::File(path), .. }] if path == &present)
+            );
+        }
+
+        // The control: allowed, it does run
+        let options = PluginOptions {
+            command_policy: CommandPolicy {
+                allow: Some(vec!["sh".to_string(), "touch".to_string()]),
+                deny: Vec::new(),
+            },
+            ..PluginOptions::default()
+        };
+        crate::run_probes(
+            probes(),
+            &options,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut |_, _| true,
+        );
+        assert!(marker.exists());
     }
-    all_purls
 }
@@ -0,0 +1,94 @@
+use crate::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a held lock is re-checked while waiting for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Waits up to `timeout` for none of `lock_files` to be write-locked, returning the
+/// path of a lock still held when it runs out. Package managers hold an `fcntl` write
+/// lock on these while changing their database, and queries run meanwhile can block
+/// or read it half written.
+pub fn wait_for_unlocked(lock_files: &[&str], timeout: Duration) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let held = lock_files
+            .iter()
+            .find(|path| is_write_locked(Path::new(path)))?;
+        if Instant::now() >= deadline {
+            return Some(held.to_string());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Waits as `wait_for_unlocked` does, failing with `Error::Busy` if a lock is still held.
+pub fn check_unlocked(lock_files: &[&str], timeout: Duration) -> Result<(), Error> {
+    match wait_for_unlocked(lock_files, timeout) {
+        Some(lock) => Err(Error::Busy {
+            lock: PathBuf::from(lock),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether another process holds a write lock on `path`. A lock file that is missing or
+/// can't be opened (e.g. when not running as root) is treated as unlocked.
+#[cfg(unix)]
+fn is_write_locked(path: &Path) -> bool {
+    use nix::fcntl::{fcntl, FcntlArg};
+    use nix::libc;
+
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+
+    // Ask whether a read lock could be taken: only a writer conflicts with it
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_RDLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    match fcntl(&file, FcntlArg::F_GETLK(&mut lock)) {
+        Ok(_) => lock.l_type != libc::F_UNLCK as libc::c_short,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_write_locked(_path: &Path) -> bool {
+    false
+}
+
+// Open file description locks, unlike the traditional ones, conflict with those of the
+// same process, so the test can hold one itself as a package manager would
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use nix::fcntl::{fcntl, FcntlArg};
+    use nix::libc;
+
+    #[test]
+    fn reports_held_lock_after_timeout() {
+        let dir = TempDir::new();
+        let path = dir.write("lock-frontend", "");
+        let lock_files = [path.to_str().unwrap()];
+        assert_eq!(
+            wait_for_unlocked(&lock_files, Duration::from_millis(100)),
+            None
+        );
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = libc::F_WRLCK as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        fcntl(&file, FcntlArg::F_OFD_SETLK(&lock)).unwrap();
+
+        let started = Instant::now();
+        assert_eq!(
+            wait_for_unlocked(&lock_files, Duration::from_millis(500)),
+            Some(path.to_string_lossy().to_string())
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,22 @@
+use crate::under_root;
+use std::path::Path;
+
+/// The distribution as `<ID>-<VERSION_ID>` from `/etc/os-release`, e.g. `debian-12` or
+/// `rhel-9.3`, as used by the purl `distro` qualifier. With a `root`, the os-release of
+/// the system mounted there is read.
+pub fn distro(root: Option<&Path>) -> Option<String> {
+    let read = |path: &str| std::fs::read_to_string(under_root(root, Path::new(path)));
+    let contents = read("/etc/os-release")
+        .or_else(|_| read("/usr/lib/os-release"))
+        .ok()?;
+    let field = |name: &str| {
+        contents.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix('=')?;
+            Some(value.trim().trim_matches('"').to_string())
+        })
+    };
+    match (field("ID")?, field("VERSION_ID")) {
+        (id, Some(version)) => Some(format!("{}-{}", id, version)),
+        (id, None) => Some(id),
+    }
+}
@@ -0,0 +1,344 @@
+use crate::{FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Detects Chrome/Chromium and Firefox extensions installed in each user's browser profiles.
+pub struct BrowserExtensionPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Browser {
+    Chrome,
+    Chromium,
+    Firefox,
+}
+
+impl Browser {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Browser::Chrome => "chrome",
+            Browser::Chromium => "chromium",
+            Browser::Firefox => "firefox",
+        }
+    }
+
+    /// Location of the browser's user data directory, relative to a home directory.
+    fn data_dir(&self) -> &'static str {
+        if cfg!(target_os = "windows") {
+            match self {
+                Browser::Chrome => "AppData\\Local\\Google\\Chrome\\User Data",
+                Browser::Chromium => "AppData\\Local\\Chromium\\User Data",
+                Browser::Firefox => "AppData\\Roaming\\Mozilla\\Firefox\\Profiles",
+            }
+        } else if cfg!(target_os = "macos") {
+            match self {
+                Browser::Chrome => "Library/Application Support/Google/Chrome",
+                Browser::Chromium => "Library/Application Support/Chromium",
+                Browser::Firefox => "Library/Application Support/Firefox/Profiles",
+            }
+        } else {
+            match self {
+                Browser::Chrome => ".config/google-chrome",
+                Browser::Chromium => ".config/chromium",
+                Browser::Firefox => ".mozilla/firefox",
+            }
+        }
+    }
+}
+
+const BROWSERS: [Browser; 3] = [Browser::Chrome, Browser::Chromium, Browser::Firefox];
+
+#[derive(Deserialize)]
+struct ChromeManifest {
+    name: String,
+    version: String,
+    default_locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChromeMessage {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct FirefoxExtensions {
+    #[serde(default)]
+    addons: Vec<FirefoxAddon>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FirefoxAddon {
+    id: String,
+    version: String,
+    #[serde(rename = "type")]
+    addon_type: Option<String>,
+    default_locale: Option<FirefoxLocale>,
+}
+
+#[derive(Deserialize)]
+struct FirefoxLocale {
+    name: Option<String>,
+}
+
+impl Plugin for BrowserExtensionPlugin {
+    fn name(&self) -> &str {
+        "browser-extensions"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["browser-extension"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        Vec::new()
+    }
+
+    fn user_probes(&self, home: &Path) -> Vec<Probe> {
+        BROWSERS
+            .iter()
+            .map(|browser| {
+                Probe::File(FileLocation::AbsolutePath(
+                    home.join(browser.data_dir()).to_string_lossy().to_string(),
+                ))
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut components = Vec::new();
+        for result in found_probes {
+            if let ProbeData::File(data_dir) = &result.data {
+                let browser = BROWSERS
+                    .iter()
+                    .find(|browser| data_dir.ends_with(browser.data_dir()));
+
+                // Profile files may be locked or mid-write by a running browser,
+                // so any unreadable or unparsable file is skipped.
+                for profile in subdirs(data_dir) {
+                    match browser {
+                        Some(Browser::Firefox) => {
+                            components.extend(firefox_extensions(&profile));
+                        }
+                        Some(browser) => {
+                            components.extend(chrome_extensions(*browser, &profile));
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+        components
+    }
+}
+
+fn subdirs(parent: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(parent)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Walks `<profile>/Extensions/<id>/<version>/manifest.json`.
+fn chrome_extensions(browser: Browser, profile: &Path) -> Vec<SoftwareComponent> {
+    let mut components = Vec::new();
+    for extension_dir in subdirs(&profile.join("Extensions")) {
+        let id = match extension_dir.file_name() {
+            Some(id) => id.to_string_lossy().to_string(),
+            None => continue,
+        };
+        for version_dir in subdirs(&extension_dir) {
+            let manifest = match std::fs::read_to_string(version_dir.join("manifest.json")) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+            if let Some((name, version)) = parse_chrome_manifest(&manifest, &version_dir) {
+                components.push(SoftwareComponent::BrowserExtension {
+                    browser: browser.as_str().to_string(),
+                    id: id.clone(),
+                    name,
+                    version,
+                    profile: profile.to_string_lossy().to_string(),
+                    user: None,
+                });
+            }
+        }
+    }
+    components
+}
+
+/// Returns the extension name and version from a Chrome `manifest.json`, resolving
+/// `__MSG_key__` names against the default locale's `messages.json`.
+fn parse_chrome_manifest(manifest: &str, extension_dir: &Path) -> Option<(String, String)> {
+    let manifest: ChromeManifest = serde_json::from_str(manifest).ok()?;
+
+    let name = match (
+        manifest
+            .name
+            .strip_prefix("__MSG_")
+            .and_then(|key| key.strip_suffix("__")),
+        &manifest.default_locale,
+    ) {
+        (Some(key), Some(locale)) => {
+            resolve_chrome_message(extension_dir, locale, key).unwrap_or(manifest.name)
+        }
+        _ => manifest.name,
+    };
+
+    Some((name, manifest.version))
+}
+
+fn resolve_chrome_message(extension_dir: &Path, locale: &str, key: &str) -> Option<String> {
+    let path = extension_dir
+        .join("_locales")
+        .join(locale)
+        .join("messages.json");
+    let messages: std::collections::HashMap<String, ChromeMessage> =
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+
+    // Message keys are case-insensitive
+    messages
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, message)| message.message)
+}
+
+/// Reads `<profile>/extensions.json`.
+fn firefox_extensions(profile: &Path) -> Vec<SoftwareComponent> {
+    let contents = match std::fs::read_to_string(profile.join("extensions.json")) {
+        Ok(contents) => contents,
+        Err(_) => return Vec::new(),
+    };
+    let extensions: FirefoxExtensions = match serde_json::from_str(&contents) {
+        Ok(extensions) => extensions,
+        Err(_) => return Vec::new(),
+    };
+
+    extensions
+        .addons
+        .into_iter()
+        .filter(|addon| addon.addon_type.as_deref() == Some("extension"))
+        .map(|addon| SoftwareComponent::BrowserExtension {
+            browser: Browser::Firefox.as_str().to_string(),
+            name: addon
+                .default_locale
+                .and_then(|locale| locale.name)
+                .unwrap_or_else(|| addon.id.clone()),
+            id: addon.id,
+            version: addon.version,
+            profile: profile.to_string_lossy().to_string(),
+            user: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{run_probes, TempDir};
+
+    #[test]
+    fn parses_chrome_manifest() {
+        let manifest = r#"{
+            "manifest_version": 3,
+            "name": "uBlock Origin Lite",
+            "version": "2024.6.10.1024",
+            "permissions": ["declarativeNetRequest"]
+        }"#;
+
+        assert_eq!(
+            parse_chrome_manifest(manifest, Path::new("/nonexistent")),
+            Some((
+                "uBlock Origin Lite".to_string(),
+                "2024.6.10.1024".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_chrome_manifest("{\"name\": \"x\"}", Path::new("/")),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_localized_chrome_name() {
+        let dir = TempDir::new();
+        dir.write(
+            "_locales/en/messages.json",
+            r#"{"appName": {"message": "Google Docs Offline"}}"#,
+        );
+        let manifest = r#"{"name": "__MSG_APPNAME__", "version": "1.7", "default_locale": "en"}"#;
+
+        assert_eq!(
+            parse_chrome_manifest(manifest, dir.path()),
+            Some(("Google Docs Offline".to_string(), "1.7".to_string()))
+        );
+    }
+
+    #[test]
+    fn finds_extensions_in_each_profile() {
+        let home = TempDir::new();
+        let chrome = Browser::Chrome.data_dir();
+        let firefox = Browser::Firefox.data_dir();
+        home.write(
+            &format!("{chrome}/Default/Extensions/abcdef/1.2.0_0/manifest.json"),
+            r#"{"name": "Dark Reader", "version": "1.2.0"}"#,
+        );
+        home.write(
+            &format!("{firefox}/x1y2.default-release/extensions.json"),
+            r#"{"addons": [
+                {"id": "uBlock0@raymondhill.net", "version": "1.58.0", "type": "extension",
+                 "defaultLocale": {"name": "uBlock Origin"}},
+                {"id": "default-theme@mozilla.org", "version": "1.3", "type": "theme"}
+            ]}"#,
+        );
+        // A profile being written to by a running browser
+        home.write(
+            &format!("{firefox}/locked.default/extensions.json"),
+            "{\"add",
+        );
+
+        let results = run_probes(BrowserExtensionPlugin.user_probes(home.path()));
+        let mut found: Vec<_> = BrowserExtensionPlugin
+            .extract(&results)
+            .into_iter()
+            .map(|component| match component {
+                SoftwareComponent::BrowserExtension {
+                    browser,
+                    id,
+                    name,
+                    version,
+                    ..
+                } => (browser, id, name, version),
+                other => panic!("unexpected component {:?}", other),
+            })
+            .collect();
+        found.sort();
+
+        let expected = [
+            ("chrome", "abcdef", "Dark Reader", "1.2.0"),
+            (
+                "firefox",
+                "uBlock0@raymondhill.net",
+                "uBlock Origin",
+                "1.58.0",
+            ),
+        ]
+        .map(|(browser, id, name, version)| {
+            (
+                browser.to_string(),
+                id.to_string(),
+                name.to_string(),
+                version.to_string(),
+            )
+        });
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use packageurl::PackageUrl;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Detects PHP dependencies from the `composer.lock` of each configured project root.
+pub struct ComposerPlugin {
+    pub roots: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct ComposerLock {
+    #[serde(default)]
+    packages: Vec<ComposerPackage>,
+}
+
+#[derive(Deserialize)]
+struct ComposerPackage {
+    name: String,
+    version: String,
+}
+
+impl Plugin for ComposerPlugin {
+    fn name(&self) -> &str {
+        "php-composer"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["composer"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        self.roots
+            .iter()
+            .map(|root| {
+                let lock = root.join("composer.lock").to_string_lossy().to_string();
+                if root.is_absolute() {
+                    Probe::File(FileLocation::AbsolutePath(lock))
+                } else {
+                    Probe::File(FileLocation::RelativePath(lock))
+                }
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut components = Vec::new();
+        for result in found_probes {
+            if let ProbeData::File(path) = &result.data {
+                if let Ok(contents) = std::fs::read_to_string(path) {
+                    components.extend(parse_composer_lock(&contents));
+                }
+            }
+        }
+        components
+    }
+}
+
+/// Parses the `packages` array of a `composer.lock` into `pkg:composer` purls.
+fn parse_composer_lock(contents: &str) -> Vec<SoftwareComponent> {
+    let lock: ComposerLock = match serde_json::from_str(contents) {
+        Ok(lock) => lock,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut components = Vec::new();
+    for package in lock.packages {
+        let (namespace, name) = match package.name.split_once('/') {
+            Some((namespace, name)) => (Some(namespace.to_string()), name.to_string()),
+            None => (None, package.name.clone()),
+        };
+
+        if let Ok(mut purl) = PackageUrl::new("composer".to_string(), name) {
+            if let Some(namespace) = namespace {
+                purl.with_namespace(namespace);
+            }
+            purl.with_version(package.version);
+            components.push(SoftwareComponent::Purl(purl));
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, run_plugin, TempDir};
+    use crate::PluginOptions;
+
+    #[test]
+    fn parses_composer_lock_packages() {
+        let lock = r#"{
+            "_readme": ["This file locks the dependencies of your project"],
+            "content-hash": "0f1e2d",
+            "packages": [
+                {"name": "monolog/monolog", "version": "2.9.1", "type": "library"},
+                {"name": "psr/log", "version": "3.0.0"},
+                {"name": "standalone", "version": "v1.0.0"}
+            ],
+            "packages-dev": [
+                {"name": "phpunit/phpunit", "version": "10.5.0"}
+            ]
+        }"#;
+
+        assert_eq!(
+            purls(&parse_composer_lock(lock)),
+            [
+                "pkg:composer/monolog/monolog@2.9.1",
+                "pkg:composer/psr/log@3.0.0",
+                "pkg:composer/standalone@v1.0.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_lock_has_no_packages() {
+        assert!(parse_composer_lock("{not json").is_empty());
+        assert!(parse_composer_lock("{}").is_empty());
+    }
+
+    #[test]
+    fn reads_the_lock_of_each_root() {
+        let dir = TempDir::new();
+        dir.write(
+            "app/composer.lock",
+            r#"{"packages": [{"name": "guzzlehttp/guzzle", "version": "7.8.1"}]}"#,
+        );
+        let plugin = ComposerPlugin {
+            roots: vec![dir.path().join("app"), dir.path().join("missing")],
+        };
+
+        assert_eq!(
+            purls(&run_plugin(&plugin, &PluginOptions::default())),
+            ["pkg:composer/guzzlehttp/guzzle@7.8.1"]
+        );
+    }
+}
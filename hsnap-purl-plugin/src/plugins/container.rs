@@ -0,0 +1,100 @@
+use crate::extract::{purls_from_lines, LinePattern};
+use crate::{Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use packageurl::PackageUrl;
+
+/// Container runtimes and the CLI that reports their version.
+const RUNTIMES: [&str; 4] = ["docker", "containerd", "podman", "crictl"];
+
+/// Detects installed container runtimes from their `--version` output.
+pub struct ContainerRuntimePlugin;
+
+impl Plugin for ContainerRuntimePlugin {
+    fn name(&self) -> &str {
+        "container-runtimes"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["container-runtime"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        RUNTIMES
+            .iter()
+            .map(|runtime| Probe::Exec {
+                program: runtime.to_string(),
+                args: vec!["--version".to_string()],
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut components = Vec::new();
+        for result in found_probes {
+            let (Probe::Exec { program, .. }, ProbeData::CommandOutput { stdout: output, .. }) =
+                (&result.probe, &result.data)
+            else {
+                continue;
+            };
+            let first_line = output.lines().next().unwrap_or_default();
+            // Runtimes are reported even when their version can't be found
+            let purl = purls_from_lines(
+                &LinePattern::FIRST_VERSION,
+                "generic",
+                Some(program),
+                first_line,
+            )
+            .pop()
+            .or_else(|| PackageUrl::new("generic".to_string(), program.clone()).ok());
+            components.extend(purl.map(SoftwareComponent::Purl));
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::purls;
+
+    fn version_output(program: &str, stdout: &str) -> ProbeResult {
+        ProbeResult {
+            probe: Probe::Exec {
+                program: program.to_string(),
+                args: vec!["--version".to_string()],
+            },
+            data: ProbeData::CommandOutput {
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_version_of_each_runtime() {
+        let found = ContainerRuntimePlugin.extract(&[
+            version_output("docker", "Docker version 24.0.7, build afdd53b\n"),
+            version_output(
+                "containerd",
+                "containerd containerd.io 1.6.26 3dd1e886e55dd695541fdcd67420c2888645a495\n",
+            ),
+            version_output("podman", "podman version 4.9.3\n"),
+            version_output("crictl", "crictl version v1.29.0\n"),
+            version_output("crictl", "\n"),
+        ]);
+
+        assert_eq!(
+            purls(&found),
+            [
+                "pkg:generic/docker@24.0.7",
+                "pkg:generic/containerd@1.6.26",
+                "pkg:generic/podman@4.9.3",
+                "pkg:generic/crictl@1.29.0",
+                "pkg:generic/crictl",
+            ]
+        );
+    }
+}
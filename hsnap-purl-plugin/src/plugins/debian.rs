@@ -1,7 +1,24 @@
-use crate::{Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use crate::{
+    locks, on_path, under_root, Error, FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult,
+    SoftwareComponent,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
 use packageurl::PackageUrl;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub struct DebianPlugin;
+pub struct DebianPlugin {
+    /// How long to wait for the package database lock before skipping.
+    pub lock_timeout: Duration,
+    /// Read the dpkg database of the system mounted here instead of the live one.
+    pub root: Option<PathBuf>,
+    /// Parse the status file rather than running dpkg-query.
+    pub no_shell: bool,
+}
+
+const LOCK_FILES: [&str; 2] = ["/var/lib/dpkg/lock-frontend", "/var/lib/dpkg/lock"];
+
+const STATUS_FILE: &str = "/var/lib/dpkg/status";
 
 impl Plugin for DebianPlugin {
     fn name(&self) -> &str {
@@ -12,31 +29,267 @@ impl Plugin for DebianPlugin {
         Some(vec![Os::Linux])
     }
 
+    fn component_kinds(&self) -> &[&str] {
+        &["deb"]
+    }
+
+    fn default_qualifiers(&self) -> Vec<(String, String)> {
+        crate::os_release::distro(self.root.as_deref())
+            .map(|distro| vec![("distro".to_string(), distro)])
+            .unwrap_or_default()
+    }
+
+    fn ready(&self) -> Result<(), Error> {
+        // A mounted root isn't in use, so there's no lock to wait for
+        match self.root {
+            Some(_) => Ok(()),
+            None => locks::check_unlocked(&LOCK_FILES, self.lock_timeout),
+        }
+    }
+
     fn probes(&self) -> Vec<Probe> {
-        vec![Probe::Command("dpkg-query -W -f='${Package}|${Version}|${Architecture}\\n'".to_string())]
+        // dpkg-query would read the host's database rather than the root's, and is missing
+        // from many minimal containers, so the status file is parsed instead
+        if self.root.is_some() || self.no_shell || !on_path("dpkg-query") {
+            return vec![Probe::File(FileLocation::AbsolutePath(
+                STATUS_FILE.to_string(),
+            ))];
+        }
+        // Streamed, as a host may have tens of thousands of packages
+        vec![Probe::ExecLines {
+            program: "dpkg-query".to_string(),
+            args: vec![
+                "-W".to_string(),
+                "-f=${Package}|${Version}|${Architecture}\n".to_string(),
+            ],
+        }]
     }
 
     fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        self.try_extract(found_probes).unwrap_or_default()
+    }
+
+    fn try_extract(&self, found_probes: &[ProbeResult]) -> Result<Vec<SoftwareComponent>, Error> {
         let mut components = Vec::new();
         for result in found_probes {
-            if let ProbeData::CommandOutput(output) = &result.data {
-                for line in output.lines() {
-                    let parts: Vec<&str> = line.split('|').collect();
-                    if parts.len() >= 3 {
-                        // Manually construct owned PackageUrl to ensure 'static lifetime
-                        // We avoid PackageUrl::new because it might infer specific lifetime from &str args
-                        // and we need to verify if it supports ownership transfer easily.
-                        // Struct instantiation is safer if fields are public.
-
-                         if let Ok(mut purl) = PackageUrl::new("deb".to_string(), parts[0].to_string()) {
-                            purl.with_version(parts[1].to_string());
-                            let _ = purl.add_qualifier("arch", parts[2].to_string());
-                            components.push(SoftwareComponent::Purl(purl));
-                        }
-                    }
-                }
+            if let ProbeData::File(path) = &result.data {
+                // An empty list would read as every package having been removed
+                let status = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+                components.extend(parse_status(&status).into_iter().filter_map(
+                    |(package, version, arch)| {
+                        component(self.root.as_deref(), package, version, arch)
+                    },
+                ));
             }
         }
-        components
+        Ok(components)
+    }
+
+    fn extract_line(&self, _probe: &Probe, line: &str) -> Option<SoftwareComponent> {
+        let parts: Vec<&str> = line.split('|').collect();
+        if parts.len() < 3 {
+            return None;
+        }
+        component(
+            self.root.as_deref(),
+            parts[0].to_string(),
+            parts[1].to_string(),
+            parts[2].to_string(),
+        )
+    }
+}
+
+fn component(
+    root: Option<&Path>,
+    package: String,
+    version: String,
+    arch: String,
+) -> Option<SoftwareComponent> {
+    let mut purl = PackageUrl::new("deb".to_string(), package.clone()).ok()?;
+    purl.with_version(version);
+    if let Some(installed_at) = install_time(root, &package, &arch) {
+        let _ = purl.add_qualifier(
+            "installed_at",
+            installed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+    }
+    let _ = purl.add_qualifier("arch", arch);
+    Some(SoftwareComponent::Purl(purl))
+}
+
+/// Parses the installed packages out of a dpkg `status` file: blank line separated stanzas
+/// of `Package:`, `Status:`, `Version:` and `Architecture:` fields, among others.
+fn parse_status(contents: &str) -> Vec<(String, String, String)> {
+    let mut packages = Vec::new();
+    for stanza in contents.split("\n\n") {
+        let field = |name: &str| {
+            stanza.lines().find_map(|line| {
+                let value = line.strip_prefix(name)?.strip_prefix(':')?;
+                Some(value.trim().to_string())
+            })
+        };
+        // Removed packages keep a stanza until purged, e.g. `Status: deinstall ok config-files`
+        if !field("Status").is_some_and(|status| status.ends_with(" installed")) {
+            continue;
+        }
+        if let (Some(package), Some(version), Some(arch)) =
+            (field("Package"), field("Version"), field("Architecture"))
+        {
+            packages.push((package, version, arch));
+        }
+    }
+    packages
+}
+
+/// dpkg keeps no install date, so use the mtime of the package's file list, which is
+/// rewritten whenever the package is installed or upgraded. Multi-arch packages name
+/// the list `<package>:<arch>.list`.
+fn install_time(root: Option<&Path>, package: &str, arch: &str) -> Option<DateTime<Utc>> {
+    let info = under_root(root, Path::new("/var/lib/dpkg/info"));
+    [
+        format!("{}:{}.list", package, arch),
+        format!("{}.list", package),
+    ]
+    .iter()
+    .find_map(|name| {
+        std::fs::metadata(info.join(name))
+            .and_then(|m| m.modified())
+            .ok()
+    })
+    .map(DateTime::<Utc>::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, TempDir};
+    use std::time::SystemTime;
+
+    #[test]
+    fn takes_install_time_from_file_list_mtime() {
+        let root = TempDir::new();
+        let installed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_564_800);
+        for name in ["libc6:amd64.list", "bash.list"] {
+            let list = root.write(&format!("var/lib/dpkg/info/{}", name), "/.\n");
+            std::fs::File::options()
+                .write(true)
+                .open(list)
+                .unwrap()
+                .set_modified(installed)
+                .unwrap();
+        }
+
+        let components = [
+            component(
+                Some(root.path()),
+                "libc6".into(),
+                "2.36-9".into(),
+                "amd64".into(),
+            ),
+            component(
+                Some(root.path()),
+                "bash".into(),
+                "5.2.15-2".into(),
+                "amd64".into(),
+            ),
+            component(
+                Some(root.path()),
+                "vim".into(),
+                "2:9.0".into(),
+                "amd64".into(),
+            ),
+        ]
+        .map(Option::unwrap);
+        assert_eq!(
+            purls(&components),
+            [
+                "pkg:deb/libc6@2.36-9?arch=amd64&installed_at=2024-05-01T12:00:00Z",
+                "pkg:deb/bash@5.2.15-2?arch=amd64&installed_at=2024-05-01T12:00:00Z",
+                "pkg:deb/vim@2:9.0?arch=amd64",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streamed_output_matches_buffered() {
+        let root = TempDir::new();
+        let plugin = DebianPlugin {
+            lock_timeout: Duration::ZERO,
+            root: Some(root.path().to_path_buf()),
+            no_shell: false,
+        };
+        let run = |probe: Probe, limit: usize| {
+            let mut errors = Vec::new();
+            let mut streamed = Vec::new();
+            let results = crate::run_probes(
+                vec![probe],
+                &crate::PluginOptions::default(),
+                &mut errors,
+                &mut Vec::new(),
+                &mut Vec::new(),
+                &mut |probe, line| {
+                    streamed.extend(plugin.extract_line(probe, line));
+                    streamed.len() < limit
+                },
+            );
+            assert!(errors.is_empty(), "probe errors: {:?}", errors);
+            (results, streamed)
+        };
+        let sh = |script: &str, lines: bool| {
+            let args = vec!["-c".to_string(), script.to_string()];
+            let program = "sh".to_string();
+            match lines {
+                true => Probe::ExecLines { program, args },
+                false => Probe::Exec { program, args },
+            }
+        };
+        // As `dpkg-query -W` lists a host with tens of thousands of packages
+        let listing = r#"awk 'BEGIN { for (i = 0; i < 50000; i++) printf "pkg%d|1.%d-1|amd64\n", i, i % 7 }'"#;
+
+        let (results, streamed) = run(sh(listing, true), usize::MAX);
+        // Nothing is kept of the output itself
+        assert!(results.is_empty());
+        let (results, _) = run(sh(listing, false), usize::MAX);
+        let ProbeData::CommandOutput { stdout, .. } = &results[0].data else {
+            panic!("unexpected probe data {:?}", results[0].data);
+        };
+        let buffered: Vec<_> = stdout
+            .lines()
+            .filter_map(|line| plugin.extract_line(&results[0].probe, line))
+            .collect();
+        assert_eq!(streamed.len(), 50000);
+        assert_eq!(purls(&streamed), purls(&buffered));
+        assert_eq!(
+            purls(&streamed[49999..]),
+            ["pkg:deb/pkg49999@1.5-1?arch=amd64"]
+        );
+
+        // Output that never ends is stopped once enough has been read
+        let (_, streamed) = run(sh("yes 'bash|5.2.15-2|amd64'", true), 1000);
+        assert_eq!(streamed.len(), 1000);
+    }
+
+    #[test]
+    fn unreadable_status_file_is_io_error() {
+        // A directory can't be read as a file
+        let root = TempDir::new();
+        let path = root.path().join("var/lib/dpkg/status");
+        std::fs::create_dir_all(&path).unwrap();
+        let plugin = DebianPlugin {
+            lock_timeout: Duration::ZERO,
+            root: Some(root.path().to_path_buf()),
+            no_shell: true,
+        };
+        let found = [ProbeResult {
+            probe: Probe::File(FileLocation::AbsolutePath(STATUS_FILE.to_string())),
+            data: ProbeData::File(path.clone()),
+        }];
+
+        match plugin.try_extract(&found) {
+            Err(Error::Io { path: failed, .. }) => assert_eq!(failed, path),
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(components) => panic!("read {} packages", components.len()),
+        }
     }
 }
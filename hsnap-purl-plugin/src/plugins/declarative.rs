@@ -0,0 +1,358 @@
+use crate::extract::{purls_from_lines, wildcard_match, LinePattern};
+use crate::{
+    under_root, Error, FileLocation, Os, Plugin, PluginError, Probe, ProbeData, ProbeResult,
+    SoftwareComponent,
+};
+use packageurl::PackageUrl;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+/// A plugin described by a JSON manifest rather than written in Rust, for detecting a
+/// product from a file or a command's output. For example:
+///
+/// ```json
+/// {
+///   "name": "acme-agent",
+///   "os": ["linux"],
+///   "probes": [{ "file": "/opt/acme/VERSION" }, { "exec": ["acme", "--version"] }],
+///   "component": { "name": "acme-agent", "version_prefix": "acme " }
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    /// `linux`, `windows` or `macos`. Runs everywhere if empty.
+    #[serde(default)]
+    pub os: Vec<String>,
+    pub probes: Vec<ManifestProbe>,
+    pub component: ComponentRule,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestProbe {
+    /// Read a file.
+    File(String),
+    /// Read every file matching a path with `*` and `?` wildcards, e.g. `/opt/acme-*/VERSION`.
+    Glob(String),
+    /// Run a shell command.
+    Command(String),
+    /// Run a program directly, given as the program and its arguments.
+    Exec(Vec<String>),
+}
+
+/// How a component is made of what a probe found.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentRule {
+    /// The purl type.
+    #[serde(rename = "type", default = "generic")]
+    pub ty: String,
+    pub namespace: Option<String>,
+    /// The component's name, unless `pattern` has a `{name}` field.
+    #[serde(default)]
+    pub name: String,
+    /// The version is what follows this on the first line starting with it, e.g.
+    /// `VERSION=`. Without one, the first non-empty line is the version.
+    pub version_prefix: Option<String>,
+    /// A template with `{name}`, `{version}` and `{arch}` fields, e.g. `{name} {version}`,
+    /// making a component of each line it matches, as `LinePattern::template` describes.
+    /// Takes the place of `version_prefix`.
+    pub pattern: Option<String>,
+}
+
+fn generic() -> String {
+    "generic".to_string()
+}
+
+/// Adapts a `Manifest` to the `Plugin` trait.
+pub struct DeclarativePlugin {
+    pub manifest: Manifest,
+    /// The manifest's `pattern`, parsed.
+    pub pattern: Option<LinePattern>,
+    /// Globs are expanded under this, as other probe paths are resolved under it. Command
+    /// probes are skipped when it is set.
+    pub root: Option<PathBuf>,
+}
+
+/// Loads every `*.json` manifest in `dir`. Manifests that can't be read or aren't valid are
+/// skipped, and returned as errors against their file name.
+pub fn load_dir(dir: &Path, root: Option<&Path>) -> (Vec<DeclarativePlugin>, Vec<PluginError>) {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect(),
+        Err(e) => {
+            let error = PluginError {
+                plugin: dir.display().to_string(),
+                error: Error::io(dir, e),
+            };
+            return (Vec::new(), vec![error]);
+        }
+    };
+    paths.sort();
+
+    let mut plugins = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        let loaded = std::fs::read_to_string(&path)
+            .map_err(|e| Error::io(&path, e))
+            .and_then(|contents| {
+                parse_manifest(&contents).map_err(|message| Error::Parse {
+                    path: path.clone(),
+                    message,
+                })
+            });
+        match loaded {
+            Ok((manifest, pattern)) => plugins.push(DeclarativePlugin {
+                manifest,
+                pattern,
+                root: root.map(Path::to_path_buf),
+            }),
+            Err(error) => errors.push(PluginError {
+                plugin: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                error,
+            }),
+        }
+    }
+    (plugins, errors)
+}
+
+fn parse_manifest(contents: &str) -> Result<(Manifest, Option<LinePattern>), String> {
+    let manifest: Manifest = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let pattern = match &manifest.component.pattern {
+        Some(pattern) => {
+            Some(LinePattern::template(pattern).map_err(|e| format!("invalid pattern: {}", e))?)
+        }
+        None => None,
+    };
+    let pattern_has_name = pattern.as_ref().is_some_and(LinePattern::has_name);
+    if manifest.name.is_empty() || (manifest.component.name.is_empty() && !pattern_has_name) {
+        return Err("name can't be empty".to_string());
+    }
+    if manifest.probes.is_empty() {
+        return Err("no probes".to_string());
+    }
+    if let Some(os) = manifest.os.iter().find(|os| parse_os(os).is_none()) {
+        return Err(format!(
+            "unknown os {:?}, expected linux, windows or macos",
+            os
+        ));
+    }
+    if manifest
+        .probes
+        .iter()
+        .any(|probe| matches!(probe, ManifestProbe::Exec(args) if args.is_empty()))
+    {
+        return Err("exec needs a program".to_string());
+    }
+    Ok((manifest, pattern))
+}
+
+fn parse_os(os: &str) -> Option<Os> {
+    match os {
+        "linux" => Some(Os::Linux),
+        "windows" => Some(Os::Windows),
+        "macos" => Some(Os::MacOS),
+        _ => None,
+    }
+}
+
+impl Plugin for DeclarativePlugin {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        if self.manifest.os.is_empty() {
+            return None;
+        }
+        Some(
+            self.manifest
+                .os
+                .iter()
+                .filter_map(|os| parse_os(os))
+                .collect(),
+        )
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        let file = |path: &str| {
+            if Path::new(path).is_absolute() {
+                Probe::FileContents(FileLocation::AbsolutePath(path.to_string()))
+            } else {
+                Probe::FileContents(FileLocation::RelativePath(path.to_string()))
+            }
+        };
+        let mut probes = Vec::new();
+        for probe in &self.manifest.probes {
+            match probe {
+                ManifestProbe::File(path) => probes.push(file(path)),
+                ManifestProbe::Glob(pattern) => probes.extend(
+                    expand_glob(pattern, self.root.as_deref())
+                        .iter()
+                        .map(|path| file(path)),
+                ),
+                // Commands would describe the live host, not the root
+                ManifestProbe::Command(_) | ManifestProbe::Exec(_) if self.root.is_some() => {}
+                ManifestProbe::Command(command) => probes.push(Probe::Command(command.clone())),
+                ManifestProbe::Exec(args) => probes.push(Probe::Exec {
+                    program: args[0].clone(),
+                    args: args[1..].to_vec(),
+                }),
+            }
+        }
+        probes
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let rule = &self.manifest.component;
+        let name = Some(rule.name.as_str()).filter(|name| !name.is_empty());
+        let mut purls: Vec<PackageUrl> = Vec::new();
+        for result in found_probes {
+            let text = match &result.data {
+                ProbeData::FileContents(contents) => contents,
+                // Some tools print their version to stderr
+                ProbeData::CommandOutput { stdout, stderr } if stdout.trim().is_empty() => stderr,
+                ProbeData::CommandOutput { stdout, .. } => stdout,
+                _ => continue,
+            };
+            let found = match &self.pattern {
+                Some(pattern) => purls_from_lines(pattern, &rule.ty, name, text),
+                None => find_version(text, rule.version_prefix.as_deref())
+                    .and_then(|version| {
+                        let mut purl = PackageUrl::new(rule.ty.clone(), rule.name.clone()).ok()?;
+                        purl.with_version(version);
+                        Some(purl)
+                    })
+                    .into_iter()
+                    .collect(),
+            };
+            for mut purl in found {
+                if let Some(namespace) = &rule.namespace {
+                    purl.with_namespace(namespace.clone());
+                }
+                // Several probes may well find the same component
+                if !purls
+                    .iter()
+                    .any(|seen| seen.to_string() == purl.to_string())
+                {
+                    purls.push(purl);
+                }
+            }
+        }
+        purls.into_iter().map(SoftwareComponent::Purl).collect()
+    }
+}
+
+fn find_version(text: &str, prefix: Option<&str>) -> Option<String> {
+    let version = match prefix {
+        Some(prefix) => text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(prefix))?,
+        None => text.lines().find(|line| !line.trim().is_empty())?,
+    };
+    let version = version.trim().trim_matches(|c| c == '"' || c == '\'');
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// The paths matching `pattern`, whose components may have `*` and `?` wildcards, as
+/// seen from `root`.
+fn expand_glob(pattern: &str, root: Option<&Path>) -> Vec<String> {
+    let pattern = Path::new(pattern);
+    let mut matches = vec![if pattern.is_absolute() {
+        PathBuf::new()
+    } else {
+        PathBuf::from(".")
+    }];
+    for component in pattern.components() {
+        let Component::Normal(name) = component else {
+            // The root or a Windows prefix, kept as is
+            matches.iter_mut().for_each(|path| path.push(component));
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if !name.contains(['*', '?']) {
+            matches.iter_mut().for_each(|path| path.push(&*name));
+            continue;
+        }
+        matches = matches
+            .iter()
+            .flat_map(|dir| {
+                let on_disk = if pattern.is_absolute() {
+                    under_root(root, dir)
+                } else {
+                    dir.clone()
+                };
+                let mut children: Vec<PathBuf> = std::fs::read_dir(on_disk)
+                    .map(|entries| {
+                        entries
+                            .filter_map(|entry| entry.ok())
+                            .filter(|entry| {
+                                wildcard_match(&name, &entry.file_name().to_string_lossy())
+                            })
+                            .map(|entry| dir.join(entry.file_name()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                children.sort();
+                children
+            })
+            .collect();
+    }
+    matches
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, run_plugin, TempDir};
+    use crate::PluginOptions;
+
+    #[test]
+    fn detects_product_from_version_file() {
+        let manifests = TempDir::new();
+        manifests.write(
+            "acme.json",
+            r#"{
+                "name": "acme-agent",
+                "probes": [{ "glob": "/opt/acme-*/VERSION" }, { "exec": ["acme", "--version"] }],
+                "component": { "namespace": "acme", "name": "acme-agent", "version_prefix": "VERSION=" }
+            }"#,
+        );
+        manifests.write("broken.json", r#"{ "name": "broken", "probes": [] }"#);
+        manifests.write("README.md", "not a manifest");
+        let root = TempDir::new();
+        root.write("opt/acme-2/VERSION", "# Acme agent\nVERSION=\"2.4.1\"\n");
+        root.write("opt/acme-3/VERSION", "VERSION=3.0.0-rc1\n");
+        root.write("opt/other/VERSION", "VERSION=9.9.9\n");
+
+        let (plugins, errors) = load_dir(manifests.path(), Some(root.path()));
+        assert_eq!(plugins.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].plugin, "broken.json");
+
+        let options = PluginOptions {
+            root: Some(root.path().to_path_buf()),
+            ..PluginOptions::default()
+        };
+        // The exec probe is skipped under a root
+        assert_eq!(plugins[0].probes().len(), 2);
+        assert_eq!(
+            purls(&run_plugin(&plugins[0], &options)),
+            [
+                "pkg:generic/acme/acme-agent@2.4.1",
+                "pkg:generic/acme/acme-agent@3.0.0-rc1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::extract::parse_csv_row;
+use crate::{Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use std::collections::HashMap;
+
+/// Lists each driver's Authenticode signature status (`Valid`, `NotSigned`, ...) and
+/// signer as CSV. Catalog signed drivers are checked against the system catalogs.
+const SIGNATURES_SCRIPT: &str = "Get-CimInstance Win32_SystemDriver | ForEach-Object { \
+    $signature = if ($_.PathName) { Get-AuthenticodeSignature -LiteralPath $_.PathName -ErrorAction SilentlyContinue }; \
+    [pscustomobject]@{ Name = $_.Name; Status = $signature.Status; Signer = $signature.SignerCertificate.Subject } \
+    } | ConvertTo-Csv -NoTypeInformation";
+
+/// Detects the installed kernel mode and file system drivers, with whether each is signed.
+pub struct WindowsDriverPlugin;
+
+impl Plugin for WindowsDriverPlugin {
+    fn name(&self) -> &str {
+        "windows-drivers"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        Some(vec![Os::Windows])
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["windows-driver"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        vec![
+            Probe::Exec {
+                program: "driverquery".to_string(),
+                args: vec!["/v".to_string(), "/fo".to_string(), "csv".to_string()],
+            },
+            Probe::Exec {
+                program: "powershell".to_string(),
+                args: vec![
+                    "-NoProfile".to_string(),
+                    "-NonInteractive".to_string(),
+                    "-Command".to_string(),
+                    SIGNATURES_SCRIPT.to_string(),
+                ],
+            },
+        ]
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let output = |name: &str| {
+            found_probes
+                .iter()
+                .find_map(|result| match (&result.probe, &result.data) {
+                    (Probe::Exec { program, .. }, ProbeData::CommandOutput { stdout, .. })
+                        if program == name =>
+                    {
+                        Some(stdout.as_str())
+                    }
+                    _ => None,
+                })
+        };
+        let signatures = output("powershell")
+            .map(parse_signatures)
+            .unwrap_or_default();
+        output("driverquery")
+            .map(|drivers| parse_driverquery(drivers, &signatures))
+            .unwrap_or_default()
+    }
+}
+
+/// Parses `driverquery /v /fo csv`. Its column names and values are localized, so the
+/// columns are taken by position: Module Name, Display Name, Description, Driver Type,
+/// Start Mode, State, Status, Accept Stop, Accept Pause, Paged Pool, Code, BSS, Link
+/// Date, Path and Init.
+fn parse_driverquery(
+    output: &str,
+    signatures: &HashMap<String, (Option<bool>, Option<String>)>,
+) -> Vec<SoftwareComponent> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let row = parse_csv_row(line);
+            let field = |i: usize| row.get(i).map(|value| value.trim().to_string());
+            let name = field(0).filter(|name| !name.is_empty())?;
+            let (signed, signer) = signatures
+                .get(&name.to_lowercase())
+                .cloned()
+                .unwrap_or_default();
+            Some(SoftwareComponent::WindowsDriver {
+                display_name: field(1).unwrap_or_default(),
+                driver_type: field(3).unwrap_or_default(),
+                start_mode: field(4).unwrap_or_default(),
+                state: field(5).unwrap_or_default(),
+                path: field(13).filter(|path| !path.is_empty()),
+                signed,
+                signer,
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Parses the `Name`, `Status` and `Signer` rows of `SIGNATURES_SCRIPT`, keyed by the
+/// lowercased driver name. Drivers whose file couldn't be checked have no status.
+fn parse_signatures(output: &str) -> HashMap<String, (Option<bool>, Option<String>)> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let row = parse_csv_row(line);
+            let name = row.first()?.to_lowercase();
+            let signed = match row.get(1).map(|status| status.trim()) {
+                None | Some("") => None,
+                Some(status) => Some(status == "Valid"),
+            };
+            let signer = row
+                .get(2)
+                .map(|signer| signer.trim().to_string())
+                .filter(|signer| !signer.is_empty());
+            Some((name, (signed, signer)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(program: &str, stdout: &str) -> ProbeResult {
+        ProbeResult {
+            probe: Probe::Exec {
+                program: program.to_string(),
+                args: Vec::new(),
+            },
+            data: ProbeData::CommandOutput {
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_driverquery_csv() {
+        let driverquery = "\"Module Name\",\"Display Name\",\"Description\",\"Driver Type\",\"Start Mode\",\"State\",\"Status\",\"Accept Stop\",\"Accept Pause\",\"Paged Pool(bytes)\",\"Code(bytes)\",\"BSS(bytes)\",\"Link Date\",\"Path\",\"Init(bytes)\"\r\n\
+            \"ACPI\",\"Microsoft ACPI Driver\",\"Microsoft ACPI Driver\",\"Kernel \",\"Boot\",\"Running\",\"OK\",\"TRUE\",\"FALSE\",\"0\",\"0\",\"0\",\"\",\"C:\\Windows\\system32\\drivers\\ACPI.sys\",\"0\"\r\n\
+            \"Ntfs\",\"Ntfs\",\"Ntfs\",\"File System \",\"Disabled\",\"Running\",\"OK\",\"TRUE\",\"FALSE\",\"0\",\"0\",\"0\",\"\",\"C:\\Windows\\system32\\drivers\\Ntfs.sys\",\"0\"\r\n\
+            \"acmefilt\",\"Acme Filter, \"\"Legacy\"\"\",\"Acme\",\"Kernel \",\"Manual\",\"Stopped\",\"OK\",\"FALSE\",\"FALSE\",\"0\",\"0\",\"0\",\"\",\"\",\"0\"\r\n";
+        let signatures = "\"Name\",\"Status\",\"Signer\"\r\n\
+            \"ACPI\",\"Valid\",\"CN=Microsoft Windows, O=Microsoft Corporation, L=Redmond, S=Washington, C=US\"\r\n\
+            \"acmefilt\",\"NotSigned\",\"\"\r\n";
+
+        let drivers = WindowsDriverPlugin.extract(&[
+            output("driverquery", driverquery),
+            output("powershell", signatures),
+        ]);
+        let drivers = serde_json::to_value(drivers).unwrap();
+        assert_eq!(
+            drivers,
+            serde_json::json!([
+                {
+                    "type": "WindowsDriver",
+                    "details": {
+                        "name": "ACPI",
+                        "display_name": "Microsoft ACPI Driver",
+                        "driver_type": "Kernel",
+                        "start_mode": "Boot",
+                        "state": "Running",
+                        "path": "C:\\Windows\\system32\\drivers\\ACPI.sys",
+                        "signed": true,
+                        "signer": "CN=Microsoft Windows, O=Microsoft Corporation, L=Redmond, S=Washington, C=US"
+                    }
+                },
+                {
+                    "type": "WindowsDriver",
+                    "details": {
+                        "name": "Ntfs",
+                        "display_name": "Ntfs",
+                        "driver_type": "File System",
+                        "start_mode": "Disabled",
+                        "state": "Running",
+                        "path": "C:\\Windows\\system32\\drivers\\Ntfs.sys"
+                    }
+                },
+                {
+                    "type": "WindowsDriver",
+                    "details": {
+                        "name": "acmefilt",
+                        "display_name": "Acme Filter, \"Legacy\"",
+                        "driver_type": "Kernel",
+                        "start_mode": "Manual",
+                        "state": "Stopped",
+                        "signed": false
+                    }
+                }
+            ])
+        );
+    }
+}
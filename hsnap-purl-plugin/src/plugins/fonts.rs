@@ -0,0 +1,447 @@
+use crate::{FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use packageurl::PackageUrl;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// How many directories deep under a font directory to look for fonts.
+const MAX_DEPTH: usize = 4;
+
+/// Upper bound on the size of a `name` table read, far above that of any real font.
+const MAX_NAME_TABLE_BYTES: u32 = 1024 * 1024;
+
+/// Upper bound on the fonts read from one collection (`.ttc`) file.
+const MAX_COLLECTION_FONTS: u32 = 256;
+
+/// The name IDs of the `name` table this reads.
+const NAME_FAMILY: u16 = 1;
+const NAME_VERSION: u16 = 5;
+/// The family the font belongs to when its family name (ID 1) is narrowed to one of at
+/// most four styles, e.g. `Noto Sans` for `Noto Sans Light`.
+const NAME_TYPOGRAPHIC_FAMILY: u16 = 16;
+
+const PLATFORM_UNICODE: u16 = 0;
+const PLATFORM_MACINTOSH: u16 = 1;
+const PLATFORM_WINDOWS: u16 = 3;
+const LANGUAGE_WINDOWS_EN_US: u16 = 0x409;
+
+/// Detects the fonts installed system-wide and in each user's font directory, from the
+/// family name and version in each TrueType and OpenType font's `name` table.
+pub struct FontPlugin;
+
+/// The system-wide font directories.
+fn font_dirs() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &["C:\\Windows\\Fonts"]
+    } else if cfg!(target_os = "macos") {
+        &["/Library/Fonts", "/System/Library/Fonts"]
+    } else {
+        &["/usr/share/fonts", "/usr/local/share/fonts"]
+    }
+}
+
+/// The font directories, relative to a home directory.
+fn user_font_dirs() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &["AppData\\Local\\Microsoft\\Windows\\Fonts"]
+    } else if cfg!(target_os = "macos") {
+        &["Library/Fonts"]
+    } else {
+        &[".fonts", ".local/share/fonts"]
+    }
+}
+
+impl Plugin for FontPlugin {
+    fn name(&self) -> &str {
+        "fonts"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["font"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        font_dirs()
+            .iter()
+            .map(|dir| Probe::File(FileLocation::AbsolutePath(dir.to_string())))
+            .collect()
+    }
+
+    fn user_probes(&self, home: &Path) -> Vec<Probe> {
+        user_font_dirs()
+            .iter()
+            .map(|dir| {
+                Probe::File(FileLocation::AbsolutePath(
+                    home.join(dir).to_string_lossy().to_string(),
+                ))
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut files = Vec::new();
+        for result in found_probes {
+            if let ProbeData::File(dir) = &result.data {
+                find_fonts(dir, 0, &mut files);
+            }
+        }
+
+        // A family comes as a file per style, and may be installed in several places
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for file in files {
+            for name in read_font_names(&file) {
+                let Some(family) = name.family else {
+                    continue;
+                };
+                if !seen.insert((family.clone(), name.version.clone())) {
+                    continue;
+                }
+                let Ok(mut purl) = PackageUrl::new("generic".to_string(), family) else {
+                    continue;
+                };
+                let _ = purl.with_namespace("font");
+                if let Some(version) = name.version {
+                    purl.with_version(version);
+                }
+                components.push(SoftwareComponent::Purl(purl));
+            }
+        }
+        components
+    }
+}
+
+/// Collects the font files (`.ttf`, `.otf` and `.ttc`) at or below `dir`. Symlinked
+/// directories aren't followed.
+fn find_fonts(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    // So the first of several copies is the same on every run
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            if depth < MAX_DEPTH {
+                find_fonts(&path, depth + 1, files);
+            }
+        } else if is_font_file(&path) && path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["ttf", "otf", "ttc"]
+                .iter()
+                .any(|font| extension.eq_ignore_ascii_case(font))
+        })
+}
+
+/// The names read from a font's `name` table.
+#[derive(Debug, Default, PartialEq)]
+struct FontName {
+    family: Option<String>,
+    /// e.g. `2.37` for a version string of `Version 2.37; ttfautohint (v1.8)`.
+    version: Option<String>,
+}
+
+/// The names of each font in a font file, or of each font in a collection. Unreadable and
+/// malformed fonts have none.
+fn read_font_names(path: &Path) -> Vec<FontName> {
+    let Ok(mut file) = File::open(path) else {
+        return Vec::new();
+    };
+    let Some(header) = read_at(&mut file, 0, 12) else {
+        return Vec::new();
+    };
+    if &header[..4] != b"ttcf" {
+        return read_font_name(&mut file, 0).into_iter().collect();
+    }
+    let count = be_u32(&header, 8).unwrap_or(0).min(MAX_COLLECTION_FONTS);
+    let Some(offsets) = read_at(&mut file, 12, count as usize * 4) else {
+        return Vec::new();
+    };
+    offsets
+        .chunks_exact(4)
+        .filter_map(|offset| read_font_name(&mut file, be_u32(offset, 0)?.into()))
+        .collect()
+}
+
+/// Reads the `name` table of the font whose table directory starts at `offset`.
+fn read_font_name(file: &mut File, offset: u64) -> Option<FontName> {
+    let header = read_at(file, offset, 12)?;
+    let tables = be_u16(&header, 4)?;
+    let directory = read_at(file, offset + 12, usize::from(tables) * 16)?;
+    let record = directory
+        .chunks_exact(16)
+        .find(|record| &record[..4] == b"name")?;
+    let length = be_u32(record, 12)?;
+    if length > MAX_NAME_TABLE_BYTES {
+        return None;
+    }
+    let table = read_at(file, be_u32(record, 8)?.into(), length as usize)?;
+    parse_name_table(&table)
+}
+
+/// Parses a `name` table: a header, a record per name giving its platform, encoding,
+/// language and ID, and the strings they point to.
+///
+/// ```text
+/// u16 format, u16 count, u16 storage offset
+/// count x (u16 platform, u16 encoding, u16 language, u16 name ID, u16 length, u16 offset)
+/// ```
+fn parse_name_table(table: &[u8]) -> Option<FontName> {
+    let count = be_u16(table, 2)?;
+    let storage = usize::from(be_u16(table, 4)?);
+    // The best-ranked string found so far for each name
+    let mut family: Option<(u8, String)> = None;
+    let mut typographic_family: Option<(u8, String)> = None;
+    let mut version: Option<(u8, String)> = None;
+    for index in 0..usize::from(count) {
+        let record = table.get(6 + index * 12..6 + (index + 1) * 12)?;
+        let [platform, encoding, language, name_id, length, offset] =
+            [0, 2, 4, 6, 8, 10].map(|at| be_u16(record, at).unwrap_or_default());
+        let best = match name_id {
+            NAME_FAMILY => &mut family,
+            NAME_TYPOGRAPHIC_FAMILY => &mut typographic_family,
+            NAME_VERSION => &mut version,
+            _ => continue,
+        };
+        let Some(rank) = rank(platform, encoding, language) else {
+            continue;
+        };
+        if best.as_ref().is_some_and(|(best, _)| *best <= rank) {
+            continue;
+        }
+        let start = storage + usize::from(offset);
+        let Some(bytes) = table.get(start..start + usize::from(length)) else {
+            continue;
+        };
+        if let Some(text) = decode(platform, bytes).filter(|text| !text.is_empty()) {
+            *best = Some((rank, text));
+        }
+    }
+    let family = typographic_family.or(family).map(|(_, family)| family);
+    let version = version.and_then(|(_, version)| parse_version(&version));
+    Some(FontName { family, version })
+}
+
+/// How good a source of a name a record is, lowest first: Windows' US English, then
+/// Windows' other languages, then Unicode, then Macintosh Roman in English. `None` for
+/// encodings this can't decode.
+fn rank(platform: u16, encoding: u16, language: u16) -> Option<u8> {
+    match (platform, encoding, language) {
+        (PLATFORM_WINDOWS, 0 | 1 | 10, LANGUAGE_WINDOWS_EN_US) => Some(0),
+        (PLATFORM_WINDOWS, 0 | 1 | 10, _) => Some(1),
+        (PLATFORM_UNICODE, _, _) => Some(2),
+        (PLATFORM_MACINTOSH, 0, 0) => Some(3),
+        _ => None,
+    }
+}
+
+/// Windows and Unicode names are UTF-16BE. Macintosh Roman names are only taken when they
+/// are ASCII, which it shares.
+fn decode(platform: u16, bytes: &[u8]) -> Option<String> {
+    let text = if platform == PLATFORM_MACINTOSH {
+        bytes
+            .is_ascii()
+            .then(|| String::from_utf8_lossy(bytes).to_string())?
+    } else {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16(&units).ok()?
+    };
+    Some(text.trim().to_string())
+}
+
+/// The version number of a version string, which the spec asks to start with `Version `
+/// but is often followed by build notes, e.g. `Version 2.37; ttfautohint (v1.8)`.
+fn parse_version(version: &str) -> Option<String> {
+    let version = version.trim();
+    let version = match version.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("version") => &version[7..],
+        _ => version,
+    };
+    version
+        .split(|c: char| c == ';' || c.is_whitespace())
+        .find(|part| !part.is_empty())
+        .map(str::to_string)
+}
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> Option<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut buffer = vec![0; length];
+    file.read_exact(&mut buffer).ok()?;
+    Some(buffer)
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, TempDir};
+
+    /// A `name` table holding `names`, as (platform, encoding, language, name ID, text).
+    fn name_table(names: &[(u16, u16, u16, u16, &str)]) -> Vec<u8> {
+        let mut records = Vec::new();
+        let mut storage = Vec::new();
+        for &(platform, encoding, language, name_id, text) in names {
+            let bytes: Vec<u8> = if platform == PLATFORM_MACINTOSH {
+                text.as_bytes().to_vec()
+            } else {
+                text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+            };
+            for field in [platform, encoding, language, name_id, bytes.len() as u16] {
+                records.extend(field.to_be_bytes());
+            }
+            records.extend((storage.len() as u16).to_be_bytes());
+            storage.extend(bytes);
+        }
+        let mut table = Vec::new();
+        table.extend(0u16.to_be_bytes());
+        table.extend((names.len() as u16).to_be_bytes());
+        table.extend((6 + records.len() as u16).to_be_bytes());
+        table.extend(records);
+        table.extend(storage);
+        table
+    }
+
+    /// A TrueType font file whose only table is `name`.
+    fn font_file(name: &[u8]) -> Vec<u8> {
+        let mut font = Vec::new();
+        font.extend(0x0001_0000u32.to_be_bytes());
+        font.extend(1u16.to_be_bytes());
+        font.extend([0; 6]);
+        font.extend(b"name");
+        font.extend(0u32.to_be_bytes());
+        font.extend(28u32.to_be_bytes());
+        font.extend((name.len() as u32).to_be_bytes());
+        font.extend(name);
+        font
+    }
+
+    #[test]
+    fn parses_sample_name_table() {
+        let table = name_table(&[
+            (PLATFORM_MACINTOSH, 0, 0, NAME_FAMILY, "Mac Family"),
+            (PLATFORM_WINDOWS, 1, 0x407, NAME_FAMILY, "Deutsche Familie"),
+            (
+                PLATFORM_WINDOWS,
+                1,
+                LANGUAGE_WINDOWS_EN_US,
+                NAME_FAMILY,
+                "Noto Sans Light",
+            ),
+            (
+                PLATFORM_WINDOWS,
+                1,
+                LANGUAGE_WINDOWS_EN_US,
+                4,
+                "Noto Sans Light Italic",
+            ),
+            (
+                PLATFORM_WINDOWS,
+                1,
+                LANGUAGE_WINDOWS_EN_US,
+                NAME_VERSION,
+                "Version 2.013; ttfautohint (v1.8.4)",
+            ),
+            (
+                PLATFORM_MACINTOSH,
+                0,
+                0,
+                NAME_TYPOGRAPHIC_FAMILY,
+                "Noto Sans",
+            ),
+        ]);
+
+        assert_eq!(
+            parse_name_table(&table),
+            Some(FontName {
+                family: Some("Noto Sans".to_string()),
+                version: Some("2.013".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_name_table(&name_table(&[
+                (PLATFORM_MACINTOSH, 0, 0, NAME_FAMILY, "DejaVu Sans"),
+                (PLATFORM_UNICODE, 3, 0, NAME_FAMILY, "DejaVu Sans Unicode"),
+            ])),
+            Some(FontName {
+                family: Some("DejaVu Sans Unicode".to_string()),
+                version: None,
+            })
+        );
+        assert_eq!(parse_name_table(&table[..4]), None);
+    }
+
+    #[test]
+    fn reports_each_family_and_version_once() {
+        let dir = TempDir::new();
+        let font = |family: &str, version: &str| {
+            font_file(&name_table(&[
+                (
+                    PLATFORM_WINDOWS,
+                    1,
+                    LANGUAGE_WINDOWS_EN_US,
+                    NAME_FAMILY,
+                    family,
+                ),
+                (
+                    PLATFORM_WINDOWS,
+                    1,
+                    LANGUAGE_WINDOWS_EN_US,
+                    NAME_VERSION,
+                    version,
+                ),
+            ]))
+        };
+        dir.write(
+            "truetype/noto/NotoSans-Regular.ttf",
+            font("Noto Sans", "Version 2.013"),
+        );
+        dir.write(
+            "truetype/noto/NotoSans-Bold.TTF",
+            font("Noto Sans", "Version 2.013"),
+        );
+        dir.write(
+            "opentype/NotoSans-Old.otf",
+            font("Noto Sans", "Version 2.001"),
+        );
+        dir.write("truetype/Broken.ttf", b"not a font");
+        dir.write("truetype/README.txt", font("Not Scanned", "Version 1.0"));
+
+        let found = [ProbeResult {
+            probe: Probe::File(FileLocation::AbsolutePath(
+                dir.path().to_string_lossy().to_string(),
+            )),
+            data: ProbeData::File(dir.path().to_path_buf()),
+        }];
+        assert_eq!(
+            purls(&FontPlugin.extract(&found)),
+            [
+                "pkg:generic/font/Noto%20Sans@2.001",
+                "pkg:generic/font/Noto%20Sans@2.013",
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::extract::{purls_from_lines, LinePattern};
+use crate::{FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use packageurl::PackageUrl;
+use serde::Deserialize;
+
+/// Node components that report their version with `--version`, e.g. `Kubernetes v1.29.2`.
+const NODE_COMPONENTS: [&str; 2] = ["kubelet", "kube-proxy"];
+/// Where the container runtime looks for CNI network configurations.
+const CNI_CONFIG_DIR: &str = "/etc/cni/net.d";
+
+/// Detects the Kubernetes node components: the kubelet and kube-proxy, the container
+/// runtime serving the kubelet (as `crictl version` reports it) and the CNI plugins the
+/// network configurations use. Hosts that aren't nodes have none of them.
+pub struct K8sNodePlugin;
+
+impl Plugin for K8sNodePlugin {
+    fn name(&self) -> &str {
+        "kubernetes-node"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        Some(vec![Os::Linux])
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["kubernetes-node"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        let mut probes: Vec<Probe> = NODE_COMPONENTS
+            .iter()
+            .map(|program| Probe::Exec {
+                program: program.to_string(),
+                args: vec!["--version".to_string()],
+            })
+            .collect();
+        probes.push(Probe::Exec {
+            program: "crictl".to_string(),
+            args: vec!["version".to_string()],
+        });
+        probes.extend(
+            cni_configs()
+                .into_iter()
+                .map(|path| Probe::FileContents(FileLocation::AbsolutePath(path))),
+        );
+        probes
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut components = Vec::new();
+        for result in found_probes {
+            match (&result.probe, &result.data) {
+                (Probe::Exec { program, .. }, ProbeData::CommandOutput { stdout, .. })
+                    if program == "crictl" =>
+                {
+                    components.extend(parse_crictl_version(stdout));
+                }
+                (Probe::Exec { program, .. }, ProbeData::CommandOutput { stdout, .. }) => {
+                    let first_line = stdout.lines().next().unwrap_or_default();
+                    components.extend(
+                        purls_from_lines(
+                            &LinePattern::FIRST_VERSION,
+                            "generic",
+                            Some(program),
+                            first_line,
+                        )
+                        .pop(),
+                    );
+                }
+                (Probe::FileContents(_), ProbeData::FileContents(contents)) => {
+                    components.extend(parse_cni_config(contents));
+                }
+                _ => {}
+            }
+        }
+        components
+            .into_iter()
+            .map(SoftwareComponent::Purl)
+            .collect()
+    }
+}
+
+/// The network configurations in `/etc/cni/net.d`, in the order the runtime reads them.
+fn cni_configs() -> Vec<String> {
+    let mut paths: Vec<String> = std::fs::read_dir(CNI_CONFIG_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext == "conf" || ext == "conflist" || ext == "json")
+                })
+                .filter_map(|path| path.to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// The runtime from `crictl version`, whose output looks like:
+///
+/// ```text
+/// Version:  0.1.0
+/// RuntimeName:  containerd
+/// RuntimeVersion:  v1.7.2
+/// RuntimeApiVersion:  v1
+/// ```
+fn parse_crictl_version(output: &str) -> Option<PackageUrl<'static>> {
+    let field = |name: &str| {
+        output.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    let name = field("RuntimeName").filter(|name| !name.is_empty())?;
+    let mut purl = PackageUrl::new("generic".to_string(), name).ok()?;
+    if let Some(version) = field("RuntimeVersion").filter(|version| !version.is_empty()) {
+        purl.with_version(version.trim_start_matches('v').to_string());
+    }
+    let _ = purl.add_qualifier("role", "cri");
+    Some(purl)
+}
+
+/// A CNI network configuration: a single plugin (`.conf`) or a list of them (`.conflist`).
+#[derive(Deserialize)]
+struct CniConfig {
+    name: Option<String>,
+    #[serde(rename = "cniVersion")]
+    cni_version: Option<String>,
+    #[serde(rename = "type")]
+    plugin_type: Option<String>,
+    #[serde(default)]
+    plugins: Vec<CniPlugin>,
+}
+
+#[derive(Deserialize)]
+struct CniPlugin {
+    #[serde(rename = "type")]
+    plugin_type: Option<String>,
+}
+
+/// A component for each plugin a network configuration uses, e.g.
+/// `pkg:generic/cni/calico?cni_version=0.3.1&network=k8s-pod-network`. The configuration
+/// only names the plugins, so they have no version.
+fn parse_cni_config(contents: &str) -> Vec<PackageUrl<'static>> {
+    let Ok(config) = serde_json::from_str::<CniConfig>(contents) else {
+        return Vec::new();
+    };
+    config
+        .plugin_type
+        .iter()
+        .chain(
+            config
+                .plugins
+                .iter()
+                .filter_map(|plugin| plugin.plugin_type.as_ref()),
+        )
+        .filter_map(|plugin_type| {
+            let mut purl = PackageUrl::new("generic".to_string(), plugin_type.clone()).ok()?;
+            purl.with_namespace("cni".to_string());
+            if let Some(cni_version) = &config.cni_version {
+                let _ = purl.add_qualifier("cni_version", cni_version.clone());
+            }
+            if let Some(network) = &config.name {
+                let _ = purl.add_qualifier("network", network.clone());
+            }
+            Some(purl)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::purls;
+
+    fn output(program: &str, stdout: &str) -> ProbeResult {
+        ProbeResult {
+            probe: Probe::Exec {
+                program: program.to_string(),
+                args: Vec::new(),
+            },
+            data: ProbeData::CommandOutput {
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_node_component_versions() {
+        let crictl = "Version:  0.1.0\nRuntimeName:  containerd\nRuntimeVersion:  v1.7.2\nRuntimeApiVersion:  v1\n";
+        let conflist = r#"{
+            "name": "k8s-pod-network",
+            "cniVersion": "0.3.1",
+            "plugins": [{"type": "calico"}, {"type": "portmap"}]
+        }"#;
+
+        let components = K8sNodePlugin.extract(&[
+            output("kubelet", "Kubernetes v1.29.2\n"),
+            output("kube-proxy", "Kubernetes v1.29.2\n"),
+            output("crictl", crictl),
+            ProbeResult {
+                probe: Probe::FileContents(FileLocation::AbsolutePath(
+                    "/etc/cni/net.d/10-calico.conflist".to_string(),
+                )),
+                data: ProbeData::FileContents(conflist.to_string()),
+            },
+        ]);
+        assert_eq!(
+            purls(&components),
+            [
+                "pkg:generic/kubelet@1.29.2",
+                "pkg:generic/kube-proxy@1.29.2",
+                "pkg:generic/containerd@1.7.2?role=cri",
+                "pkg:generic/cni/calico?cni_version=0.3.1&network=k8s-pod-network",
+                "pkg:generic/cni/portmap?cni_version=0.3.1&network=k8s-pod-network",
+            ]
+        );
+        // Not a node
+        assert!(K8sNodePlugin.extract(&[]).is_empty());
+    }
+}
@@ -1,7 +1,25 @@
 pub mod windows;
+pub mod drivers;
 pub mod rhel;
 pub mod debian;
+pub mod composer;
+pub mod browser;
+pub mod container;
+pub mod python;
+pub mod runtime;
+pub mod kubernetes;
+pub mod fonts;
+pub mod declarative;
 
 pub use windows::WindowsRegistryPlugin;
+pub use drivers::WindowsDriverPlugin;
 pub use rhel::RhelPlugin;
 pub use debian::DebianPlugin;
+pub use composer::ComposerPlugin;
+pub use browser::BrowserExtensionPlugin;
+pub use container::ContainerRuntimePlugin;
+pub use python::PythonVenvPlugin;
+pub use runtime::RuntimePlugin;
+pub use kubernetes::K8sNodePlugin;
+pub use fonts::FontPlugin;
+pub use declarative::DeclarativePlugin;
@@ -0,0 +1,231 @@
+use crate::{FileLocation, Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use packageurl::PackageUrl;
+use std::path::{Path, PathBuf};
+
+/// Where pipx keeps its venvs, relative to a home directory. Older versions used the first.
+const PIPX_VENVS: [&str; 2] = [".local/pipx/venvs", ".local/share/pipx/venvs"];
+
+/// How many directories deep under a root to look for virtualenvs.
+const MAX_DEPTH: usize = 4;
+
+/// Detects Python packages installed in virtualenvs under each configured root, and in
+/// each user's pipx venvs.
+pub struct PythonVenvPlugin {
+    pub roots: Vec<PathBuf>,
+}
+
+impl Plugin for PythonVenvPlugin {
+    fn name(&self) -> &str {
+        "python-venv"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["pypi"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        self.roots
+            .iter()
+            .map(|root| {
+                let root = root.to_string_lossy().to_string();
+                if Path::new(&root).is_absolute() {
+                    Probe::File(FileLocation::AbsolutePath(root))
+                } else {
+                    Probe::File(FileLocation::RelativePath(root))
+                }
+            })
+            .collect()
+    }
+
+    fn user_probes(&self, home: &Path) -> Vec<Probe> {
+        PIPX_VENVS
+            .iter()
+            .map(|venvs| {
+                Probe::File(FileLocation::AbsolutePath(
+                    home.join(venvs).to_string_lossy().to_string(),
+                ))
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut venvs = Vec::new();
+        for result in found_probes {
+            if let ProbeData::File(root) = &result.data {
+                find_venvs(root, 0, &mut venvs);
+            }
+        }
+
+        let mut components = Vec::new();
+        for venv in venvs {
+            for site_packages in site_packages_dirs(&venv) {
+                for dist_info in subdirs(&site_packages) {
+                    if !dist_info.to_string_lossy().ends_with(".dist-info") {
+                        continue;
+                    }
+                    let metadata = match std::fs::read_to_string(dist_info.join("METADATA")) {
+                        Ok(metadata) => metadata,
+                        Err(_) => continue,
+                    };
+                    let Some((name, version)) = parse_metadata(&metadata) else {
+                        continue;
+                    };
+                    if let Ok(mut purl) = PackageUrl::new("pypi".to_string(), normalize(&name)) {
+                        purl.with_version(version);
+                        let _ = purl.add_qualifier("venv", venv.to_string_lossy().to_string());
+                        components.push(SoftwareComponent::Purl(purl));
+                    }
+                }
+            }
+        }
+        components
+    }
+}
+
+/// Collects every virtualenv (a directory holding a `pyvenv.cfg`) at or below `dir`.
+/// Virtualenvs aren't searched for nested ones, and symlinks aren't followed.
+fn find_venvs(dir: &Path, depth: usize, venvs: &mut Vec<PathBuf>) {
+    if dir.join("pyvenv.cfg").is_file() {
+        if !venvs.iter().any(|venv| venv == dir) {
+            venvs.push(dir.to_path_buf());
+        }
+        return;
+    }
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            find_venvs(&entry.path(), depth + 1, venvs);
+        }
+    }
+}
+
+/// `lib/python3.X/site-packages` on Unix, `Lib/site-packages` on Windows.
+fn site_packages_dirs(venv: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = ["lib", "lib64"]
+        .iter()
+        .flat_map(|lib| subdirs(&venv.join(lib)))
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("python"))
+        })
+        .map(|dir| dir.join("site-packages"))
+        .chain([venv.join("Lib").join("site-packages")])
+        .filter(|dir| dir.is_dir())
+        .collect();
+    // lib64 is often a link to lib
+    dirs.sort();
+    dirs.dedup_by(|a, b| a.canonicalize().ok() == b.canonicalize().ok());
+    dirs
+}
+
+fn subdirs(parent: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(parent)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `Name` and `Version` of a distribution's core metadata, which uses email-style
+/// headers ending at the first blank line.
+fn parse_metadata(metadata: &str) -> Option<(String, String)> {
+    let headers = metadata.lines().take_while(|line| !line.trim().is_empty());
+    let (mut name, mut version) = (None, None);
+    for line in headers {
+        if let Some((key, value)) = line.split_once(':') {
+            match key.trim() {
+                "Name" => name = Some(value.trim().to_string()),
+                "Version" => version = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    Some((name?, version?))
+}
+
+/// Normalizes a project name as PyPI and the purl spec do: lowercase, with runs of `-`,
+/// `_` and `.` replaced by a single `-`.
+fn normalize(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.extend(c.to_lowercase());
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{purls, run_plugin, TempDir};
+    use crate::PluginOptions;
+
+    const METADATA: &str = "Metadata-Version: 2.1
+Name: Flask_SQLAlchemy
+Version: 3.1.1
+Summary: Add SQLAlchemy support to your Flask application.
+Requires-Python: >=3.8
+Requires-Dist: flask>=2.2.5
+
+Name: not a header once the description starts
+";
+
+    #[test]
+    fn parses_dist_info_metadata() {
+        assert_eq!(
+            parse_metadata(METADATA),
+            Some(("Flask_SQLAlchemy".to_string(), "3.1.1".to_string()))
+        );
+        assert_eq!(normalize("Flask_SQLAlchemy"), "flask-sqlalchemy");
+        assert_eq!(
+            parse_metadata("Metadata-Version: 2.1\nName: requests\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_packages_in_virtualenvs_under_root() {
+        let dir = TempDir::new();
+        dir.write("apps/web/.venv/pyvenv.cfg", "home = /usr/bin\n");
+        dir.write(
+            "apps/web/.venv/lib/python3.12/site-packages/Flask_SQLAlchemy-3.1.1.dist-info/METADATA",
+            METADATA,
+        );
+        // Not a virtualenv, so not looked in
+        dir.write(
+            "apps/other/lib/python3.12/site-packages/six-1.16.0.dist-info/METADATA",
+            "Name: six\nVersion: 1.16.0\n",
+        );
+
+        let plugin = PythonVenvPlugin {
+            roots: vec![dir.path().join("apps")],
+        };
+        let components = run_plugin(&plugin, &PluginOptions::default());
+        let venv = dir.path().join("apps/web/.venv");
+        let expected = PackageUrl::new("pypi", "flask-sqlalchemy")
+            .unwrap()
+            .with_version("3.1.1")
+            .add_qualifier("venv", venv.to_string_lossy().to_string())
+            .unwrap()
+            .to_string();
+        assert_eq!(purls(&components), vec![expected]);
+    }
+}
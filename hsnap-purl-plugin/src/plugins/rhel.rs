@@ -1,7 +1,22 @@
-use crate::{Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+use crate::{locks, on_path, Error, Os, Plugin, Probe, ProbeResult, SoftwareComponent};
+use chrono::{DateTime, SecondsFormat};
 use packageurl::PackageUrl;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub struct RhelPlugin;
+pub struct RhelPlugin {
+    /// How long to wait for the package database lock before skipping.
+    pub lock_timeout: Duration,
+    /// Query the rpm database of the system mounted here instead of the live one.
+    pub root: Option<PathBuf>,
+    /// Don't run rpm, and so collect nothing.
+    pub no_shell: bool,
+}
+
+/// Separates the query format fields. Unlike `|`, it can't appear in a package's summary.
+const FIELD_SEPARATOR: char = '\x1f';
+
+const LOCK_FILES: [&str; 2] = ["/var/lib/rpm/.rpm.lock", "/usr/lib/sysimage/rpm/.rpm.lock"];
 
 impl Plugin for RhelPlugin {
     fn name(&self) -> &str {
@@ -12,26 +27,143 @@ impl Plugin for RhelPlugin {
         Some(vec![Os::Linux])
     }
 
+    fn component_kinds(&self) -> &[&str] {
+        &["rpm"]
+    }
+
+    fn default_qualifiers(&self) -> Vec<(String, String)> {
+        crate::os_release::distro(self.root.as_deref())
+            .map(|distro| vec![("distro".to_string(), distro)])
+            .unwrap_or_default()
+    }
+
+    fn ready(&self) -> Result<(), Error> {
+        // A mounted root isn't in use, so there's no lock to wait for
+        match self.root {
+            Some(_) => Ok(()),
+            None => locks::check_unlocked(&LOCK_FILES, self.lock_timeout),
+        }
+    }
+
     fn probes(&self) -> Vec<Probe> {
-        vec![Probe::Command("rpm -qa --qf '%{NAME}|%{VERSION}|%{RELEASE}|%{ARCH}\\n'".to_string())]
-    }
-
-    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
-        let mut components = Vec::new();
-        for result in found_probes {
-            if let ProbeData::CommandOutput(output) = &result.data {
-                for line in output.lines() {
-                    let parts: Vec<&str> = line.split('|').collect();
-                    if parts.len() >= 4 {
-                        if let Ok(mut purl) = PackageUrl::new("rpm".to_string(), parts[0].to_string()) {
-                            purl.with_version(format!("{}-{}", parts[1], parts[2]));
-                            let _ = purl.add_qualifier("arch", parts[3].to_string());
-                            components.push(SoftwareComponent::Purl(purl));
-                        }
-                    }
-                }
-            }
+        // Unlike dpkg's status file, rpm's BerkeleyDB and sqlite databases are only read
+        // through rpm, so without it no packages are collected
+        if self.no_shell || !on_path("rpm") {
+            return Vec::new();
+        }
+        let mut args = Vec::new();
+        if let Some(root) = &self.root {
+            args.extend(["--root".to_string(), root.to_string_lossy().to_string()]);
         }
-        components
+        args.extend(["-qa".to_string(), "--qf".to_string(), "%{NAME}\x1f%{VERSION}\x1f%{RELEASE}\x1f%{ARCH}\x1f%{INSTALLTIME}\x1f%{VENDOR}\x1f%{SUMMARY}\n".to_string()]);
+        // Streamed, as a host may have tens of thousands of packages
+        vec![Probe::ExecLines {
+            program: "rpm".to_string(),
+            args,
+        }]
+    }
+
+    fn extract(&self, _found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        Vec::new()
+    }
+
+    fn extract_line(&self, _probe: &Probe, line: &str) -> Option<SoftwareComponent> {
+        parse_line(line)
+    }
+}
+
+/// Builds the component of a line of `rpm -qa --qf` output.
+fn parse_line(line: &str) -> Option<SoftwareComponent> {
+    let parts: Vec<&str> = line.split(FIELD_SEPARATOR).collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let mut purl = PackageUrl::new("rpm".to_string(), parts[0].to_string()).ok()?;
+    purl.with_version(format!("{}-{}", parts[1], parts[2]));
+    let _ = purl.add_qualifier("arch", parts[3].to_string());
+    // INSTALLTIME is seconds since the epoch
+    if let Some(installed_at) = parts
+        .get(4)
+        .and_then(|t| t.parse::<i64>().ok())
+        .and_then(|t| DateTime::from_timestamp(t, 0))
+    {
+        let _ = purl.add_qualifier(
+            "installed_at",
+            installed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+    }
+    // rpm prints (none) for unset tags
+    if let Some(vendor) = parts.get(5).filter(|v| !v.is_empty() && **v != "(none)") {
+        let _ = purl.add_qualifier("vendor", vendor.to_string());
+    }
+    // The summary (parts[6]) is free text describing the package, not part of its
+    // identity, so it's left out of the purl
+    Some(SoftwareComponent::Purl(purl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::purls;
+
+    fn line(fields: &[&str]) -> String {
+        fields.join(&FIELD_SEPARATOR.to_string())
+    }
+
+    #[test]
+    fn maps_installtime_to_iso_timestamp() {
+        let component = parse_line(&line(&[
+            "bash",
+            "5.1.8",
+            "9.el9",
+            "x86_64",
+            "1714564800",
+            "Red Hat, Inc.",
+            "(none)",
+        ]));
+
+        assert_eq!(
+            purls(&[component.unwrap()]),
+            ["pkg:rpm/bash@5.1.8-9.el9?arch=x86_64&installed_at=2024-05-01T12:00:00Z&vendor=Red%20Hat,%20Inc."]
+        );
+    }
+
+    #[test]
+    fn leaves_out_unset_installtime() {
+        let component = parse_line(&line(&["tzdata", "2024a", "1.el9", "noarch", "(none)"]));
+
+        assert_eq!(
+            purls(&[component.unwrap()]),
+            ["pkg:rpm/tzdata@2024a-1.el9?arch=noarch"]
+        );
+    }
+
+    #[test]
+    fn keeps_pipe_in_summary_out_of_purl() {
+        let component = parse_line(&line(&[
+            "perl-IO-Tty",
+            "1.16",
+            "4.el9",
+            "x86_64",
+            "(none)",
+            "Red Hat, Inc.",
+            "Perl interface to pseudo tty's | pty helpers",
+        ]));
+
+        // The pipe doesn't split the fields, and the summary isn't a qualifier
+        assert_eq!(
+            purls(&[component.unwrap()]),
+            ["pkg:rpm/perl-IO-Tty@1.16-4.el9?arch=x86_64&vendor=Red%20Hat,%20Inc."]
+        );
+    }
+
+    #[test]
+    fn collects_nothing_without_rpm() {
+        let plugin = RhelPlugin {
+            lock_timeout: Duration::ZERO,
+            root: None,
+            no_shell: true,
+        };
+        assert!(plugin.probes().is_empty());
     }
 }
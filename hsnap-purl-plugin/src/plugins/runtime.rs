@@ -0,0 +1,117 @@
+use crate::extract::{purls_from_lines, LinePattern};
+use crate::{Os, Plugin, Probe, ProbeData, ProbeResult, SoftwareComponent};
+
+/// Language runtimes, as `(name, program, version argument)`.
+const RUNTIMES: [(&str, &str, &str); 6] = [
+    ("python", "python3", "--version"),
+    ("node", "node", "--version"),
+    ("go", "go", "version"),
+    ("ruby", "ruby", "--version"),
+    ("php", "php", "--version"),
+    ("java", "java", "-version"),
+];
+
+/// Detects installed language runtimes from their version output.
+pub struct RuntimePlugin;
+
+impl Plugin for RuntimePlugin {
+    fn name(&self) -> &str {
+        "language-runtimes"
+    }
+
+    fn supported_os(&self) -> Option<Vec<Os>> {
+        None
+    }
+
+    fn component_kinds(&self) -> &[&str] {
+        &["language-runtime"]
+    }
+
+    fn probes(&self) -> Vec<Probe> {
+        RUNTIMES
+            .iter()
+            .map(|(_, program, arg)| Probe::Exec {
+                program: program.to_string(),
+                args: vec![arg.to_string()],
+            })
+            .collect()
+    }
+
+    fn extract(&self, found_probes: &[ProbeResult]) -> Vec<SoftwareComponent> {
+        let mut components = Vec::new();
+        for result in found_probes {
+            let (Probe::Exec { program, .. }, ProbeData::CommandOutput { stdout, stderr }) =
+                (&result.probe, &result.data)
+            else {
+                continue;
+            };
+            let Some(name) = RUNTIMES
+                .iter()
+                .find(|(_, runtime, _)| runtime == program)
+                .map(|(name, _, _)| *name)
+            else {
+                continue;
+            };
+            // The version is on the first line: `Python 3.12.1`, `v20.10.0`,
+            // `go version go1.21.5 linux/amd64`, `ruby 3.2.2 (2023-03-30 revision e51014f9c0)`,
+            // `PHP 8.2.7 (cli) ...` or java's `openjdk version "17.0.8" 2023-07-18`.
+            // java (and Python before 3.4) write it to stderr
+            let purl = [stdout, stderr].into_iter().find_map(|output| {
+                let first_line = output.lines().next()?;
+                purls_from_lines(
+                    &LinePattern::FIRST_VERSION,
+                    "generic",
+                    Some(name),
+                    first_line,
+                )
+                .pop()
+            });
+            components.extend(purl.map(SoftwareComponent::Purl));
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::purls;
+
+    fn version_output(program: &str, arg: &str, stdout: &str, stderr: &str) -> ProbeResult {
+        ProbeResult {
+            probe: Probe::Exec {
+                program: program.to_string(),
+                args: vec![arg.to_string()],
+            },
+            data: ProbeData::CommandOutput {
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_java_version_from_stderr() {
+        let results = [
+            version_output(
+                "java",
+                "-version",
+                "",
+                "openjdk version \"17.0.8\" 2023-07-18\n\
+                 OpenJDK Runtime Environment (build 17.0.8+7-Debian-1deb12u1)\n\
+                 OpenJDK 64-Bit Server VM (build 17.0.8+7-Debian-1deb12u1, mixed mode, sharing)\n",
+            ),
+            version_output("python3", "--version", "Python 3.11.2\n", ""),
+            version_output("go", "version", "go version go1.21.5 linux/amd64\n", ""),
+        ];
+
+        assert_eq!(
+            purls(&RuntimePlugin.extract(&results)),
+            [
+                "pkg:generic/java@17.0.8",
+                "pkg:generic/python@3.11.2",
+                "pkg:generic/go@1.21.5",
+            ]
+        );
+    }
+}
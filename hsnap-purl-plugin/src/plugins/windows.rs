@@ -1,5 +1,5 @@
-use crate::{Os, Plugin, Probe, ProbeResult, SoftwareComponent};
-
+use crate::{version_info, Os, Plugin, Probe, ProbeResult, RegistryEntry, SoftwareComponent};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 
 pub struct WindowsRegistryPlugin;
 
@@ -12,6 +12,10 @@ impl Plugin for WindowsRegistryPlugin {
         Some(vec![Os::Windows])
     }
 
+    fn component_kinds(&self) -> &[&str] {
+        &["windows-component"]
+    }
+
     fn probes(&self) -> Vec<Probe> {
         vec![
             Probe::WindowsRegistry("HKLM\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall".to_string()),
@@ -29,8 +33,21 @@ impl Plugin for WindowsRegistryPlugin {
                     if let Some(name) = &entry.display_name {
                         components.push(SoftwareComponent::WindowsComponent {
                             name: name.clone(),
-                            version: entry.display_version.clone().unwrap_or_default(),
+                            version: entry
+                                .display_version
+                                .clone()
+                                .filter(|version| !version.trim().is_empty())
+                                .or_else(|| executable_version(entry))
+                                .unwrap_or_default(),
                             publisher: entry.publisher.clone(), 
+                            install_date: entry.install_date.clone(),
+                            installed_at: entry
+                                .install_date
+                                .as_deref()
+                                .and_then(parse_install_date),
+                            estimated_size: entry.estimated_size,
+                            url_info_about: entry.url_info_about.clone(),
+                            uninstall_string: entry.uninstall_string.clone(),
                         });
                     }
                 }
@@ -39,3 +56,73 @@ impl Plugin for WindowsRegistryPlugin {
         components
     }
 }
+
+/// Seconds from the FILETIME epoch, 1601-01-01, to the Unix epoch.
+const FILETIME_UNIX_EPOCH_SECONDS: i64 = 11_644_473_600;
+
+/// Parses an InstallDate. Installers mostly write a `YYYYMMDD` string, but some write it as
+/// a DWORD (read as the same digits), others a Unix time in seconds or a FILETIME (100ns
+/// ticks since 1601), and a few a `YYYY-MM-DD` or US `M/D/YYYY` date. Eight digits that
+/// make a valid date are taken as one; a Unix time of eight digits would be in 1970-73.
+/// Anything before Windows 1.0 shipped, in 1985, is taken to be garbage.
+fn parse_install_date(value: &str) -> Option<DateTime<Utc>> {
+    parse_install_date_value(value.trim()).filter(|date| date.year() >= 1985)
+}
+
+fn parse_install_date_value(value: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .filter(|_| value.len() == 8)
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%m/%d/%Y").ok());
+    if let Some(date) = date {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    let number = value.parse::<u64>().ok()?;
+    // A FILETIME of 2^32 ticks is only seven minutes after 1601, so anything that size is a Unix time
+    if number <= u64::from(u32::MAX) {
+        return DateTime::from_timestamp(number as i64, 0);
+    }
+    let ticks = i64::try_from(number).ok()?;
+    DateTime::from_timestamp(ticks / 10_000_000 - FILETIME_UNIX_EPOCH_SECONDS, 0)
+}
+
+/// Falls back to the VERSIONINFO of the program's executable when the registry has no DisplayVersion.
+fn executable_version(entry: &RegistryEntry) -> Option<String> {
+    let exe = version_info::primary_executable(
+        entry.display_icon.as_deref(),
+        entry.install_location.as_deref(),
+    )?;
+    version_info::product_version(&exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_install_date_forms_to_the_same_date() {
+        let expected = NaiveDate::from_ymd_opt(2023, 1, 15)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| date.and_utc());
+        for value in [
+            "20230115",
+            " 20230115 ",
+            "2023-01-15",
+            "1/15/2023",
+            // Unix time
+            "1673740800",
+            // FILETIME
+            "133182144000000000",
+        ] {
+            assert_eq!(parse_install_date(value), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_unrecognized_install_dates() {
+        for value in ["", "unknown", "20231345", "19700101", "12345", "-1"] {
+            assert_eq!(parse_install_date(value), None, "{value}");
+        }
+    }
+}
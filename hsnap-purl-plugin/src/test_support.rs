@@ -0,0 +1,86 @@
+//! Helpers shared by the unit tests.
+
+use crate::{Plugin, PluginOptions, Probe, ProbeResult, SoftwareComponent};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A directory under the system temp directory, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "hsnap-purl-plugin-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to `relative`, creating its parent directories.
+    pub fn write(&self, relative: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.0.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The purls among `components`, as strings.
+pub fn purls(components: &[SoftwareComponent]) -> Vec<String> {
+    components
+        .iter()
+        .filter_map(|component| match component {
+            SoftwareComponent::Purl(purl) => Some(purl.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Runs `plugin`'s probes through the probe engine and extracts its components, as
+/// `run_plugins` does for the host's plugins.
+pub fn run_plugin(plugin: &dyn Plugin, options: &PluginOptions) -> Vec<SoftwareComponent> {
+    let mut errors = Vec::new();
+    let mut lines = Vec::new();
+    let results = crate::run_probes(
+        plugin.probes(),
+        options,
+        &mut errors,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &mut |probe, line| {
+            lines.extend(plugin.extract_line(probe, line));
+            true
+        },
+    );
+    assert!(errors.is_empty(), "probe errors: {:?}", errors);
+    lines.extend(plugin.try_extract(&results).unwrap());
+    lines
+}
+
+/// Runs `probes` through the probe engine with the default options.
+pub fn run_probes(probes: Vec<Probe>) -> Vec<ProbeResult> {
+    let mut errors = Vec::new();
+    let results = crate::run_probes(
+        probes,
+        &PluginOptions::default(),
+        &mut errors,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &mut |_, _| true,
+    );
+    assert!(errors.is_empty(), "probe errors: {:?}", errors);
+    results
+}
@@ -0,0 +1,169 @@
+use crate::under_root;
+use std::path::{Path, PathBuf};
+
+/// Accounts below this uid are system accounts on Linux, per the default `UID_MIN`.
+const UID_MIN: u32 = 1000;
+/// `nobody`, which is above `UID_MIN` but no one's login.
+const OVERFLOW_UID: u32 = 65534;
+
+/// An account from `/etc/passwd`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswdEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+impl PasswdEntry {
+    pub fn is_system(&self) -> bool {
+        self.uid < UID_MIN || self.uid == OVERFLOW_UID
+    }
+}
+
+/// A user account and its home directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserHome {
+    pub name: String,
+    pub home: PathBuf,
+}
+
+/// Returns the users on this host whose home directory exists on disk.
+///
+/// On Linux these come from `/etc/passwd`; on macOS and Windows the per-user
+/// profile directories under `/Users` and `%SystemDrive%\Users` are listed instead.
+/// Accounts sharing a home directory are only returned once. With a `root`, the users of
+/// the system mounted there are returned, with their home directories as seen from it.
+pub fn home_dirs(root: Option<&Path>) -> Vec<UserHome> {
+    let mut homes: Vec<UserHome> = if cfg!(target_os = "windows") && root.is_none() {
+        let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+        list_dirs(PathBuf::from(format!("{}\\Users", drive)))
+    } else if cfg!(target_os = "macos") && root.is_none() {
+        list_dirs(PathBuf::from("/Users"))
+    } else {
+        passwd_entries(root)
+            .into_iter()
+            .filter(|entry| !entry.home.is_empty() && entry.home != "/")
+            .map(|entry| UserHome {
+                name: entry.name,
+                home: PathBuf::from(entry.home),
+            })
+            .collect()
+    };
+
+    homes.retain(|user| under_root(root, &user.home).is_dir());
+    let mut seen = Vec::new();
+    homes.retain(|user| {
+        if seen.contains(&user.home) {
+            false
+        } else {
+            seen.push(user.home.clone());
+            true
+        }
+    });
+    homes
+}
+
+/// The accounts in `/etc/passwd`, in file order. Empty where there is none, e.g. on Windows.
+pub fn passwd_entries(root: Option<&Path>) -> Vec<PasswdEntry> {
+    std::fs::read_to_string(under_root(root, Path::new("/etc/passwd")))
+        .map(|passwd| parse_passwd(&passwd))
+        .unwrap_or_default()
+}
+
+/// Parses `name:password:uid:gid:gecos:home:shell` lines, skipping malformed ones.
+fn parse_passwd(passwd: &str) -> Vec<PasswdEntry> {
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, uid, gid, _, home, shell] = fields[..] else {
+                return None;
+            };
+            Some(PasswdEntry {
+                name: name.to_string(),
+                uid: uid.parse().ok()?,
+                gid: gid.parse().ok()?,
+                home: home.to_string(),
+                shell: shell.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn list_dirs(parent: PathBuf) -> Vec<UserHome> {
+    std::fs::read_dir(parent)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| UserHome {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    home: entry.path(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn parses_passwd_line() {
+        let entries = parse_passwd(
+            "alice:x:1000:1001:Alice Liddell,,,:/home/alice:/bin/bash\n\
+             # not an account\n\
+             broken:x:abc:0::/:/bin/sh\n\
+             sshd:x:105:65534::/run/sshd:/usr/sbin/nologin\n",
+        );
+        assert_eq!(
+            entries[0],
+            PasswdEntry {
+                name: "alice".to_string(),
+                uid: 1000,
+                gid: 1001,
+                home: "/home/alice".to_string(),
+                shell: "/bin/bash".to_string(),
+            }
+        );
+        assert!(!entries[0].is_system());
+        assert_eq!(entries[1].name, "sshd");
+        assert!(entries[1].is_system());
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn lists_homes_of_passwd_users_under_root() {
+        let root = TempDir::new();
+        root.write(
+            "etc/passwd",
+            "root:x:0:0:root:/root:/bin/bash\n\
+             daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+             alice:x:1000:1000:Alice:/home/alice:/bin/bash\n\
+             bob:x:1001:1001::/home/bob:/bin/zsh\n\
+             carol:x:1002:1002::/home/carol:/bin/bash\n\
+             alice2:x:1003:1003::/home/alice:/bin/bash\n\
+             nobody:x:65534:65534:nobody:/:/usr/sbin/nologin\n",
+        );
+        for home in ["root", "home/alice", "home/bob"] {
+            std::fs::create_dir_all(root.path().join(home)).unwrap();
+        }
+
+        // carol's home doesn't exist, alice2 shares alice's and nobody's is /
+        let names: Vec<_> = home_dirs(Some(root.path()))
+            .into_iter()
+            .map(|user| (user.name, user.home))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("root".to_string(), PathBuf::from("/root")),
+                ("alice".to_string(), PathBuf::from("/home/alice")),
+                ("bob".to_string(), PathBuf::from("/home/bob")),
+            ]
+        );
+    }
+}
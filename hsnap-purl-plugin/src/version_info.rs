@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+/// Finds an installed program's main executable: the `DisplayIcon` when it points at
+/// an `.exe`, otherwise the only `.exe` directly inside `InstallLocation`.
+pub fn primary_executable(
+    display_icon: Option<&str>,
+    install_location: Option<&str>,
+) -> Option<PathBuf> {
+    // DisplayIcon is often quoted and may carry an icon index, e.g. "C:\app\app.exe",0
+    let icon = display_icon.map(|icon| {
+        let icon = icon.trim();
+        let icon = icon.rsplit_once(',').map_or(icon, |(path, index)| {
+            if index.trim().parse::<i32>().is_ok() {
+                path
+            } else {
+                icon
+            }
+        });
+        PathBuf::from(icon.trim().trim_matches('"'))
+    });
+    if let Some(icon) = icon.filter(|icon| is_exe(icon) && icon.is_file()) {
+        return Some(icon);
+    }
+
+    let location = install_location?.trim().trim_matches('"');
+    if location.is_empty() {
+        return None;
+    }
+    let mut exes = std::fs::read_dir(location)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_exe(path) && path.is_file());
+    match (exes.next(), exes.next()) {
+        (Some(exe), None) => Some(exe),
+        _ => None,
+    }
+}
+
+fn is_exe(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+/// The product version from a PE file's VERSIONINFO resource, as `major.minor.build.revision`.
+#[cfg(target_os = "windows")]
+pub fn product_version(path: &Path) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+    };
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // The root block "\" holds the language independent VS_FIXEDFILEINFO
+    let root: Vec<u16> = "\\".encode_utf16().chain(Some(0)).collect();
+
+    // SAFETY: both strings are NUL terminated, `data` is sized as the API asked for,
+    // and VerQueryValueW only returns pointers into `data`, which outlives them.
+    unsafe {
+        let size = GetFileVersionInfoSizeW(wide_path.as_ptr(), std::ptr::null_mut());
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        if GetFileVersionInfoW(wide_path.as_ptr(), 0, size, data.as_mut_ptr().cast()) == 0 {
+            return None;
+        }
+
+        let mut info: *mut std::ffi::c_void = std::ptr::null_mut();
+        let mut len = 0u32;
+        if VerQueryValueW(data.as_ptr().cast(), root.as_ptr(), &mut info, &mut len) == 0
+            || info.is_null()
+            || (len as usize) < std::mem::size_of::<VS_FIXEDFILEINFO>()
+        {
+            return None;
+        }
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwProductVersionMS >> 16,
+            info.dwProductVersionMS & 0xffff,
+            info.dwProductVersionLS >> 16,
+            info.dwProductVersionLS & 0xffff
+        ))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn product_version(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn finds_executable_from_display_icon_or_install_location() {
+        let dir = TempDir::new();
+        let app = dir.write("app/app.exe", "MZ");
+        dir.write("app/readme.txt", "");
+        let icon = format!("\"{}\",0", app.display());
+        let location = dir.path().join("app");
+
+        assert_eq!(primary_executable(Some(&icon), None), Some(app.clone()));
+        assert_eq!(
+            primary_executable(None, location.to_str()),
+            Some(app.clone())
+        );
+        // An icon file isn't the program
+        let ico = dir.write("app/app.ico", "");
+        assert_eq!(
+            primary_executable(ico.to_str(), location.to_str()),
+            Some(app)
+        );
+        // Which of several executables is the program can't be told
+        dir.write("app/uninstall.exe", "MZ");
+        assert_eq!(primary_executable(None, location.to_str()), None);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn reads_version_of_system_dll() {
+        let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".into());
+        let kernel32 = Path::new(&system_root)
+            .join("System32")
+            .join("kernel32.dll");
+        let version = product_version(&kernel32).unwrap();
+        let parts: Vec<u32> = version
+            .split('.')
+            .map(|part| part.parse().unwrap())
+            .collect();
+        assert_eq!(parts.len(), 4);
+        // Every supported Windows is 10.0 or later
+        assert!(parts[0] >= 10, "{}", version);
+    }
+}
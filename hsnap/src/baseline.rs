@@ -0,0 +1,124 @@
+use crate::{delta, HostSnapshot};
+use hsnap_purl_plugin::ComponentWithSource;
+use std::path::Path;
+
+/// The software components recorded by the last run, or `None` on the first run or if
+/// the file can't be read.
+pub fn load(path: &Path) -> Option<Vec<ComponentWithSource>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(components) => Some(components),
+        Err(e) => {
+            eprintln!(
+                "Ignoring unreadable baseline file {}: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+pub fn save(path: &Path, components: &[ComponentWithSource]) -> std::io::Result<()> {
+    let contents = serde_json::to_vec_pretty(components).map_err(std::io::Error::other)?;
+    std::fs::write(path, contents)
+}
+
+/// Cuts `snapshot` down to its metadata and the software components added and removed
+/// since `baseline`.
+pub fn changes_since(snapshot: &HostSnapshot, baseline: &[ComponentWithSource]) -> HostSnapshot {
+    HostSnapshot {
+        metadata: snapshot.metadata.clone(),
+        hardware: None,
+        operating_system: None,
+        network: None,
+        storage: None,
+        services: Vec::new(),
+        users: None,
+        repositories: None,
+        scheduled_tasks: None,
+        processes: None,
+        environment_variables: None,
+        trusted_cas: None,
+        boot_entries: None,
+        profiles: None,
+        software_components: Vec::new(),
+        software_delta: Some(delta::diff(baseline, &snapshot.software_components)),
+        omitted_sections: Vec::new(),
+        routed_sections: Vec::new(),
+        components_truncated: false,
+        collection_errors: Vec::new(),
+        skipped_probes: Vec::new(),
+        privilege_limited: Vec::new(),
+        truncated: false,
+        digest: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{empty_snapshot, TempDir};
+    use hsnap_purl_plugin::SoftwareComponent;
+
+    fn snapshot_with(purls: &[&str]) -> HostSnapshot {
+        let mut snapshot = empty_snapshot();
+        snapshot.software_components = purls
+            .iter()
+            .map(|purl| ComponentWithSource {
+                component: SoftwareComponent::Purl(purl.parse().unwrap()),
+                source: "debian-dpkg".to_string(),
+                license: None,
+                homepage: None,
+            })
+            .collect();
+        snapshot
+    }
+
+    fn purls(components: &[ComponentWithSource]) -> Vec<String> {
+        components
+            .iter()
+            .map(|found| match &found.component {
+                SoftwareComponent::Purl(purl) => purl.to_string(),
+                other => panic!("unexpected component {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_install_and_removal_across_runs() {
+        let dir = TempDir::new();
+        let path = dir.path().join("baseline.json");
+
+        // The first run has no baseline, so outputs everything and records it
+        let first = snapshot_with(&["pkg:deb/bash@5.2.15-2", "pkg:deb/curl@7.88.1-10"]);
+        assert!(load(&path).is_none());
+        save(&path, &first.software_components).unwrap();
+
+        // curl removed and jq installed since
+        let second = snapshot_with(&["pkg:deb/bash@5.2.15-2", "pkg:deb/jq@1.6-2.1"]);
+        let changes = changes_since(&second, &load(&path).unwrap());
+        let delta = changes.software_delta.as_ref().unwrap();
+        assert_eq!(purls(&delta.added), ["pkg:deb/jq@1.6-2.1"]);
+        assert_eq!(purls(&delta.removed), ["pkg:deb/curl@7.88.1-10"]);
+        assert!(changes.software_components.is_empty());
+        assert!(changes.hardware.is_none());
+        assert_eq!(changes.metadata.id, "test-host");
+        save(&path, &second.software_components).unwrap();
+
+        // Nothing changed by the third
+        let third = snapshot_with(&["pkg:deb/bash@5.2.15-2", "pkg:deb/jq@1.6-2.1"]);
+        let delta = changes_since(&third, &load(&path).unwrap())
+            .software_delta
+            .unwrap();
+        assert!(delta.added.is_empty() && delta.removed.is_empty());
+    }
+
+    #[test]
+    fn ignores_unreadable_baseline() {
+        let dir = TempDir::new();
+        let path = dir.path().join("baseline.json");
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load(&path).is_none());
+    }
+}
@@ -0,0 +1,383 @@
+use hsnap_purl_plugin::extract::wildcard_match;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where systemd-boot (and GRUB on Fedora and RHEL) find Boot Loader Specification
+/// entries: the `loader` directory of the EFI system partition or the boot partition.
+const LOADER_DIRS: [&str; 3] = ["/boot/loader", "/efi/loader", "/boot/efi/loader"];
+/// GRUB's generated configuration, under `grub` on Debian and `grub2` on Fedora and RHEL.
+const GRUB_DIRS: [&str; 2] = ["/boot/grub", "/boot/grub2"];
+
+/// An entry of the boot menu.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BootEntry {
+    /// `bls` for a Boot Loader Specification entry file, `grub` for a `grub.cfg` menu entry.
+    pub kind: String,
+    /// The entry file's name without `.conf`, or the GRUB menu entry's `--id`.
+    pub id: Option<String>,
+    /// For GRUB entries in submenus, preceded by the submenus' titles, e.g.
+    /// `Advanced options for Ubuntu>Ubuntu, with Linux 6.5.0-14-generic`.
+    pub title: Option<String>,
+    /// The kernel version. For GRUB, taken from the kernel's file name.
+    pub version: Option<String>,
+    /// The kernel image, or the EFI program (such as a unified kernel image) it runs.
+    pub linux: Option<String>,
+    /// The kernel command line.
+    pub options: Option<String>,
+    /// Whether it is the entry booted by default.
+    pub default: bool,
+}
+
+/// Reads the Boot Loader Specification entries and GRUB menu entries, marking the default.
+pub fn collect() -> Vec<BootEntry> {
+    let mut entries = Vec::new();
+
+    for dir in LOADER_DIRS.iter().map(Path::new) {
+        let files = entry_files(&dir.join("entries"));
+        if files.is_empty() {
+            continue;
+        }
+        let loader_default = std::fs::read_to_string(dir.join("loader.conf"))
+            .ok()
+            .and_then(|contents| parse_loader_default(&contents));
+        // GRUB reads these entries too when grub.cfg runs `blscfg`, and saves its default
+        // as an entry's id
+        let grub_default = GRUB_DIRS
+            .iter()
+            .find_map(|grub| saved_entry(Path::new(grub)));
+        for path in files {
+            let Some(id) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut entry = parse_bls_entry(&contents);
+            entry.default = match &loader_default {
+                Some(pattern) => {
+                    wildcard_match(pattern, &id) || wildcard_match(pattern, &format!("{}.conf", id))
+                }
+                None => grub_default.as_deref() == Some(&id),
+            };
+            entry.id = Some(id);
+            entries.push(entry);
+        }
+    }
+
+    if let Some(dir) = GRUB_DIRS
+        .iter()
+        .map(Path::new)
+        .find(|dir| dir.join("grub.cfg").is_file())
+    {
+        if let Ok(contents) = std::fs::read_to_string(dir.join("grub.cfg")) {
+            entries.extend(parse_grub_cfg(&contents, saved_entry(dir).as_deref()));
+        }
+    }
+
+    entries
+}
+
+/// The `.conf` files in `dir`, sorted so the output is stable.
+fn entry_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "conf"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// The `default` of systemd-boot's `loader.conf`, an entry's id or file name, which may
+/// contain wildcards.
+fn parse_loader_default(contents: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once(char::is_whitespace)?;
+        (key == "default").then(|| value.trim().to_string())
+    })
+}
+
+/// Parses a Boot Loader Specification entry, a `key value` line per setting:
+///
+/// ```text
+/// title      Fedora Linux 39 (Workstation Edition)
+/// version    6.5.6-300.fc39.x86_64
+/// linux      /vmlinuz-6.5.6-300.fc39.x86_64
+/// initrd     /initramfs-6.5.6-300.fc39.x86_64.img
+/// options    root=UUID=1e8d0d5c-0f38-4d59-a7c5-2b1b2b2c3d4e ro rhgb quiet
+/// ```
+fn parse_bls_entry(contents: &str) -> BootEntry {
+    let mut entry = BootEntry {
+        kind: "bls".to_string(),
+        id: None,
+        title: None,
+        version: None,
+        linux: None,
+        options: None,
+        default: false,
+    };
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key {
+            "title" => entry.title = Some(value),
+            "version" => entry.version = Some(value),
+            "linux" | "efi" => entry.linux = Some(value),
+            // options may be given on several lines, which are joined
+            "options" => {
+                entry.options = Some(match entry.options.take() {
+                    Some(options) => format!("{} {}", options, value),
+                    None => value,
+                })
+            }
+            _ => {}
+        }
+    }
+    entry
+}
+
+/// The `saved_entry` in the `grubenv` in `dir`, which `grub-set-default` writes.
+fn saved_entry(dir: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(dir.join("grubenv")).ok()?;
+    contents.lines().find_map(|line| {
+        let value = line.strip_prefix("saved_entry=")?;
+        Some(value.to_string()).filter(|value| !value.is_empty())
+    })
+}
+
+/// Parses the menu entries of a `grub.cfg`, marking the one its `set default` selects:
+///
+/// ```text
+/// set default="${saved_entry}"
+/// menuentry 'Ubuntu' --class ubuntu $menuentry_id_option 'gnulinux-simple-1e8d' {
+///     linux   /boot/vmlinuz-6.5.0-14-generic root=UUID=1e8d ro quiet splash
+/// }
+/// submenu 'Advanced options for Ubuntu' $menuentry_id_option 'gnulinux-advanced-1e8d' {
+///     menuentry 'Ubuntu, with Linux 6.5.0-14-generic' ...
+/// ```
+///
+/// The default is an entry's position, title or id, with `>` between those of the
+/// submenus containing it, e.g. `1>2`. `${saved_entry}` stands for `saved_entry`.
+fn parse_grub_cfg(contents: &str, saved_entry: Option<&str>) -> Vec<BootEntry> {
+    // The names the default may use for an entry: at each submenu level, its position,
+    // title and id
+    let mut entries: Vec<(BootEntry, Vec<[String; 3]>)> = Vec::new();
+    // The open blocks, with the names of submenus; menu entries, functions and other
+    // blocks hold no menu entries
+    let mut blocks: Vec<Option<[String; 3]>> = Vec::new();
+    // How many menu entries and submenus each level of submenu has had so far
+    let mut positions = vec![0];
+    let mut default = "0".to_string();
+    let mut in_entry = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        let words = split_words(line);
+        let Some(command) = words.first() else {
+            continue;
+        };
+        match command.as_str() {
+            "menuentry" | "submenu" => {
+                let position = positions.last_mut().expect("top level");
+                let title = words.get(1).cloned().unwrap_or_default();
+                let id = words
+                    .iter()
+                    .position(|word| word == "--id" || word == "$menuentry_id_option")
+                    .and_then(|i| words.get(i + 1))
+                    .cloned()
+                    .unwrap_or_default();
+                let names = [position.to_string(), title, id];
+                *position += 1;
+                if command == "submenu" {
+                    positions.push(0);
+                    blocks.push(Some(names));
+                } else {
+                    let mut path: Vec<[String; 3]> = blocks.iter().flatten().cloned().collect();
+                    path.push(names.clone());
+                    let title = path
+                        .iter()
+                        .map(|[_, title, _]| title.as_str())
+                        .collect::<Vec<_>>()
+                        .join(">");
+                    let id = Some(names[2].clone()).filter(|id| !id.is_empty());
+                    entries.push((
+                        BootEntry {
+                            kind: "grub".to_string(),
+                            id,
+                            title: Some(title),
+                            version: None,
+                            linux: None,
+                            options: None,
+                            default: false,
+                        },
+                        path,
+                    ));
+                    in_entry = true;
+                    blocks.push(None);
+                }
+            }
+            "}" => {
+                in_entry = false;
+                if let Some(Some(_)) = blocks.pop() {
+                    positions.pop();
+                }
+            }
+            "linux" | "linuxefi" | "linux16" if in_entry => {
+                if let Some((entry, _)) = entries.last_mut() {
+                    let kernel = words.get(1).cloned();
+                    entry.version = kernel.as_deref().and_then(|kernel| {
+                        let name = kernel.rsplit('/').next()?;
+                        Some(name.strip_prefix("vmlinuz-")?.to_string())
+                    });
+                    entry.linux = kernel;
+                    entry.options = Some(words[2..].join(" ")).filter(|o| !o.is_empty());
+                }
+            }
+            "set" => {
+                // `set default="${next_entry}"` only applies to a one-time boot
+                if let Some(value) = words.get(1).and_then(|w| w.strip_prefix("default=")) {
+                    if !value.contains("next_entry") {
+                        default = value.to_string();
+                    }
+                }
+            }
+            _ if line.ends_with('{') => blocks.push(None),
+            _ => {}
+        }
+    }
+
+    let default = match default.as_str() {
+        "${saved_entry}" | "$saved_entry" => saved_entry.unwrap_or("0").to_string(),
+        _ => default,
+    };
+    let selectors: Vec<&str> = default.split('>').collect();
+    entries
+        .into_iter()
+        .map(|(mut entry, path)| {
+            entry.default = path.len() == selectors.len()
+                && path
+                    .iter()
+                    .zip(&selectors)
+                    .all(|(names, selector)| names.iter().any(|name| name == selector));
+            entry
+        })
+        .collect()
+}
+
+/// Splits a `grub.cfg` line into words as its shell-like syntax does, removing quotes.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => word.extend(chars.next()),
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '#') if !in_word => break,
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_systemd_boot_entry() {
+        let contents = "# Boot Loader Specification type#1 entry\n\
+            title      Fedora Linux 39 (Workstation Edition)\n\
+            version    6.5.6-300.fc39.x86_64\n\
+            linux      /vmlinuz-6.5.6-300.fc39.x86_64\n\
+            initrd     /initramfs-6.5.6-300.fc39.x86_64.img\n\
+            options    root=UUID=1e8d0d5c-0f38-4d59-a7c5-2b1b2b2c3d4e ro\n\
+            options    rhgb quiet\n";
+        assert_eq!(
+            parse_bls_entry(contents),
+            BootEntry {
+                kind: "bls".to_string(),
+                id: None,
+                title: Some("Fedora Linux 39 (Workstation Edition)".to_string()),
+                version: Some("6.5.6-300.fc39.x86_64".to_string()),
+                linux: Some("/vmlinuz-6.5.6-300.fc39.x86_64".to_string()),
+                options: Some(
+                    "root=UUID=1e8d0d5c-0f38-4d59-a7c5-2b1b2b2c3d4e ro rhgb quiet".to_string()
+                ),
+                default: false,
+            }
+        );
+        assert_eq!(
+            parse_loader_default("timeout 3\ndefault fedora-*\n").as_deref(),
+            Some("fedora-*")
+        );
+    }
+
+    #[test]
+    fn marks_grub_default_in_submenu() {
+        let contents = "set default=\"${saved_entry}\"\n\
+            menuentry 'Ubuntu' --class ubuntu $menuentry_id_option 'gnulinux-simple-1e8d' {\n\
+            \tlinux\t/boot/vmlinuz-6.5.0-15-generic root=UUID=1e8d ro quiet splash\n\
+            }\n\
+            submenu 'Advanced options for Ubuntu' $menuentry_id_option 'gnulinux-advanced-1e8d' {\n\
+            \tmenuentry 'Ubuntu, with Linux 6.5.0-14-generic' $menuentry_id_option 'gnulinux-6.5.0-14-generic-advanced-1e8d' {\n\
+            \t\tlinux\t/boot/vmlinuz-6.5.0-14-generic root=UUID=1e8d ro\n\
+            \t}\n\
+            }\n";
+        let entries = parse_grub_cfg(contents, Some("gnulinux-advanced-1e8d>0"));
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.title.as_deref().unwrap(),
+                    entry.version.as_deref().unwrap(),
+                    entry.default,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Ubuntu", "6.5.0-15-generic", false),
+                (
+                    "Advanced options for Ubuntu>Ubuntu, with Linux 6.5.0-14-generic",
+                    "6.5.0-14-generic",
+                    true
+                ),
+            ]
+        );
+        assert!(parse_grub_cfg(contents, None)[0].default);
+    }
+}
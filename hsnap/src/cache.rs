@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use hsnap_purl_plugin::{ComponentWithSource, Inventory, PluginOptions, SkippedProbe};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Package databases whose modification time invalidates a cached inventory. A sqlite
+/// database's `-wal` file is watched too, as it changes without the database changing.
+const PACKAGE_DATABASES: [&str; 7] = [
+    "/var/lib/dpkg/status",
+    "/var/lib/rpm/rpmdb.sqlite",
+    "/var/lib/rpm/rpmdb.sqlite-wal",
+    "/var/lib/rpm/Packages",
+    "/usr/lib/sysimage/rpm/rpmdb.sqlite",
+    "/usr/lib/sysimage/rpm/rpmdb.sqlite-wal",
+    "/usr/lib/sysimage/rpm/Packages",
+];
+
+/// A software inventory saved by a previous run.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: DateTime<Utc>,
+    /// The package database state the inventory was collected from, see `fingerprint`.
+    fingerprint: String,
+    components: Vec<ComponentWithSource>,
+    truncated: bool,
+    #[serde(default)]
+    skipped: Vec<SkippedProbe>,
+}
+
+/// An on-disk cache of the plugin results, so frequent runs don't re-enumerate every package.
+pub struct InventoryCache {
+    path: PathBuf,
+    ttl: Duration,
+    fingerprint: String,
+}
+
+impl InventoryCache {
+    pub fn new(
+        dir: &Path,
+        ttl: Duration,
+        host_id: &str,
+        options: &PluginOptions,
+    ) -> InventoryCache {
+        // One file per host and set of options, so runs with different options don't evict each other
+        let mut extra_qualifiers: Vec<_> = options.extra_qualifiers.iter().collect();
+        extra_qualifiers.sort();
+        let key = Sha256::digest(format!(
+            "{}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}\n{:?}",
+            host_id,
+            options.root,
+            options.composer_roots,
+            options.venv_roots,
+            options.fonts,
+            options.max_components,
+            options.component_kinds,
+            extra_qualifiers,
+            options.no_shell,
+            options.max_command_output,
+            options.plugin_dirs,
+            options.command_policy
+        ));
+        InventoryCache {
+            path: dir.join(format!("inventory-{}.json", &hex::encode(key)[..16])),
+            ttl,
+            fingerprint: fingerprint(options),
+        }
+    }
+
+    /// The cached inventory, if it is younger than the TTL and nothing it depends on changed.
+    pub fn load(&self) -> Option<Inventory> {
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+        let age = Utc::now()
+            .signed_duration_since(entry.created_at)
+            .to_std()
+            .ok()?;
+        if entry.fingerprint != self.fingerprint || age > self.ttl {
+            return None;
+        }
+        Some(Inventory {
+            components: entry.components,
+            truncated: entry.truncated,
+            errors: Vec::new(),
+            skipped: entry.skipped,
+            warnings: Vec::new(),
+        })
+    }
+
+    pub fn save(&self, inventory: &Inventory) -> Result<(), String> {
+        let entry = CacheEntry {
+            created_at: Utc::now(),
+            fingerprint: self.fingerprint.clone(),
+            components: inventory.components.clone(),
+            truncated: inventory.truncated,
+            skipped: inventory.skipped.clone(),
+        };
+        let contents = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&self.path, contents).map_err(|e| e.to_string())
+    }
+}
+
+/// SHA256 over the modification time of each package database and composer.lock, so
+/// installing or removing a package misses the cache.
+fn fingerprint(options: &PluginOptions) -> String {
+    // With --root the plugins read absolute paths from under it
+    let under_root = |path: PathBuf| match (&options.root, path.strip_prefix("/")) {
+        (Some(root), Ok(relative)) => root.join(relative),
+        _ => path,
+    };
+    let mut watched: Vec<PathBuf> = PACKAGE_DATABASES.iter().map(PathBuf::from).collect();
+    watched.extend(
+        options
+            .composer_roots
+            .iter()
+            .map(|root| root.join("composer.lock")),
+    );
+    let mut watched: Vec<PathBuf> = watched.into_iter().map(under_root).collect();
+    // Adding or removing a plugin manifest changes its directory's modification time
+    watched.extend(options.plugin_dirs.iter().cloned());
+
+    let mut hasher = Sha256::new();
+    for path in &watched {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        hasher.update(format!("{}={:?}\n", path.display(), modified));
+    }
+    hex::encode(hasher.finalize())
+}
@@ -0,0 +1,50 @@
+use crate::{HostSnapshot, Metadata};
+use clap::ValueEnum;
+use hsnap_purl_plugin::{ComponentWithSource, SoftwareComponent};
+use serde::Serialize;
+
+/// How `--components-only-json` writes each component.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ComponentStyle {
+    /// As in the snapshot: its type, details, source and, with `--enrich`, license and homepage.
+    #[default]
+    Object,
+    /// Purls as strings, e.g. `pkg:deb/curl@7.88.1-10?arch=amd64`. Components that
+    /// aren't purls, such as Windows components, are still written as objects.
+    Purl,
+}
+
+/// The software components on their own, serialized as a bare JSON array.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct ComponentList {
+    // Kept only to name outputs such as S3 objects, never serialized
+    #[serde(skip)]
+    pub metadata: Metadata,
+    components: Vec<ComponentEntry>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ComponentEntry {
+    Purl(String),
+    Object(Box<ComponentWithSource>),
+}
+
+/// Takes the snapshot's software components, in `style`.
+pub fn from_snapshot(snapshot: HostSnapshot, style: ComponentStyle) -> ComponentList {
+    let components = snapshot
+        .software_components
+        .into_iter()
+        .map(|component| match (&component.component, style) {
+            (SoftwareComponent::Purl(purl), ComponentStyle::Purl) => {
+                ComponentEntry::Purl(purl.to_string())
+            }
+            _ => ComponentEntry::Object(Box::new(component)),
+        })
+        .collect();
+    ComponentList {
+        metadata: snapshot.metadata,
+        components,
+    }
+}
@@ -0,0 +1,245 @@
+/// A processor's family, model, stepping and microcode revision, which tell which errata
+/// (such as Spectre and Meltdown) it has and whether its microcode mitigates them.
+#[derive(Clone, Default)]
+pub struct CpuSignature {
+    pub family: Option<u32>,
+    pub model: Option<u32>,
+    pub stepping: Option<u32>,
+    pub microcode: Option<String>,
+}
+
+/// The signature of each of the `count` logical CPUs. On Linux they are read from
+/// `/proc/cpuinfo`; elsewhere, x86 CPUs' are asked of CPUID, without the microcode
+/// revision, and taken to be the same on every CPU.
+pub fn signatures(count: usize) -> Vec<CpuSignature> {
+    let mut signatures = if cfg!(target_os = "linux") {
+        std::fs::read_to_string("/proc/cpuinfo")
+            .map(|contents| parse_cpuinfo_signatures(&contents))
+            .unwrap_or_default()
+    } else {
+        vec![cpuid_signature(); count]
+    };
+    signatures.resize_with(count, CpuSignature::default);
+    signatures
+}
+
+/// The CPU's feature flags, e.g. `aes` or `avx2`, named as Linux names them. On Linux
+/// they are read from `/proc/cpuinfo`; elsewhere, only x86 CPUs' are found, with CPUID.
+pub fn collect() -> Vec<String> {
+    if cfg!(target_os = "linux") {
+        return std::fs::read_to_string("/proc/cpuinfo")
+            .map(|contents| parse_cpuinfo_flags(&contents))
+            .unwrap_or_default();
+    }
+    cpuid_features()
+}
+
+/// The flags of the first processor in `/proc/cpuinfo`: its `flags` line on x86, or its
+/// `Features` line on ARM, e.g. `Features : fp asimd evtstrm aes pmull sha1 sha2 crc32`.
+fn parse_cpuinfo_flags(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            matches!(key.trim(), "flags" | "Features")
+                .then(|| value.split_whitespace().map(str::to_string).collect())
+        })
+        .unwrap_or_default()
+}
+
+/// The signature of each processor in `/proc/cpuinfo`, from the lines of its block:
+///
+/// ```text
+/// processor       : 0
+/// vendor_id       : GenuineIntel
+/// cpu family      : 6
+/// model           : 85
+/// model name      : Intel(R) Xeon(R) Gold 6230 CPU @ 2.10GHz
+/// stepping        : 7
+/// microcode       : 0x5003604
+/// ```
+///
+/// ARM processors have none of these lines, so their signatures are empty.
+fn parse_cpuinfo_signatures(contents: &str) -> Vec<CpuSignature> {
+    let mut signatures = Vec::new();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if key.trim() == "processor" {
+            signatures.push(CpuSignature::default());
+            continue;
+        }
+        let Some(signature) = signatures.last_mut() else {
+            continue;
+        };
+        match key.trim() {
+            "cpu family" => signature.family = value.parse().ok(),
+            "model" => signature.model = value.parse().ok(),
+            "stepping" => signature.stepping = value.parse().ok(),
+            "microcode" => signature.microcode = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    signatures
+}
+
+/// The family, model and stepping of CPUID leaf 1, combining the extended family and
+/// model with the base ones as Linux does.
+fn cpuid_signature() -> CpuSignature {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let eax = std::arch::x86_64::__cpuid(1).eax;
+        let base_family = (eax >> 8) & 0xf;
+        let base_model = (eax >> 4) & 0xf;
+        let family = match base_family {
+            0xf => base_family + ((eax >> 20) & 0xff),
+            _ => base_family,
+        };
+        let model = match base_family {
+            0x6 | 0xf => (((eax >> 16) & 0xf) << 4) + base_model,
+            _ => base_model,
+        };
+        CpuSignature {
+            family: Some(family),
+            model: Some(model),
+            stepping: Some(eax & 0xf),
+            microcode: None,
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        CpuSignature::default()
+    }
+}
+
+/// The features CPUID reports, which the OS supports too (e.g. AVX needs the OS to save
+/// its registers).
+fn cpuid_features() -> Vec<String> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        macro_rules! detected {
+            ($($feature:tt => $name:literal,)*) => {
+                [$((is_x86_feature_detected!($feature), $name)),*]
+            };
+        }
+        detected! {
+            "tsc" => "tsc",
+            "mmx" => "mmx",
+            "fxsr" => "fxsr",
+            "sse" => "sse",
+            "sse2" => "sse2",
+            "sse3" => "pni",
+            "pclmulqdq" => "pclmulqdq",
+            "ssse3" => "ssse3",
+            "fma" => "fma",
+            "cmpxchg16b" => "cx16",
+            "sse4.1" => "sse4_1",
+            "sse4.2" => "sse4_2",
+            "movbe" => "movbe",
+            "popcnt" => "popcnt",
+            "aes" => "aes",
+            "xsave" => "xsave",
+            "avx" => "avx",
+            "f16c" => "f16c",
+            "rdrand" => "rdrand",
+            "lzcnt" => "abm",
+            "sse4a" => "sse4a",
+            "bmi1" => "bmi1",
+            "avx2" => "avx2",
+            "bmi2" => "bmi2",
+            "avx512f" => "avx512f",
+            "avx512dq" => "avx512dq",
+            "rdseed" => "rdseed",
+            "adx" => "adx",
+            "avx512cd" => "avx512cd",
+            "sha" => "sha_ni",
+            "avx512bw" => "avx512bw",
+            "avx512vl" => "avx512vl",
+        }
+        .into_iter()
+        .filter(|(detected, _)| *detected)
+        .map(|(_, name)| name.to_string())
+        .collect()
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpuinfo_flags() {
+        let x86 = "processor\t: 0\n\
+            vendor_id\t: GenuineIntel\n\
+            cpu family\t: 6\n\
+            model\t\t: 85\n\
+            stepping\t: 7\n\
+            microcode\t: 0x5003604\n\
+            flags\t\t: fpu vme sse2 aes avx avx2\n\
+            \n\
+            processor\t: 1\n\
+            vendor_id\t: GenuineIntel\n\
+            cpu family\t: 6\n\
+            model\t\t: 85\n\
+            stepping\t: 7\n\
+            microcode\t: 0x5003604\n\
+            flags\t\t: fpu vme sse2 aes avx avx2\n";
+        assert_eq!(
+            parse_cpuinfo_flags(x86),
+            ["fpu", "vme", "sse2", "aes", "avx", "avx2"]
+        );
+
+        let arm = "processor\t: 0\n\
+            BogoMIPS\t: 50.00\n\
+            Features\t: fp asimd evtstrm aes pmull sha1 sha2 crc32\n\
+            CPU implementer\t: 0x41\n";
+        assert_eq!(
+            parse_cpuinfo_flags(arm),
+            ["fp", "asimd", "evtstrm", "aes", "pmull", "sha1", "sha2", "crc32"]
+        );
+        assert!(parse_cpuinfo_flags("").is_empty());
+    }
+
+    #[test]
+    fn parses_cpuinfo_microcode_and_stepping() {
+        let contents = "processor\t: 0\n\
+            vendor_id\t: AuthenticAMD\n\
+            cpu family\t: 25\n\
+            model\t\t: 1\n\
+            model name\t: AMD EPYC 7763 64-Core Processor\n\
+            stepping\t: 1\n\
+            microcode\t: 0xa0011d1\n\
+            \n\
+            processor\t: 1\n\
+            vendor_id\t: AuthenticAMD\n\
+            cpu family\t: 25\n\
+            model\t\t: 1\n\
+            model name\t: AMD EPYC 7763 64-Core Processor\n\
+            stepping\t: 1\n\
+            microcode\t: 0xa001173\n";
+        let signatures: Vec<_> = parse_cpuinfo_signatures(contents)
+            .into_iter()
+            .map(|s| (s.family, s.model, s.stepping, s.microcode))
+            .collect();
+        assert_eq!(
+            signatures,
+            [
+                (Some(25), Some(1), Some(1), Some("0xa0011d1".to_string())),
+                // Each CPU keeps its own revision
+                (Some(25), Some(1), Some(1), Some("0xa001173".to_string())),
+            ]
+        );
+
+        // ARM processors have none of the lines
+        let arm = "processor\t: 0\nBogoMIPS\t: 50.00\nCPU implementer\t: 0x41\n";
+        let signature = &parse_cpuinfo_signatures(arm)[0];
+        assert_eq!(signature.stepping, None);
+        assert_eq!(signature.microcode, None);
+    }
+}
@@ -0,0 +1,62 @@
+use hsnap_purl_plugin::{ComponentWithSource, SoftwareComponent};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// The change in software components since a baseline the receiver already has.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ComponentDelta {
+    /// `component_set_digest` of the baseline this delta applies to.
+    pub baseline: String,
+    pub added: Vec<ComponentWithSource>,
+    pub removed: Vec<ComponentWithSource>,
+}
+
+/// A stable identity for a component. Purls use their canonical string form, since
+/// their qualifiers serialize to JSON in no particular order.
+pub fn component_key(component: &SoftwareComponent) -> String {
+    match component {
+        SoftwareComponent::Purl(purl) => purl.to_string(),
+        other => serde_json::to_string(other).expect("Failed to serialize component"),
+    }
+}
+
+/// Keyed by the component alone, so a component found by a different plugin than
+/// before isn't a change.
+fn keyed(components: &[ComponentWithSource]) -> BTreeMap<String, &ComponentWithSource> {
+    components
+        .iter()
+        .map(|component| (component_key(&component.component), component))
+        .collect()
+}
+
+/// SHA256 over the sorted, newline separated key of each component, so the same set
+/// of components always has the same digest whatever order the plugins found them in.
+pub fn component_set_digest(components: &[ComponentWithSource]) -> String {
+    let mut hasher = Sha256::new();
+    for key in keyed(components).keys() {
+        hasher.update(key.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Computes the components added and removed going from `baseline` to `current`.
+pub fn diff(baseline: &[ComponentWithSource], current: &[ComponentWithSource]) -> ComponentDelta {
+    let before = keyed(baseline);
+    let after = keyed(current);
+
+    ComponentDelta {
+        baseline: component_set_digest(baseline),
+        added: after
+            .iter()
+            .filter(|(key, _)| !before.contains_key(*key))
+            .map(|(_, component)| (*component).clone())
+            .collect(),
+        removed: before
+            .iter()
+            .filter(|(key, _)| !after.contains_key(*key))
+            .map(|(_, component)| (*component).clone())
+            .collect(),
+    }
+}
@@ -0,0 +1,50 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use base64::Engine;
+use serde::Serialize;
+
+/// Length of the GCM authentication tag appended to the ciphertext.
+const TAG_LEN: usize = 16;
+
+/// A shared AES-256 key used to encrypt snapshots.
+pub struct EncryptionKey {
+    cipher: Aes256Gcm,
+}
+
+/// The encrypted form of a payload. All binary fields are standard base64.
+#[derive(Serialize)]
+pub struct EncryptedEnvelope {
+    /// The JOSE name of the cipher: AES-256 in GCM mode.
+    pub algorithm: &'static str,
+    pub nonce: String,
+    pub ciphertext: String,
+    pub tag: String,
+}
+
+impl EncryptionKey {
+    /// Parses a key given as 64 hex characters.
+    pub fn from_hex(hex_key: &str) -> Result<EncryptionKey, String> {
+        let key = hex::decode(hex_key.trim()).map_err(|e| format!("Invalid hex key: {}", e))?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| format!("Key must be 32 bytes, got {}", key.len()))?;
+        Ok(EncryptionKey { cipher })
+    }
+
+    /// Encrypts `plaintext` under a fresh random nonce.
+    pub fn encrypt(&self, plaintext: &[u8]) -> EncryptedEnvelope {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut sealed = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .expect("Failed to encrypt snapshot");
+        let tag = sealed.split_off(sealed.len() - TAG_LEN);
+
+        let base64 = base64::engine::general_purpose::STANDARD;
+        EncryptedEnvelope {
+            algorithm: "A256GCM",
+            nonce: base64.encode(nonce),
+            ciphertext: base64.encode(sealed),
+            tag: base64.encode(tag),
+        }
+    }
+}
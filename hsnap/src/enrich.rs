@@ -0,0 +1,278 @@
+use hsnap_purl_plugin::{ComponentWithSource, SoftwareComponent};
+use packageurl::PackageUrl;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Purl types whose namespace only names the distribution, e.g. `pkg:deb/debian/curl`,
+/// so an entry without one applies to the package on every distribution.
+const DISTRO_TYPES: [&str; 4] = ["deb", "rpm", "apk", "alpm"];
+
+/// Well known packages, as versionless purls, with their SPDX license and homepage.
+const EMBEDDED: &[(&str, &str, &str)] = &[
+    (
+        "pkg:deb/bash",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/bash/",
+    ),
+    (
+        "pkg:deb/coreutils",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/coreutils/",
+    ),
+    ("pkg:deb/curl", "curl", "https://curl.se"),
+    ("pkg:deb/git", "GPL-2.0-only", "https://git-scm.com"),
+    (
+        "pkg:deb/grep",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/grep/",
+    ),
+    (
+        "pkg:deb/gzip",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/gzip/",
+    ),
+    (
+        "pkg:deb/libc6",
+        "LGPL-2.1-or-later",
+        "https://www.gnu.org/software/libc/",
+    ),
+    ("pkg:deb/nginx", "BSD-2-Clause", "https://nginx.org"),
+    (
+        "pkg:deb/openssh-client",
+        "SSH-OpenSSH",
+        "https://www.openssh.com",
+    ),
+    (
+        "pkg:deb/openssh-server",
+        "SSH-OpenSSH",
+        "https://www.openssh.com",
+    ),
+    ("pkg:deb/openssl", "Apache-2.0", "https://www.openssl.org"),
+    ("pkg:deb/apache2", "Apache-2.0", "https://httpd.apache.org"),
+    (
+        "pkg:deb/rsync",
+        "GPL-3.0-or-later",
+        "https://rsync.samba.org",
+    ),
+    ("pkg:deb/sudo", "ISC", "https://www.sudo.ws"),
+    ("pkg:deb/systemd", "LGPL-2.1-or-later", "https://systemd.io"),
+    (
+        "pkg:deb/tar",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/tar/",
+    ),
+    (
+        "pkg:deb/wget",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/wget/",
+    ),
+    ("pkg:deb/zlib1g", "Zlib", "https://zlib.net"),
+    (
+        "pkg:rpm/bash",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/bash/",
+    ),
+    (
+        "pkg:rpm/coreutils",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/coreutils/",
+    ),
+    ("pkg:rpm/curl", "curl", "https://curl.se"),
+    ("pkg:rpm/git", "GPL-2.0-only", "https://git-scm.com"),
+    (
+        "pkg:rpm/glibc",
+        "LGPL-2.1-or-later",
+        "https://www.gnu.org/software/libc/",
+    ),
+    ("pkg:rpm/httpd", "Apache-2.0", "https://httpd.apache.org"),
+    ("pkg:rpm/nginx", "BSD-2-Clause", "https://nginx.org"),
+    (
+        "pkg:rpm/openssh-clients",
+        "SSH-OpenSSH",
+        "https://www.openssh.com",
+    ),
+    (
+        "pkg:rpm/openssh-server",
+        "SSH-OpenSSH",
+        "https://www.openssh.com",
+    ),
+    ("pkg:rpm/openssl", "Apache-2.0", "https://www.openssl.org"),
+    (
+        "pkg:rpm/rsync",
+        "GPL-3.0-or-later",
+        "https://rsync.samba.org",
+    ),
+    ("pkg:rpm/sudo", "ISC", "https://www.sudo.ws"),
+    ("pkg:rpm/systemd", "LGPL-2.1-or-later", "https://systemd.io"),
+    (
+        "pkg:rpm/wget",
+        "GPL-3.0-or-later",
+        "https://www.gnu.org/software/wget/",
+    ),
+    ("pkg:rpm/zlib", "Zlib", "https://zlib.net"),
+    ("pkg:apk/busybox", "GPL-2.0-only", "https://busybox.net"),
+    ("pkg:apk/musl", "MIT", "https://musl.libc.org"),
+    ("pkg:npm/express", "MIT", "https://expressjs.com"),
+    ("pkg:npm/lodash", "MIT", "https://lodash.com"),
+    ("pkg:npm/react", "MIT", "https://react.dev"),
+    (
+        "pkg:pypi/django",
+        "BSD-3-Clause",
+        "https://www.djangoproject.com",
+    ),
+    (
+        "pkg:pypi/flask",
+        "BSD-3-Clause",
+        "https://flask.palletsprojects.com",
+    ),
+    ("pkg:pypi/numpy", "BSD-3-Clause", "https://numpy.org"),
+    (
+        "pkg:pypi/requests",
+        "Apache-2.0",
+        "https://requests.readthedocs.io",
+    ),
+    ("pkg:pypi/urllib3", "MIT", "https://urllib3.readthedocs.io"),
+    ("pkg:cargo/serde", "MIT OR Apache-2.0", "https://serde.rs"),
+    ("pkg:cargo/tokio", "MIT", "https://tokio.rs"),
+    ("pkg:gem/rails", "MIT", "https://rubyonrails.org"),
+    (
+        "pkg:maven/org.apache.logging.log4j/log4j-core",
+        "Apache-2.0",
+        "https://logging.apache.org/log4j/2.x/",
+    ),
+];
+
+/// What is known of a package.
+#[derive(Deserialize, Clone, Default)]
+pub struct PackageInfo {
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+}
+
+/// The packages to enrich components with, keyed by versionless purl.
+pub struct EnrichDb {
+    packages: HashMap<String, PackageInfo>,
+}
+
+impl EnrichDb {
+    /// The embedded packages.
+    pub fn embedded() -> Self {
+        let packages = EMBEDDED
+            .iter()
+            .filter_map(|(purl, license, homepage)| {
+                let info = PackageInfo {
+                    license: Some(license.to_string()),
+                    homepage: Some(homepage.to_string()),
+                };
+                Some((versionless(&PackageUrl::from_str(purl).ok()?), info))
+            })
+            .collect();
+        EnrichDb { packages }
+    }
+
+    /// Adds the packages of an `--enrich-db` file, a JSON object of versionless purls, e.g.
+    /// `{"pkg:deb/curl": {"license": "curl", "homepage": "https://curl.se"}}`, over the
+    /// embedded ones.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let entries: HashMap<String, PackageInfo> =
+            serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+        for (purl, info) in entries {
+            let parsed =
+                PackageUrl::from_str(&purl).map_err(|e| format!("Invalid purl {}: {}", purl, e))?;
+            self.packages.insert(versionless(&parsed), info);
+        }
+        Ok(())
+    }
+
+    /// Sets the license and homepage of each purl component found in the database.
+    pub fn enrich(&self, components: &mut [ComponentWithSource]) {
+        for component in components {
+            let SoftwareComponent::Purl(purl) = &component.component else {
+                continue;
+            };
+            let Some(info) = self.lookup(purl) else {
+                continue;
+            };
+            component.license = info.license.clone();
+            component.homepage = info.homepage.clone();
+        }
+    }
+
+    /// The entry for the package, or for a distribution package, the entry for it on
+    /// any distribution.
+    fn lookup(&self, purl: &PackageUrl) -> Option<&PackageInfo> {
+        if let Some(info) = self.packages.get(&versionless(purl)) {
+            return Some(info);
+        }
+        if !DISTRO_TYPES.contains(&purl.ty()) || purl.namespace().is_none() {
+            return None;
+        }
+        let any_distro = PackageUrl::new(purl.ty().to_string(), purl.name().to_string()).ok()?;
+        self.packages.get(&versionless(&any_distro))
+    }
+}
+
+/// The purl without its version, qualifiers or subpath, e.g. `pkg:deb/debian/curl`.
+fn versionless(purl: &PackageUrl) -> String {
+    let mut key = match PackageUrl::new(purl.ty().to_string(), purl.name().to_string()) {
+        Ok(key) => key,
+        Err(_) => return purl.to_string(),
+    };
+    if let Some(namespace) = purl.namespace() {
+        key.with_namespace(namespace.to_string());
+    }
+    key.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(purl: &str) -> ComponentWithSource {
+        ComponentWithSource {
+            component: SoftwareComponent::Purl(purl.parse().unwrap()),
+            source: "debian".to_string(),
+            license: None,
+            homepage: None,
+        }
+    }
+
+    #[test]
+    fn enriches_known_purls_from_fixture_db() {
+        let mut db = EnrichDb::embedded();
+        db.load(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/enrich_db.json"
+        )))
+        .unwrap();
+        let mut components = [
+            component("pkg:deb/debian/acme-agent@2.1.0?arch=amd64"),
+            component("pkg:deb/ubuntu/curl@8.5.0-2ubuntu10"),
+            component("pkg:deb/debian/bash@5.2.15-2"),
+            component("pkg:deb/ubuntu/acme-agent@2.1.0"),
+        ];
+        db.enrich(&mut components);
+
+        let fields: Vec<_> = components
+            .iter()
+            .map(|c| (c.license.as_deref(), c.homepage.as_deref()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (Some("MIT"), Some("https://acme.example")),
+                // The fixture's entry replaces the embedded one
+                (Some("curl"), Some("https://curl.se/")),
+                (
+                    Some("GPL-3.0-or-later"),
+                    Some("https://www.gnu.org/software/bash/")
+                ),
+                // Only known on Debian
+                (None, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+/// What kind of host the snapshot was taken on, so consumers can tell real hosts apart
+/// from containers and virtual machines.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HostEnvironment {
+    BareMetal,
+    Vm,
+    Container,
+    Wsl,
+    /// Detection is only implemented on Linux.
+    Unknown,
+}
+
+/// `/sys/class/dmi/id/sys_vendor` and `product_name` values reported by common hypervisors.
+const HYPERVISOR_VENDORS: [&str; 9] = [
+    "QEMU",
+    "KVM",
+    "VMware",
+    "VirtualBox",
+    "innotek GmbH",
+    "Xen",
+    "Amazon EC2",
+    "Google Compute Engine",
+    "Virtual Machine",
+];
+
+/// Cgroup path fragments used by container runtimes.
+const CONTAINER_CGROUPS: [&str; 5] = ["/docker", "/kubepods", "/containerd", "/lxc", "/libpod"];
+
+pub fn detect() -> HostEnvironment {
+    if !cfg!(target_os = "linux") {
+        return HostEnvironment::Unknown;
+    }
+
+    detect_from(
+        |path| std::fs::read_to_string(path).unwrap_or_default(),
+        |path| std::path::Path::new(path).exists(),
+    )
+}
+
+/// Classifies the host from the files `read` returns, empty when missing, and whether
+/// the marker files `exists`.
+fn detect_from(read: impl Fn(&str) -> String, exists: impl Fn(&str) -> bool) -> HostEnvironment {
+    // WSL 2 is also a VM, and may run containers, so it is checked first
+    if is_wsl(&read("/proc/version")) {
+        HostEnvironment::Wsl
+    } else if exists("/.dockerenv")
+        || exists("/run/.containerenv")
+        || is_container_cgroup(&read("/proc/1/cgroup"))
+    {
+        HostEnvironment::Container
+    } else if is_hypervisor_vendor(&read("/sys/class/dmi/id/sys_vendor"))
+        || is_hypervisor_vendor(&read("/sys/class/dmi/id/product_name"))
+        || has_hypervisor_flag(&read("/proc/cpuinfo"))
+    {
+        HostEnvironment::Vm
+    } else {
+        HostEnvironment::BareMetal
+    }
+}
+
+/// WSL kernels are built by Microsoft, e.g. `Linux version 5.15.90.1-microsoft-standard-WSL2`.
+pub fn is_wsl(proc_version: &str) -> bool {
+    proc_version.to_lowercase().contains("microsoft")
+}
+
+pub fn is_container_cgroup(cgroup: &str) -> bool {
+    cgroup.lines().any(|line| {
+        CONTAINER_CGROUPS
+            .iter()
+            .any(|fragment| line.contains(fragment))
+    })
+}
+
+pub fn is_hypervisor_vendor(dmi_value: &str) -> bool {
+    let dmi_value = dmi_value.trim();
+    HYPERVISOR_VENDORS
+        .iter()
+        .any(|vendor| dmi_value.contains(vendor))
+}
+
+/// x86 guests see a `hypervisor` CPU flag.
+pub fn has_hypervisor_flag(cpuinfo: &str) -> bool {
+    cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("flags"))
+        .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect_with(files: &[(&str, &str)]) -> HostEnvironment {
+        let files: HashMap<&str, &str> = files.iter().copied().collect();
+        detect_from(
+            |path| files.get(path).copied().unwrap_or_default().to_string(),
+            |path| files.contains_key(path),
+        )
+    }
+
+    #[test]
+    fn maps_proc_version_to_environment() {
+        for (proc_version, expected) in [
+            (
+                "Linux version 5.15.153.1-microsoft-standard-WSL2 (root@941d701f84f1) (gcc (GCC) 11.2.0) #1 SMP Fri Mar 29 23:14:13 UTC 2024",
+                HostEnvironment::Wsl,
+            ),
+            (
+                "Linux version 4.4.0-19041-Microsoft (Microsoft@Microsoft.com) (gcc version 5.4.0 (GCC) ) #3996-Microsoft Thu Jan 18 16:36:00 PST 2024",
+                HostEnvironment::Wsl,
+            ),
+            (
+                "Linux version 6.1.0-18-amd64 (debian-kernel@lists.debian.org) (gcc-12 (Debian 12.2.0-14) 12.2.0) #1 SMP PREEMPT_DYNAMIC Debian 6.1.76-1 (2024-02-01)",
+                HostEnvironment::BareMetal,
+            ),
+            (
+                "Linux version 5.14.0-362.8.1.el9_3.x86_64 (mockbuild@x86-vm-07.build.eng.bos.redhat.com) (gcc (GCC) 11.4.1 20230605 (Red Hat 11.4.1-2)) #1 SMP PREEMPT_DYNAMIC Tue Oct 3 11:12:36 EDT 2023",
+                HostEnvironment::BareMetal,
+            ),
+        ] {
+            assert_eq!(
+                detect_with(&[("/proc/version", proc_version)]),
+                expected,
+                "{}",
+                proc_version
+            );
+        }
+    }
+
+    #[test]
+    fn detects_containers_and_vms() {
+        let kernel = ("/proc/version", "Linux version 6.1.0-18-amd64");
+        assert_eq!(
+            detect_with(&[kernel, ("/.dockerenv", "")]),
+            HostEnvironment::Container
+        );
+        assert_eq!(
+            detect_with(&[
+                kernel,
+                (
+                    "/proc/1/cgroup",
+                    "0::/kubepods/besteffort/pod6f1b/0f2c5e1a9b7d\n"
+                )
+            ]),
+            HostEnvironment::Container
+        );
+        assert_eq!(
+            detect_with(&[kernel, ("/proc/1/cgroup", "0::/init.scope\n")]),
+            HostEnvironment::BareMetal
+        );
+        assert_eq!(
+            detect_with(&[kernel, ("/sys/class/dmi/id/sys_vendor", "QEMU\n")]),
+            HostEnvironment::Vm
+        );
+        assert_eq!(
+            detect_with(&[
+                kernel,
+                (
+                    "/proc/cpuinfo",
+                    "processor\t: 0\nflags\t\t: fpu vme hypervisor\n"
+                )
+            ]),
+            HostEnvironment::Vm
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::delta;
+use hsnap_purl_plugin::{ComponentWithSource, SoftwareComponent};
+use packageurl::PackageUrl;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// An entry of an `--extra-components` file: a purl string, or a full component as it
+/// appears in `software_components`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtraComponent {
+    Purl(String),
+    Component(SoftwareComponent),
+}
+
+/// Reads a JSON array of extra components from `path`.
+pub fn load(path: &Path) -> Result<Vec<SoftwareComponent>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let entries: Vec<ExtraComponent> =
+        serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+
+    entries
+        .into_iter()
+        .map(|entry| match entry {
+            ExtraComponent::Purl(purl) => PackageUrl::from_str(&purl)
+                .map(SoftwareComponent::Purl)
+                .map_err(|e| format!("Invalid purl {}: {}", purl, e)),
+            ExtraComponent::Component(component) => Ok(component),
+        })
+        .collect()
+}
+
+/// Appends the `extra` components that aren't already in `components`.
+pub fn merge(components: &mut Vec<ComponentWithSource>, extra: Vec<SoftwareComponent>) {
+    let mut seen: HashSet<String> = components
+        .iter()
+        .map(|component| delta::component_key(&component.component))
+        .collect();
+    for component in extra {
+        if seen.insert(delta::component_key(&component)) {
+            components.push(ComponentWithSource {
+                component,
+                source: "extra-components".to_string(),
+                license: None,
+                homepage: None,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn merges_extra_purls_from_file() {
+        let dir = TempDir::new();
+        let path = dir.path().join("extra.json");
+        std::fs::write(
+            &path,
+            r#"["pkg:generic/acme-agent@4.2.0", "pkg:deb/debian/bash@5.2.15-2", "pkg:npm/left-pad@1.3.0"]"#,
+        )
+        .unwrap();
+        let mut components = vec![ComponentWithSource {
+            component: SoftwareComponent::Purl("pkg:deb/debian/bash@5.2.15-2".parse().unwrap()),
+            source: "debian-dpkg".to_string(),
+            license: None,
+            homepage: None,
+        }];
+
+        merge(&mut components, load(&path).unwrap());
+        let merged: Vec<(String, &str)> = components
+            .iter()
+            .map(|found| {
+                (
+                    delta::component_key(&found.component),
+                    found.source.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                ("pkg:deb/debian/bash@5.2.15-2".to_string(), "debian-dpkg"),
+                (
+                    "pkg:generic/acme-agent@4.2.0".to_string(),
+                    "extra-components"
+                ),
+                ("pkg:npm/left-pad@1.3.0".to_string(), "extra-components"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_purl() {
+        let dir = TempDir::new();
+        let path = dir.path().join("extra.json");
+        std::fs::write(&path, r#"["not a purl"]"#).unwrap();
+        assert!(load(&path)
+            .unwrap_err()
+            .starts_with("Invalid purl not a purl"));
+    }
+}
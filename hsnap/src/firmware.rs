@@ -0,0 +1,104 @@
+use std::path::Path;
+
+const EFI_DIR: &str = "/sys/firmware/efi";
+/// The SecureBoot variable, under the EFI global variable GUID.
+const SECURE_BOOT_EFIVAR: &str =
+    "/sys/firmware/efi/efivars/SecureBoot-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+const TPM_CLASS_DIR: &str = "/sys/class/tpm";
+
+/// Whether the firmware booted with Secure Boot enforced. `Some(false)` on a host booted
+/// without UEFI, `None` where it can't be told, e.g. when efivarfs isn't mounted.
+pub fn secure_boot() -> Option<bool> {
+    if cfg!(target_os = "windows") {
+        return windows_secure_boot();
+    }
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    if !Path::new(EFI_DIR).exists() {
+        return Some(false);
+    }
+    parse_secure_boot_efivar(&std::fs::read(SECURE_BOOT_EFIVAR).ok()?)
+}
+
+/// An efivarfs file is the variable's 4 byte attributes, then its data: for SecureBoot,
+/// a single byte that is 1 when Secure Boot is enforced.
+fn parse_secure_boot_efivar(contents: &[u8]) -> Option<bool> {
+    contents.get(4).map(|enabled| *enabled == 1)
+}
+
+/// Whether the host has a TPM, or `None` where it can't be told.
+pub fn tpm_present() -> Option<bool> {
+    if cfg!(target_os = "windows") {
+        return windows_tpm_present();
+    }
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    match std::fs::read_dir(TPM_CLASS_DIR) {
+        Ok(mut devices) => Some(devices.next().is_some()),
+        // Kernels without TPM support have no tpm class
+        Err(_) if Path::new("/dev/tpm0").exists() => Some(true),
+        Err(_) => Path::new("/sys/class").exists().then_some(false),
+    }
+}
+
+/// Read from the registry rather than with `Confirm-SecureBootUEFI`, which needs an
+/// elevated PowerShell.
+fn windows_secure_boot() -> Option<bool> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+
+        // Only present on UEFI systems
+        match RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey("SYSTEM\\CurrentControlSet\\Control\\SecureBoot\\State")
+        {
+            Ok(state) => state
+                .get_value::<u32, _>("UEFISecureBootEnabled")
+                .ok()
+                .map(|enabled| enabled == 1),
+            Err(_) => Some(false),
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+/// Asks WMI's `Win32_Tpm`, which is only readable by administrators.
+fn windows_tpm_present() -> Option<bool> {
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "[bool](Get-CimInstance -Namespace root/cimv2/security/microsofttpm \
+             -ClassName Win32_Tpm -ErrorAction Stop)",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "True" => Some(true),
+        "False" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_secure_boot_efivar() {
+        // EFI_VARIABLE_BOOTSERVICE_ACCESS | EFI_VARIABLE_RUNTIME_ACCESS, little-endian
+        assert_eq!(parse_secure_boot_efivar(&[0x06, 0, 0, 0, 1]), Some(true));
+        assert_eq!(parse_secure_boot_efivar(&[0x06, 0, 0, 0, 0]), Some(false));
+        // The attributes alone, or an unreadable variable
+        assert_eq!(parse_secure_boot_efivar(&[0x06, 0, 0, 0]), None);
+        assert_eq!(parse_secure_boot_efivar(&[]), None);
+    }
+}
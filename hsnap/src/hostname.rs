@@ -0,0 +1,136 @@
+/// The fully qualified domain name of a host called `host_name`, found the way
+/// `hostname -f` would without DNS: the canonical name `/etc/hosts` gives it, or the
+/// name with the `domain` (or first `search` domain) of `/etc/resolv.conf` appended. On
+/// Windows the domain comes from the TCP/IP parameters in the registry. `None` if no
+/// domain is configured.
+pub fn fqdn(host_name: &str) -> Option<String> {
+    fqdn_from(host_name, |path| {
+        std::fs::read_to_string(path).unwrap_or_default()
+    })
+}
+
+/// `fqdn`, reading files with `read`.
+fn fqdn_from(host_name: &str, read: impl Fn(&str) -> String) -> Option<String> {
+    if host_name.contains('.') {
+        return Some(host_name.to_string());
+    }
+    let domain = if cfg!(target_os = "windows") {
+        windows_domain()
+    } else {
+        if let Some(fqdn) = fqdn_from_hosts(&read("/etc/hosts"), host_name) {
+            return Some(fqdn);
+        }
+        domain_from_resolv_conf(&read("/etc/resolv.conf"))
+    };
+    domain.map(|domain| format!("{}.{}", host_name, domain))
+}
+
+/// The first name on the `/etc/hosts` line listing `host_name`, if it is `host_name`
+/// qualified with a domain.
+fn fqdn_from_hosts(contents: &str, host_name: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        // The address, then the canonical name, then aliases
+        let names: Vec<&str> = line.split_whitespace().skip(1).collect();
+        let canonical = names.first()?;
+        let qualified = canonical
+            .strip_prefix(host_name)
+            .is_some_and(|domain| domain.len() > 1 && domain.starts_with('.'));
+        (names.contains(&host_name) && qualified).then(|| canonical.to_string())
+    })
+}
+
+/// The `domain`, or else the first `search` domain, of a `resolv.conf`.
+fn domain_from_resolv_conf(contents: &str) -> Option<String> {
+    let mut search = None;
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("domain") => {
+                if let Some(domain) = fields.next() {
+                    return Some(domain.trim_end_matches('.').to_string());
+                }
+            }
+            Some("search") => {
+                search = search.or_else(|| fields.next().map(str::to_string));
+            }
+            _ => {}
+        }
+    }
+    // A search domain of "." means none
+    search
+        .map(|domain| domain.trim_end_matches('.').to_string())
+        .filter(|domain| !domain.is_empty())
+}
+
+/// The primary DNS suffix, or failing that the one assigned by DHCP.
+fn windows_domain() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+
+        let parameters = RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey("SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Parameters")
+            .ok()?;
+        ["Domain", "DhcpDomain"].iter().find_map(|value| {
+            parameters
+                .get_value::<String, _>(value)
+                .ok()
+                .filter(|domain| !domain.is_empty())
+        })
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    fn files<'a>(hosts: &'a str, resolv_conf: &'a str) -> impl Fn(&str) -> String + 'a {
+        move |path| match path {
+            "/etc/hosts" => hosts.to_string(),
+            "/etc/resolv.conf" => resolv_conf.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn resolves_fqdn_from_hosts_and_resolv_conf() {
+        let hosts = "127.0.0.1 localhost\n\
+                     # 10.0.0.1 web01.old.example web01\n\
+                     10.0.0.5 web01.corp.example.com web01 # static\n";
+        let resolv_conf = "search lab.example.net corp.example.com\nnameserver 10.0.0.2\n";
+        assert_eq!(
+            fqdn_from("web01", files(hosts, resolv_conf)).as_deref(),
+            Some("web01.corp.example.com")
+        );
+        // Not in /etc/hosts, so the first search domain
+        assert_eq!(
+            fqdn_from("db02", files(hosts, resolv_conf)).as_deref(),
+            Some("db02.lab.example.net")
+        );
+        // domain wins over search
+        let resolv_conf = "search lab.example.net\ndomain example.org.\n";
+        assert_eq!(
+            fqdn_from("db02", files("", resolv_conf)).as_deref(),
+            Some("db02.example.org")
+        );
+        assert_eq!(
+            fqdn_from("db02.example.org", files("", "")).as_deref(),
+            Some("db02.example.org")
+        );
+    }
+
+    #[test]
+    fn no_fqdn_without_a_domain() {
+        assert_eq!(fqdn_from("web01", files("", "")), None);
+        assert_eq!(fqdn_from("web01", files("", "search .\n")), None);
+        // A canonical name that isn't web01 qualified
+        let hosts = "10.0.0.5 frontend.example.com web01\n";
+        assert_eq!(fqdn_from("web01", files(hosts, "")), None);
+    }
+}
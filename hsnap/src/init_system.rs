@@ -0,0 +1,73 @@
+use std::path::Path;
+use std::process::Command;
+
+/// OpenRC keeps its state here while it manages the services.
+const OPENRC_STATE_DIR: &str = "/run/openrc";
+
+/// The host's init system and its version, e.g. `systemd` and `252`. The version is
+/// asked of the manager's command, so it is left out with `no_shell`.
+pub fn detect(no_shell: bool) -> (Option<String>, Option<String>) {
+    if cfg!(target_os = "macos") {
+        return (Some("launchd".to_string()), None);
+    }
+    if !cfg!(target_os = "linux") {
+        return (None, None);
+    }
+    let Ok(comm) = std::fs::read_to_string("/proc/1/comm") else {
+        return (None, None);
+    };
+    let Some(init_system) = from_comm(comm.trim(), Path::new(OPENRC_STATE_DIR).is_dir()) else {
+        return (None, None);
+    };
+    let version = match init_system {
+        "systemd" if !no_shell => version("systemctl"),
+        "openrc" if !no_shell => version("openrc"),
+        _ => None,
+    };
+    (Some(init_system.to_string()), version)
+}
+
+/// The init system PID 1's command name belongs to. OpenRC usually runs under SysV's (or
+/// busybox's) `init`, so that is told apart by whether OpenRC is running. Other commands,
+/// such as a container's entrypoint, aren't init systems.
+fn from_comm(comm: &str, openrc_running: bool) -> Option<&'static str> {
+    match comm {
+        "systemd" => Some("systemd"),
+        "openrc-init" => Some("openrc"),
+        "init" if openrc_running => Some("openrc"),
+        "init" => Some("sysvinit"),
+        "runit" | "runit-init" => Some("runit"),
+        "s6-svscan" => Some("s6"),
+        _ => None,
+    }
+}
+
+/// The first number in the first line of `program --version`, e.g. `252` from
+/// `systemd 252 (252.22-1~deb12u1)` or `0.44.10` from `openrc (OpenRC) 0.44.10 (Gentoo Linux)`.
+fn version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .next()?
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_pid_1_comm_to_init_system() {
+        assert_eq!(from_comm("systemd", false), Some("systemd"));
+        assert_eq!(from_comm("openrc-init", false), Some("openrc"));
+        assert_eq!(from_comm("init", true), Some("openrc"));
+        assert_eq!(from_comm("init", false), Some("sysvinit"));
+        assert_eq!(from_comm("runit", false), Some("runit"));
+        assert_eq!(from_comm("s6-svscan", false), Some("s6"));
+        // A container's entrypoint
+        assert_eq!(from_comm("nginx", false), None);
+    }
+}
@@ -0,0 +1,121 @@
+use clap::ValueEnum;
+use std::path::Path;
+use std::process::Command;
+
+/// systemd's machine ID, and where D-Bus kept it before systemd took it over.
+const MACHINE_ID_FILES: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// What the snapshot's id defaults to when `--id` isn't given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum IdSource {
+    /// The hostname, or with `--fqdn-id`, the fully qualified domain name.
+    #[default]
+    Hostname,
+    /// The machine ID, falling back to the hostname where there is none.
+    MachineId,
+}
+
+/// An identifier of the OS installation that, unlike the hostname, doesn't change:
+/// systemd's machine ID on Linux, the hardware UUID on macOS and the MachineGuid Windows
+/// generates at install.
+pub fn read() -> Option<String> {
+    if cfg!(target_os = "windows") {
+        return windows_machine_guid();
+    }
+    if cfg!(target_os = "macos") {
+        let output = Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        return parse_ioreg_uuid(&String::from_utf8_lossy(&output.stdout));
+    }
+    from_files(&MACHINE_ID_FILES.map(Path::new))
+}
+
+/// The machine ID in the first of `paths` that holds one.
+fn from_files(paths: &[&Path]) -> Option<String> {
+    paths.iter().find_map(|path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| parse_machine_id(&contents))
+    })
+}
+
+/// A machine ID file holds 32 lowercase hex digits and a newline. It is empty, or says
+/// `uninitialized`, on an image that hasn't booted yet.
+fn parse_machine_id(contents: &str) -> Option<String> {
+    let id = contents.trim();
+    (id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| id.to_lowercase())
+}
+
+/// The `"IOPlatformUUID" = "4C4C4544-0042-3510-8052-B4C04F564433"` line of `ioreg`.
+fn parse_ioreg_uuid(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "\"IOPlatformUUID\"").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn windows_machine_guid() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+
+        RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey("SOFTWARE\\Microsoft\\Cryptography")
+            .and_then(|key| key.get_value::<String, _>("MachineGuid"))
+            .ok()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn reads_machine_id_file() {
+        let dir = TempDir::new();
+        let etc = dir.path().join("machine-id");
+        let dbus = dir.path().join("dbus-machine-id");
+        std::fs::write(&dbus, "0F1E2D3C4B5A69788796A5B4C3D2E1F0\n").unwrap();
+
+        // Not yet written by systemd, so D-Bus's is used
+        assert_eq!(
+            from_files(&[&etc, &dbus]).as_deref(),
+            Some("0f1e2d3c4b5a69788796a5b4c3d2e1f0")
+        );
+        std::fs::write(&etc, "uninitialized\n").unwrap();
+        assert_eq!(
+            from_files(&[&etc, &dbus]).as_deref(),
+            Some("0f1e2d3c4b5a69788796a5b4c3d2e1f0")
+        );
+        std::fs::write(&etc, "4c4c4544004235108052b4c04f564433\n").unwrap();
+        assert_eq!(
+            from_files(&[&etc, &dbus]).as_deref(),
+            Some("4c4c4544004235108052b4c04f564433")
+        );
+        std::fs::write(&etc, "").unwrap();
+        std::fs::remove_file(&dbus).unwrap();
+        assert_eq!(from_files(&[&etc, &dbus]), None);
+    }
+
+    #[test]
+    fn parses_ioreg_platform_uuid() {
+        let output = "+-o MacBookPro18,3  <class IOPlatformExpertDevice>\n\
+            {\n\
+            \x20 \"IOPlatformSerialNumber\" = \"C02XXXXXXXXX\"\n\
+            \x20 \"IOPlatformUUID\" = \"4C4C4544-0042-3510-8052-B4C04F564433\"\n\
+            }\n";
+        assert_eq!(
+            parse_ioreg_uuid(output).as_deref(),
+            Some("4C4C4544-0042-3510-8052-B4C04F564433")
+        );
+    }
+}
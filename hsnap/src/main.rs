@@ -1,47 +1,774 @@
+use cache::InventoryCache;
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use hsnap_purl_plugin::{self, SoftwareComponent};
-use reqwest::Client;
-use rsa::pkcs1::DecodeRsaPrivateKey;
-use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+use clap::{Parser, Subcommand};
+use encryption::{EncryptedEnvelope, EncryptionKey};
+use hsnap_purl_plugin::users::PasswdEntry;
+use hsnap_purl_plugin::{self, CommandPolicy, ComponentWithSource, PluginOptions, SkippedProbe};
 use serde::{Deserialize, Serialize};
-use sysinfo::{Components, Disks, Networks, System, Users};
+use signing::{
+    EcdsaSignatureFormat, EcdsaSigningKey, SignatureEncoding, Signer, SigningAlgorithm, SigningKey,
+};
+use sink::{FileSink, HttpSink, Payload, S3Sink, SnapshotSink, StdoutSink};
+use spool::Spool;
+use state::State;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{
+    Components, CpuRefreshKind, Disks, MemoryRefreshKind, Networks, RefreshKind, System, Users,
+    MINIMUM_CPU_UPDATE_INTERVAL,
+};
 
-#[derive(Parser, Debug)]
+mod baseline;
+mod boot_entries;
+mod cache;
+mod components_only;
+mod cpu_features;
+mod delta;
+mod encryption;
+mod enrich;
+mod environment;
+mod extra_components;
+mod firmware;
+mod hostname;
+mod init_system;
+mod machine_id;
+mod mounts;
+mod msgpack;
+mod numa;
+mod overlay;
+mod package_index;
+mod privileges;
+mod processes;
+mod profiles;
+mod repositories;
+mod routes;
+mod routing;
+mod s3;
+mod scheduled_tasks;
+mod sections;
+mod signing;
+mod sink;
+mod snapshot_digest;
+mod spool;
+mod state;
+mod summary;
+mod swaps;
+mod system_profiler;
+mod systemd;
+#[cfg(test)]
+mod test_support;
+mod trust_store;
+mod verify;
+
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// ID to map hsnap to a host. Defaults to hostname if not provided.
     #[arg(long)]
     id: Option<String>,
 
-    /// URL to POST the JSON data to.
+    /// Default the id to the fully qualified domain name, from /etc/hosts or the
+    /// resolv.conf domain, rather than the short hostname. The short hostname is
+    /// still used if no domain is configured.
+    #[arg(long)]
+    fqdn_id: bool,
+
+    /// What the id defaults to: the hostname, or the machine ID, which doesn't change when
+    /// the host is renamed.
+    #[arg(long, value_enum, default_value_t = machine_id::IdSource::Hostname)]
+    id_source: machine_id::IdSource,
+
+    /// URL to POST the JSON data to. Can be repeated to send to several endpoints.
+    /// An s3://bucket/prefix url uploads the snapshot as an object instead, using the standard AWS credential env vars
+    /// or the shared credentials file and config profile. Instance roles (IMDS), web identity tokens and SSO aren't
+    /// supported; export their credentials into the environment first.
+    #[arg(long)]
+    url: Vec<String>,
+
+    /// Send a section to its own url instead of with the rest of the snapshot, as
+    /// SECTION=URL, e.g. "processes=https://example.com/processes". The section is POSTed as
+    /// JSON Lines, a record per line, and listed in routed_sections. Only list sections can
+    /// be routed, and they are neither signed nor encrypted. Can be repeated.
+    #[arg(
+        long,
+        value_name = "SECTION=URL",
+        value_parser = parse_route,
+        conflicts_with_all = ["encrypt_key", "encrypt_key_file", "delta", "baseline_file"]
+    )]
+    route: Vec<(String, String)>,
+
+    /// User-Agent to send with snapshots. Defaults to hsnap/<version>.
     #[arg(long)]
-    url: Option<String>,
+    user_agent: Option<String>,
+
+    /// Add the host id to the User-Agent, as "hsnap/<version> (<id>)".
+    #[arg(long)]
+    user_agent_host_id: bool,
+
+    /// PEM file of the CA certificates to trust for HTTPS urls, in place of the built-in
+    /// roots.
+    #[arg(long, value_name = "PEM")]
+    tls_roots_file: Option<PathBuf>,
+
+    /// Accept any server certificate, even expired, self-signed or for another host. Only
+    /// ever for testing: it leaves snapshots open to interception.
+    #[arg(long)]
+    danger_accept_invalid_certs: bool,
 
     /// The private key used to sign this data, as a string.
     #[arg(long)]
     signing_key: Option<String>,
+
+    /// File containing a private key to sign this data with. Repeat it, or combine it with
+    /// --signing-key, to sign with several keys during a key rotation, giving the envelope
+    /// a list of signatures.
+    #[arg(long, value_name = "PATH")]
+    signing_key_file: Vec<PathBuf>,
+
+    /// The kind of key --signing-key and --signing-key-file hold, and so how they sign.
+    #[arg(long, value_enum, default_value_t = SigningAlgorithm::Rsa)]
+    signing_algorithm: SigningAlgorithm,
+
+    /// How ECDSA signatures are written, recorded in the envelope as `signature_format`.
+    #[arg(long, value_enum, default_value_t = EcdsaSignatureFormat::Der)]
+    ecdsa_signature_format: EcdsaSignatureFormat,
+
+    /// Hex AES-256 key to encrypt the snapshot with, after signing it. Prefer --encrypt-key-file,
+    /// as arguments are visible to other users of the host.
+    #[arg(long, conflicts_with = "encrypt_key_file")]
+    encrypt_key: Option<String>,
+
+    /// File containing the hex AES-256 key to encrypt the snapshot with.
+    #[arg(long)]
+    encrypt_key_file: Option<PathBuf>,
+
+    /// Adds a digest of the snapshot, a hash of its canonical JSON, for integrity checks
+    /// without a signing key.
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    digest: Option<snapshot_digest::DigestAlgorithm>,
+
+    /// How the signature is encoded in the signed snapshot.
+    #[arg(long, value_enum, default_value_t = SignatureEncoding::Hex)]
+    signature_encoding: SignatureEncoding,
+
+    /// Emit the exact bytes that were signed, as a string in a `signed` field, instead of
+    /// re-serializing the signed envelope, so a verifier can check them as they are.
+    #[arg(long)]
+    no_signature_reserialize: bool,
+
+    /// Maximum number of seconds to spend capturing. Sections not collected in time are left out
+    /// and the snapshot is marked as truncated.
+    #[arg(long, value_name = "SECONDS")]
+    deadline: Option<u64>,
+
+    /// Write the JSON data to this file instead of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Print a table of the software components, after a line about the host, instead of
+    /// the JSON. For reading on a terminal.
+    #[arg(long, conflicts_with_all = ["url", "output", "route"])]
+    summary: bool,
+
+    /// Output only the software components, as a top-level JSON array, for consumers that
+    /// don't take the snapshot object. Written wherever the snapshot would be.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "summary",
+            "signing_key",
+            "signing_key_file",
+            "encrypt_key",
+            "encrypt_key_file",
+            "digest",
+            "delta",
+            "changed_sections",
+            "baseline_file",
+        ]
+    )]
+    components_only_json: bool,
+
+    /// How --components-only-json writes each component.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = components_only::ComponentStyle::Object,
+        requires = "components_only_json"
+    )]
+    component_style: components_only::ComponentStyle,
+
+    /// File used to remember what was last sent to each url, so unchanged snapshots are not re-sent.
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// Directory to keep snapshots that couldn't be delivered in, sending them once the
+    /// url accepts them again.
+    #[arg(long, value_name = "DIR")]
+    spool_dir: Option<PathBuf>,
+
+    /// Consecutive failed deliveries after which a url is backed off from, when
+    /// --state-file is given. 0 never backs off.
+    #[arg(long, default_value_t = 3)]
+    breaker_threshold: u32,
+
+    /// Longest the backoff from a failing url grows to, in seconds.
+    #[arg(long, default_value_t = 3600)]
+    breaker_max_backoff: u64,
+
+    /// Project root containing a composer.lock to scan for PHP dependencies. Can be repeated.
+    #[arg(long)]
+    composer_root: Vec<PathBuf>,
+
+    /// Directory to search for Python virtualenvs (any directory with a pyvenv.cfg), up
+    /// to 4 levels deep. Can be repeated.
+    #[arg(long)]
+    venv_root: Vec<PathBuf>,
+
+    /// Detect the installed fonts, system-wide and in each user's font directory, as
+    /// pkg:generic/font purls.
+    #[arg(long)]
+    fonts: bool,
+
+    /// Read software components from the filesystem mounted here, e.g. an extracted
+    /// container image, instead of the live host. Other sections still describe the host.
+    #[arg(long, value_name = "PATH")]
+    root: Option<PathBuf>,
+
+    /// Never run external programs: read the dpkg status file directly, collect no rpm
+    /// packages and skip plugins' command probes.
+    #[arg(long)]
+    no_shell: bool,
+
+    /// Only let plugins' command probes run these programs, named as a file name or path,
+    /// e.g. "dpkg-query,rpm". Only programs run directly are checked: a shell command runs
+    /// only if the shell (sh, or cmd on Windows) is listed, and may then run anything. The
+    /// dpkg status file is read directly when dpkg-query isn't allowed.
+    #[arg(long, value_delimiter = ',', value_name = "PROGRAMS")]
+    probe_allowlist: Option<Vec<String>>,
+
+    /// Never let plugins' command probes run these programs, named as a file name or path.
+    /// A shell command can't be checked, so with only a denylist none run.
+    #[arg(long, value_delimiter = ',', value_name = "PROGRAMS")]
+    probe_denylist: Vec<String>,
+
+    /// Skip every plugin command probe, still checking files, globs and the registry. The
+    /// dpkg status file is read directly.
+    #[arg(long, conflicts_with = "probe_allowlist")]
+    no_commands: bool,
+
+    /// Maximum number of software components to collect. Any beyond this are dropped
+    /// and the snapshot is marked with components_truncated.
+    #[arg(long, value_name = "N")]
+    max_components: Option<usize>,
+
+    /// Only run plugins producing these kinds of component, e.g. "deb,rpm". Kinds are purl
+    /// types, or windows-component, windows-driver and browser-extension. All plugins run
+    /// by default.
+    #[arg(long, value_delimiter = ',')]
+    component_kinds: Vec<String>,
+
+    /// Seconds to wait for a package manager (apt, dnf, ...) to release the package database
+    /// before skipping it, rather than querying it mid-transaction.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    package_lock_timeout: u64,
+
+    /// Bytes of output to keep from each command a plugin runs. A command writing more
+    /// is stopped and its output truncated.
+    #[arg(long, value_name = "BYTES", default_value_t = hsnap_purl_plugin::MAX_COMMAND_OUTPUT_BYTES)]
+    max_command_output: u64,
+
+    /// Exit with an error, outputting nothing, when a plugin or section fails or --deadline
+    /// expires, rather than outputting what could be collected. A plugin fails when it
+    /// panics, or a file it reads exists but can't be read, or a command it runs is
+    /// installed but fails.
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Directory of JSON plugin manifests, each describing files or commands to probe and
+    /// the component to report from them. May be repeated. Manifests can run commands, so
+    /// the directory should only be writable by whoever runs hsnap.
+    #[arg(long, value_name = "DIR")]
+    plugin_dir: Vec<PathBuf>,
+
+    /// JSON file with an array of extra software components to include, each either a purl
+    /// string or a component object as found in software_components. Duplicates are dropped.
+    #[arg(long, value_name = "PATH")]
+    extra_components: Option<PathBuf>,
+
+    /// Add the license and homepage of well known packages to their components, from a
+    /// database built into hsnap and any --enrich-db.
+    #[arg(long)]
+    enrich: bool,
+
+    /// JSON file of more packages for --enrich, an object of versionless purls to their
+    /// license and homepage, e.g. {"pkg:deb/curl": {"license": "curl", "homepage":
+    /// "https://curl.se"}}. Its entries take precedence over the built-in ones.
+    #[arg(long, value_name = "PATH", requires = "enrich")]
+    enrich_db: Option<PathBuf>,
+
+    /// Extra qualifier to add to every purl from a plugin, as PLUGIN:KEY=VALUE, e.g.
+    /// "debian-dpkg:repository_url=https://deb.example.com". Can be repeated.
+    #[arg(long, value_name = "PLUGIN:KEY=VALUE", value_parser = parse_purl_qualifier)]
+    purl_qualifier: Vec<(String, String, String)>,
+
+    /// Directory to cache the software inventory in between runs. Within --cache-ttl, and as long
+    /// as the package databases are unchanged, the cached inventory is reused instead of running the plugins.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// How long a cached software inventory stays valid.
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    cache_ttl: u64,
+
+    /// Write empty lists such as services and software_components as [] instead of leaving
+    /// them out, for consumers that expect a fixed schema.
+    #[arg(long)]
+    include_empty: bool,
+
+    /// Include the configured yum/dnf repositories and apt sources.
+    #[arg(long)]
+    repositories: bool,
+
+    /// Include cron jobs (from the system crontabs and each user's crontab) or, on Windows,
+    /// the Task Scheduler's tasks.
+    #[arg(long)]
+    scheduled_tasks: bool,
+
+    /// Include these environment variables of hsnap's own environment, e.g.
+    /// "PATH,JAVA_HOME,HTTPS_PROXY". No others are ever included, as they may hold secrets.
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    env_vars: Vec<String>,
+
+    /// Include the CA certificates in the system trust store, flagging expired ones.
+    #[arg(long)]
+    trusted_cas: bool,
+
+    /// Include the boot menu's entries, from Boot Loader Specification entry files
+    /// (systemd-boot's) and grub.cfg, marking the default.
+    #[arg(long)]
+    boot_entries: bool,
+
+    /// Include the configuration profiles installed on macOS, e.g. by an MDM server. Listing
+    /// them needs root.
+    #[arg(long)]
+    profiles: bool,
+
+    /// Percentage of a disk in use at which it is flagged with low_space.
+    #[arg(long, value_name = "PERCENT", default_value_t = 90.0, value_parser = parse_percent)]
+    low_space_threshold: f64,
+
+    /// Include overlay mounts, such as running containers' root filesystems, with the
+    /// layers stacked in each and, for docker's, the image layer digests.
+    #[arg(long)]
+    overlay_layers: bool,
+
+    /// List each filesystem mounted more than once, e.g. by bind mounts, as one disk with
+    /// the other mount points in also_mounted_at, rather than marking the repeats with
+    /// duplicate_of.
+    #[arg(long)]
+    dedupe_mounts: bool,
+
+    /// Include the N processes using the most CPU, or memory with --top-by mem.
+    #[arg(long, value_name = "N", conflicts_with = "deterministic")]
+    top_processes: Option<usize>,
+
+    /// What --top-processes ranks processes by.
+    #[arg(long, value_enum, default_value_t = processes::TopBy::Cpu)]
+    top_by: processes::TopBy,
+
+    /// Send each url only the software components added and removed since the last snapshot
+    /// it accepted, tracked in --state-file. The full snapshot is sent when there is no baseline yet.
+    #[arg(long, requires = "state_file")]
+    delta: bool,
+
+    /// Send each url only the sections that changed since the last snapshot it accepted,
+    /// tracked in --state-file. Unchanged sections are left out and listed in omitted_sections.
+    #[arg(long, requires = "state_file")]
+    changed_sections: bool,
+
+    /// Output only the software components added and removed since the last run, as
+    /// software_delta, keeping the components seen in this file between runs. The full
+    /// snapshot is output when there is no baseline yet, or the capture was incomplete.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["delta", "changed_sections"])]
+    baseline_file: Option<PathBuf>,
+
+    /// Set the snapshot's timestamps to this RFC 3339 time, e.g. "2024-01-15T09:30:00Z",
+    /// rather than the host's clock, which may be wrong. SOURCE_DATE_EPOCH, in seconds
+    /// since 1970, does the same when this isn't given.
+    #[arg(long, value_name = "RFC3339", value_parser = parse_timestamp)]
+    timestamp: Option<DateTime<Utc>>,
+
+    /// Make the output reproducible: the timestamps are set from --timestamp or
+    /// SOURCE_DATE_EPOCH (or 1970-01-01), readings such as CPU usage, memory in use, temperatures and free disk
+    /// space are zeroed, and lists are sorted, so an unchanged host gives identical output.
+    #[arg(long)]
+    deterministic: bool,
+}
+
+/// Parses a `--purl-qualifier` of the form `PLUGIN:KEY=VALUE`.
+fn parse_purl_qualifier(arg: &str) -> Result<(String, String, String), String> {
+    let (plugin, qualifier) = arg
+        .split_once(':')
+        .ok_or("expected PLUGIN:KEY=VALUE, e.g. debian-dpkg:distro=debian-12")?;
+    let (key, value) = qualifier
+        .split_once('=')
+        .ok_or("expected PLUGIN:KEY=VALUE, e.g. debian-dpkg:distro=debian-12")?;
+    if plugin.is_empty() || key.is_empty() {
+        return Err("plugin and qualifier key can't be empty".to_string());
+    }
+    Ok((plugin.to_string(), key.to_string(), value.to_string()))
+}
+
+/// Parses a percentage from 0 to 100.
+fn parse_percent(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err("expected a percentage from 0 to 100".to_string()),
+    }
+}
+
+fn parse_timestamp(arg: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(arg)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|_| "expected an RFC 3339 time, e.g. 2024-01-15T09:30:00Z".to_string())
+}
+
+/// Parses a `--route` of the form `SECTION=URL`.
+fn parse_route(arg: &str) -> Result<(String, String), String> {
+    let (section, url) = arg
+        .split_once('=')
+        .ok_or("expected SECTION=URL, e.g. processes=https://example.com/processes")?;
+    if !routing::ROUTABLE.contains(&section) {
+        return Err(format!(
+            "{} can't be routed, only {}",
+            section,
+            routing::ROUTABLE.join(", ")
+        ));
+    }
+    if s3::S3Location::parse(url).is_some() {
+        return Err("sections can only be routed to http(s) urls".to_string());
+    }
+    Ok((section.to_string(), url.to_string()))
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Commands {
+    /// Install a systemd service and timer that run hsnap on a schedule.
+    InstallSystemd {
+        /// systemd OnCalendar= expression for when to run, e.g. "daily" or "*-*-* 02:00:00".
+        #[arg(long, default_value = "daily")]
+        interval: String,
+
+        /// Directory to write the unit files to.
+        #[arg(long, default_value_os_t = systemd::default_unit_dir())]
+        unit_dir: PathBuf,
+
+        /// Overwrite existing unit files.
+        #[arg(long)]
+        force: bool,
+
+        /// Run `systemctl enable --now` on the timer after installing.
+        #[arg(long)]
+        enable: bool,
+
+        /// Arguments to pass to hsnap on each run, after `--`.
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Remove the systemd service and timer installed by install-systemd.
+    UninstallSystemd {
+        /// Directory the unit files were written to.
+        #[arg(long, default_value_os_t = systemd::default_unit_dir())]
+        unit_dir: PathBuf,
+
+        /// Run `systemctl disable --now` on the timer before removing it.
+        #[arg(long)]
+        disable: bool,
+    },
+    /// Check the signature of a signed snapshot, read from a file or fetched from a URL.
+    Verify {
+        /// PEM public key of the key the snapshot should be signed with.
+        #[arg(long, value_name = "PATH")]
+        public_key: PathBuf,
+
+        /// Signed snapshot to check.
+        #[arg(
+            long,
+            value_name = "PATH",
+            required_unless_present = "url",
+            conflicts_with = "url"
+        )]
+        file: Option<PathBuf>,
+
+        /// GET the signed snapshot to check from this URL.
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+/// The most a snapshot fetched by `verify` from a url may take up. Far beyond any real
+/// host.
+const MAX_SNAPSHOT_BYTES: usize = 256 * 1024 * 1024;
+
+/// Set from `--include-empty`, before anything is serialized.
+static INCLUDE_EMPTY: AtomicBool = AtomicBool::new(false);
+
+#[cfg(test)]
+thread_local! {
+    /// Stands in for `--include-empty` in a test, without changing the output of the
+    /// tests running alongside it.
+    static INCLUDE_EMPTY_IN_TEST: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Leaves empty lists out of the JSON, unless `--include-empty` was given. Every list
+/// field that may be empty uses this rather than `Vec::is_empty`, so the flag covers
+/// them all.
+fn skip_empty<T>(list: &[T]) -> bool {
+    #[cfg(test)]
+    if INCLUDE_EMPTY_IN_TEST.with(|include| include.get()) {
+        return false;
+    }
+    list.is_empty() && !INCLUDE_EMPTY.load(Ordering::Relaxed)
 }
 
 #[derive(Serialize)]
 struct SignedSnapshot {
+    // Every other field of the envelope is covered by the signature
+    #[serde(flatten)]
+    signed: SignedContent,
+    // The signature is serialized as a Hex string unless --signature-encoding says otherwise
+    signature: String,
+}
+
+/// A snapshot signed with several keys, each signature listed with its key.
+#[derive(Serialize)]
+struct MultiSignedSnapshot {
+    // Every other field of the envelope is covered by each of the signatures
+    #[serde(flatten)]
+    signed: MultiSignedContent,
+    signatures: Vec<KeySignature>,
+}
+
+/// The part of a `MultiSignedSnapshot` every signature covers: the envelope with its
+/// `signatures` field removed.
+#[derive(Serialize)]
+struct MultiSignedContent {
     snapshot: HostSnapshot,
-    // The signature is serialized as a Hex string (default for rsa+serde)
+    signature_encoding: SignatureEncoding,
+    /// The keys of the `signatures`, so what each signature claims about its key is signed
+    /// too and can't be changed or dropped unnoticed.
+    signers: Vec<SignerKey>,
+    signed_at: DateTime<Utc>,
+}
+
+/// One of the keys a `MultiSignedSnapshot` is signed with.
+#[derive(Serialize)]
+struct SignerKey {
+    key_id: String,
+    algorithm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    curve: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_format: Option<EcdsaSignatureFormat>,
+}
+
+#[derive(Serialize)]
+struct KeySignature {
+    key_id: String,
+    algorithm: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    curve: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_format: Option<EcdsaSignatureFormat>,
     signature: String,
 }
 
+/// A signed snapshot as the exact bytes that were signed, for `--no-signature-reserialize`:
+/// `signed` is the compact JSON of a `SignedContent` (or, signed with several keys, a
+/// `MultiSignedContent`), kept as a string so it reaches the verifier unchanged.
+#[derive(Serialize)]
+struct VerbatimSignedSnapshot {
+    // Kept in the clear only to name outputs such as S3 objects, never serialized
+    #[serde(skip)]
+    metadata: Metadata,
+    signed: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signatures: Option<Vec<KeySignature>>,
+}
+
+/// The part of a `SignedSnapshot` covered by its signature. The signed bytes are the
+/// compact JSON of this struct, i.e. the envelope with its `signature` field removed.
+#[derive(Serialize)]
+struct SignedContent {
+    snapshot: HostSnapshot,
+    signature_encoding: SignatureEncoding,
+    algorithm: String,
+    /// Of an elliptic curve key, the curve, and how the signature's r and s are written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    curve: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_format: Option<EcdsaSignatureFormat>,
+    key_id: String,
+    signed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct EncryptedSnapshot {
+    // Kept in the clear only to name outputs such as S3 objects, never serialized
+    #[serde(skip)]
+    metadata: Metadata,
+    #[serde(flatten)]
+    envelope: EncryptedEnvelope,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct HostSnapshot {
     metadata: Metadata,
-    hardware: HardwareInfo,
-    operating_system: OperatingSystemInfo,
-    network: NetworkInfo,
-    storage: StorageInfo,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    // Sections are only ever None when left out by --changed-sections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hardware: Option<HardwareInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operating_system: Option<OperatingSystemInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    network: Option<NetworkInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    storage: Option<StorageInfo>,
+    #[serde(skip_serializing_if = "skip_empty")]
     services: Vec<String>, // Placeholder
-    users: Vec<UserInfo>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    software_components: Vec<SoftwareComponent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    users: Option<Vec<UserInfo>>,
+    /// Only collected with `--repositories`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repositories: Option<Vec<repositories::RepoInfo>>,
+    /// Only collected with `--scheduled-tasks`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled_tasks: Option<Vec<scheduled_tasks::ScheduledTask>>,
+    /// Only collected with `--top-processes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    processes: Option<Vec<processes::ProcessInfo>>,
+    /// The `--env-vars` that are set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    environment_variables: Option<BTreeMap<String, String>>,
+    /// Only collected with `--trusted-cas`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trusted_cas: Option<Vec<trust_store::CertSummary>>,
+    /// Only collected with `--boot-entries`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    boot_entries: Option<Vec<boot_entries::BootEntry>>,
+    /// Only collected with `--profiles`, on macOS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profiles: Option<Vec<profiles::Profile>>,
+    #[serde(skip_serializing_if = "skip_empty")]
+    software_components: Vec<ComponentWithSource>,
+    /// With `--delta`, replaces `software_components` with the changes since the receiver's baseline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    software_delta: Option<delta::ComponentDelta>,
+    /// Set when `--max-components` was reached and the remaining components were dropped.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    components_truncated: bool,
+    /// With `--changed-sections`, the sections left out because the receiver already has them.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    omitted_sections: Vec<String>,
+    /// With `--route`, the sections sent to their own url instead.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    routed_sections: Vec<String>,
+    /// Sections that couldn't be collected, which are left empty, or only in part, such
+    /// as `software_components` when a plugin failed.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    collection_errors: Vec<CollectionError>,
+    /// Plugin command probes that `--probe-allowlist`, `--probe-denylist` or `--no-commands`
+    /// kept from running.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    skipped_probes: Vec<SkippedProbe>,
+    /// Sections that may be missing data because hsnap ran without root (an administrator
+    /// on Windows), e.g. `processes`, whose other users' executables it can't read.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    privilege_limited: Vec<String>,
+    /// Set when `--deadline` expired before every section was collected.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+    /// With `--digest`, the hash of the rest of the snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<snapshot_digest::SnapshotDigest>,
+}
+
+/// Sections collected so far, shared with the collection thread so a deadline can keep them.
+#[derive(Default)]
+struct PartialSnapshot {
+    hardware: Option<HardwareInfo>,
+    operating_system: Option<OperatingSystemInfo>,
+    network: Option<NetworkInfo>,
+    storage: Option<StorageInfo>,
+    users: Option<Vec<UserInfo>>,
+    repositories: Option<Vec<repositories::RepoInfo>>,
+    scheduled_tasks: Option<Vec<scheduled_tasks::ScheduledTask>>,
+    processes: Option<Vec<processes::ProcessInfo>>,
+    environment_variables: Option<BTreeMap<String, String>>,
+    trusted_cas: Option<Vec<trust_store::CertSummary>>,
+    boot_entries: Option<Vec<boot_entries::BootEntry>>,
+    profiles: Option<Vec<profiles::Profile>>,
+    software_components: Option<Vec<ComponentWithSource>>,
+    components_truncated: bool,
+    collection_errors: Vec<CollectionError>,
+    skipped_probes: Vec<SkippedProbe>,
+    /// A section whose collection the tests make panic.
+    #[cfg(test)]
+    fail_section: Option<&'static str>,
+}
+
+impl PartialSnapshot {
+    /// The snapshot of the sections collected. A section the deadline or a panic cut short
+    /// is left out, rather than filled with defaults a receiver would take for real readings.
+    fn into_snapshot(
+        self,
+        metadata: Metadata,
+        privilege_limited: Vec<String>,
+        truncated: bool,
+    ) -> HostSnapshot {
+        HostSnapshot {
+            metadata,
+            hardware: self.hardware,
+            operating_system: self.operating_system,
+            network: self.network,
+            storage: self.storage,
+            services: vec![], // Placeholder
+            users: self.users,
+            repositories: self.repositories,
+            scheduled_tasks: self.scheduled_tasks,
+            processes: self.processes,
+            environment_variables: self.environment_variables,
+            trusted_cas: self.trusted_cas,
+            boot_entries: self.boot_entries,
+            profiles: self.profiles,
+            software_components: self.software_components.unwrap_or_default(),
+            software_delta: None,
+            omitted_sections: Vec::new(),
+            routed_sections: Vec::new(),
+            components_truncated: self.components_truncated,
+            collection_errors: self.collection_errors,
+            skipped_probes: self.skipped_probes,
+            privilege_limited,
+            truncated,
+            digest: None,
+        }
+    }
+}
+
+/// A section that failed to be collected, and why.
+#[derive(Serialize, Deserialize, Clone)]
+struct CollectionError {
+    section: String,
+    error: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -49,13 +776,65 @@ struct Metadata {
     // The user will provide this id, to map hsnap to a host. If not provided, the hsnap will use the hostname
     id: String,
     timestamp: DateTime<Utc>,
+    /// The host's clock when `timestamp` was set by `--timestamp` or `SOURCE_DATE_EPOCH`,
+    /// so the two can be compared. Left out with `--deterministic`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock_time: Option<DateTime<Utc>>,
+    /// A stable identifier of the OS installation: systemd's machine ID on Linux, the
+    /// hardware UUID on macOS and the MachineGuid on Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    machine_id: Option<String>,
+    /// Whether this is a real host, a VM, a container or WSL.
+    environment: environment::HostEnvironment,
+    /// How long collecting the snapshot took, in milliseconds.
+    #[serde(default)]
+    capture_duration_ms: u64,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct HardwareInfo {
     cpu_info: Vec<CpuInfo>,
+    /// e.g. `x86_64` or `aarch64`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cpu_architecture: Option<String>,
+    /// Unknown on some platforms, e.g. in some virtual machines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    physical_core_count: Option<usize>,
+    /// Hardware threads, one per entry of `cpu_info`.
+    #[serde(default)]
+    logical_core_count: usize,
+    /// Feature flags, e.g. `aes` or `avx2`, as Linux names them.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    cpu_features: Vec<String>,
+    /// Empty where the kernel doesn't report NUMA nodes, i.e. on platforms other than Linux.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    numa_nodes: Vec<numa::NumaNode>,
     memory: MemoryInfo,
     components: Vec<ComponentInfo>,
+    /// Model identifier, e.g. `MacBookPro18,3`. Only collected on macOS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    /// Only collected on macOS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    serial_number: Option<String>,
+    /// The version is only collected on macOS, Secure Boot and TPM state on Linux and Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    firmware: Option<FirmwareInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct FirmwareInfo {
+    /// The BIOS, UEFI or (on macOS) boot ROM version.
+    version: Option<String>,
+    /// The macOS OS loader (iBoot) version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    os_loader_version: Option<String>,
+    /// Whether Secure Boot is enforced. Collected on Linux and Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secure_boot: Option<bool>,
+    /// Collected on Linux, and on Windows when run as an administrator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tpm_present: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -65,14 +844,35 @@ struct CpuInfo {
     brand: String,
     frequency: u64,
     usage: f32,
+    /// Collected on Linux, and from CPUID on other x86 hosts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    family: Option<u32>,
+    /// Collected on Linux, and from CPUID on other x86 hosts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<u32>,
+    /// Collected on Linux, and from CPUID on other x86 hosts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stepping: Option<u32>,
+    /// The microcode revision, e.g. `0x5003604`. Only collected on Linux.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    microcode: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct MemoryInfo {
     total_memory: u64,
     used_memory: u64,
+    /// How much could be allocated without swapping, including reclaimable caches.
+    #[serde(default)]
+    available_memory: u64,
+    /// How much is entirely unused, leaving out caches.
+    #[serde(default)]
+    free_memory: u64,
     total_swap: u64,
     used_swap: u64,
+    /// Only collected on Linux.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    swap_devices: Vec<swaps::SwapDevice>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -81,29 +881,58 @@ struct ComponentInfo {
     temperature: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct OperatingSystemInfo {
     os_name: Option<String>,
     os_version: Option<String>,
     kernel_version: Option<String>,
     host_name: Option<String>,
+    /// `systemd`, `openrc`, `sysvinit`, `runit` or `s6` on Linux, `launchd` on macOS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    init_system: Option<String>,
+    /// Not collected with `--no-shell`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    init_system_version: Option<String>,
+    /// When apt, dnf or pacman last refreshed its package index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    package_index_updated: Option<DateTime<Utc>>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct NetworkInfo {
     interfaces: Vec<NetworkInterface>,
+    /// Gateways of the IPv4 and IPv6 default routes.
+    default_gateways: Vec<String>,
+    dns_servers: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct NetworkInterface {
     name: String,
     mac_address: String,
-    ips: Vec<String>,
+    ips: Vec<IpAddress>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct IpAddress {
+    addr: String,
+    /// `ipv4` or `ipv6`.
+    family: String,
+    /// `loopback`, `link-local`, `private` (RFC 1918 and IPv6 unique local) or `global`.
+    scope: String,
+    prefix_len: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct StorageInfo {
     disks: Vec<DiskInfo>,
+    /// The total space of the disks, counting each filesystem once however many times
+    /// it is mounted.
+    #[serde(default)]
+    total_physical_space: u64,
+    /// Only collected with `--overlay-layers`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    overlay_mounts: Option<Vec<overlay::OverlayMount>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -114,7 +943,24 @@ struct DiskInfo {
     mount_point: String,
     total_space: u64,
     available_space: u64,
+    /// The share of the disk that isn't available, as a percentage.
+    #[serde(default)]
+    used_percent: f64,
+    /// Whether `used_percent` is at or over `--low-space-threshold`.
+    #[serde(default)]
+    low_space: bool,
     is_removable: bool,
+    /// Mount flags such as `ro`/`rw` and `noexec`, from `/proc/mounts` on Linux.
+    mount_options: Vec<String>,
+    total_inodes: Option<u64>,
+    available_inodes: Option<u64>,
+    /// For a filesystem already listed under another mount point, e.g. the same device
+    /// mounted twice or a bind mount, that mount point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<String>,
+    /// With `--dedupe-mounts`, the mount points of the duplicates folded into this disk.
+    #[serde(default, skip_serializing_if = "skip_empty")]
+    also_mounted_at: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -122,175 +968,2654 @@ struct UserInfo {
     name: String,
     id: String,
     groups: Vec<String>,
+    /// The rest are read from `/etc/passwd`, so are only set on Linux and macOS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    home_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shell: Option<String>,
+    /// Whether the uid is below 1000, or `nobody`'s. Only set on Linux.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    is_system: Option<bool>,
+}
+
+/// Checks the arguments `install-systemd` forwards parse as hsnap's own, so a typo fails
+/// when installing rather than at the timer's first run.
+fn check_forwarded_args(forwarded: &[String]) -> Result<(), String> {
+    Args::try_parse_from(std::iter::once("hsnap").chain(forwarded.iter().map(String::as_str)))
+        .map(|_| ())
+        .map_err(|e| format!("Invalid arguments after --: {}", e.to_string().trim_end()))
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
 
-    // Normal Capture Mode (with optional signing)
-    let snapshot: HostSnapshot = capture_snapshot(&args).await;
-
-    let signed_snapshot = match &args.signing_key {
-        Some(private_key_pem) => {
-            let private_key = RsaPrivateKey::from_pkcs1_pem(&private_key_pem)
-                .expect("Failed to parse private key");
-            let schema = Pkcs1v15Sign::new_unprefixed();
-            let snapshot_bytes =
-                serde_json::to_vec(&snapshot).expect("Failed to serialize snapshot");
-            let signature = private_key
-                .sign(schema, snapshot_bytes.as_slice())
-                .expect("Unable to sign snapshot with private key");
-
-            let string_signature = hex::encode(&signature);
-
-            Some(SignedSnapshot {
-                snapshot: snapshot.clone(),
-                signature: string_signature,
-            })
+    if let Some(command) = &args.command {
+        let result = match command {
+            Commands::InstallSystemd {
+                interval,
+                unit_dir,
+                force,
+                enable,
+                args,
+            } => check_forwarded_args(args)
+                .and_then(|()| systemd::install(unit_dir, interval, args, *force, *enable)),
+            Commands::UninstallSystemd { unit_dir, disable } => {
+                systemd::uninstall(unit_dir, *disable)
+            }
+            Commands::Verify {
+                public_key,
+                file,
+                url,
+            } => {
+                let source = match (file, url) {
+                    (Some(path), _) => verify::Source::File(path.clone()),
+                    (None, url) => verify::Source::Url(url.clone().unwrap_or_default()),
+                };
+                match http_client(&args) {
+                    Ok(client) => verify::run(&source, public_key, &client).await,
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        None => None,
-    };
+        return;
+    }
 
-    match (&args.url, &signed_snapshot) {
-        (Some(url), Some(signed_snapshot)) => {
-            //Post the signed snapshot to the given url
-            let client = reqwest::Client::new();
-            post_data(client, url, signed_snapshot).await;
+    INCLUDE_EMPTY.store(args.include_empty, Ordering::Relaxed);
+
+    // rpm --root needs an absolute path
+    if let Some(root) = &args.root {
+        match std::fs::canonicalize(root) {
+            Ok(root) if root.is_dir() => args.root = Some(root),
+            _ => {
+                eprintln!("--root {} is not a directory", root.display());
+                std::process::exit(1);
+            }
         }
-        (Some(url), None) => {
-            //Post the original snapshot to the given url
-            let client = reqwest::Client::new();
-            post_data(client, url, snapshot).await;
+    }
+
+    // Fail on a bad key before spending time on a capture
+    if let Err(e) = encryption_key(&args).transpose() {
+        eprintln!("Failed to load encryption key: {}", e);
+        std::process::exit(1);
+    }
+    let keys = match signing_keys(&args) {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        (None, Some(signed_snapshot)) => {
-            //Pretty print the signed snapshot to stdout
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&signed_snapshot)
-                    .expect("Failed to serialize signed snapshot")
-            );
+    };
+    if let Err(e) = http_client(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    // Normal Capture Mode (with optional signing)
+    let mut snapshot: HostSnapshot = capture_snapshot(&args).await;
+
+    if let Some(status) = fail_fast_status(&args, &snapshot) {
+        eprintln!("Capture was incomplete, not outputting the snapshot (--fail-fast)");
+        std::process::exit(status);
+    }
+
+    let mut failed = !route_sections(&args, &mut snapshot).await;
+
+    let new_baseline = apply_baseline(&args, &mut snapshot);
+
+    let payload = match build_payload(&args, &keys, snapshot.clone()) {
+        Ok(payload) => payload,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
-        (None, None) => {
-            //Pretty print the original snapshot to stdout
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&snapshot).expect("Failed to seralize snapshot")
-            );
+    };
+
+    if let Some(path) = &args.output {
+        let sink = FileSink { path: path.clone() };
+        if let Err(e) = sink.deliver(&payload).await {
+            eprintln!("Failed to write snapshot to {}: {}", sink.target(), e);
+            failed = true;
+        }
+    }
+
+    if !args.url.is_empty() {
+        //Post the snapshot to each of the given urls
+        failed |= !post_to_all(&args, &keys, &snapshot, &payload).await;
+    } else if args.summary {
+        print!("{}", summary::render(&snapshot));
+    } else if args.output.is_none() {
+        //Pretty print the snapshot to stdout
+        StdoutSink
+            .deliver(&payload)
+            .await
+            .expect("Failed to serialize snapshot");
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    // Only once the changes have been delivered, so none are lost
+    if let Some((path, components)) = new_baseline {
+        if let Err(e) = baseline::save(path, &components) {
+            eprintln!("Failed to write baseline file {}: {}", path.display(), e);
+            std::process::exit(1);
         }
     }
 }
 
-async fn post_data<T: Serialize + Sized>(client: Client, url: &String, json: T) {
-    match client.post(url).json(&json).send().await {
-        Ok(res) => {
-            if res.status().is_success() {
-                println!("Successfully sent snapshot to {}", url);
-            } else {
-                eprintln!(
-                    "Failed to send snapshot to {}: Status {}",
-                    url,
-                    res.status()
-                );
+/// Whether nothing was left out of the snapshot. A partial one would show every section or
+/// component it's missing as changed or removed.
+fn is_complete(snapshot: &HostSnapshot) -> bool {
+    !snapshot.truncated && !snapshot.components_truncated && snapshot.collection_errors.is_empty()
+}
+
+/// With `--baseline-file`, cuts the snapshot down to the changes since the baseline,
+/// returning the components to save as the next baseline once they are delivered. An
+/// incomplete capture is output in full and leaves the baseline as it was.
+fn apply_baseline<'a>(
+    args: &'a Args,
+    snapshot: &mut HostSnapshot,
+) -> Option<(&'a PathBuf, Vec<ComponentWithSource>)> {
+    let path = args.baseline_file.as_ref()?;
+    if !is_complete(snapshot) {
+        eprintln!(
+            "Capture was incomplete, not updating baseline file {}",
+            path.display()
+        );
+        return None;
+    }
+    let components = snapshot.software_components.clone();
+    if let Some(baseline) = baseline::load(path) {
+        *snapshot = baseline::changes_since(snapshot, &baseline);
+    }
+    Some((path, components))
+}
+
+/// With `--fail-fast`, the status to exit with instead of outputting an incomplete snapshot.
+fn fail_fast_status(args: &Args, snapshot: &HostSnapshot) -> Option<i32> {
+    let incomplete = snapshot.truncated || !snapshot.collection_errors.is_empty();
+    (args.fail_fast && incomplete).then_some(1)
+}
+
+/// Takes each `--route`d section out of the snapshot and sends it to its url, returning
+/// false if any of them couldn't be sent.
+async fn route_sections(args: &Args, snapshot: &mut HostSnapshot) -> bool {
+    let client = http_client(args).expect("Failed to build HTTP client");
+    let mut sent = true;
+    let mut routed: Vec<(&str, Option<Vec<u8>>)> = Vec::new();
+    for (section, url) in &args.route {
+        // A section may be routed to several urls, but can only be taken out once
+        let index = match routed.iter().position(|(name, _)| name == section) {
+            Some(index) => index,
+            None => {
+                routed.push((section, routing::take_section(snapshot, section)));
+                routed.len() - 1
+            }
+        };
+        let Some(body) = &routed[index].1 else {
+            eprintln!("No {} were collected to send to {}", section, url);
+            continue;
+        };
+        match routing::post(&client, url, body.clone()).await {
+            // Kept off stdout when the snapshot itself is printed there
+            Ok(()) if args.url.is_empty() && args.output.is_none() => {}
+            Ok(()) => println!("Successfully sent {} to {}", section, url),
+            Err(e) => {
+                eprintln!("Failed to send {} to {}: {}", section, url, e);
+                sent = false;
             }
         }
-        Err(e) => {
-            eprintln!("Error sending snapshot to {}: {}", url, e)
+    }
+    sent
+}
+
+/// Signs the snapshot with `keys` if there are any, then encrypts it if an encryption key
+/// was given.
+fn build_payload(
+    args: &Args,
+    keys: &[Box<dyn Signer>],
+    mut snapshot: HostSnapshot,
+) -> Result<Payload, String> {
+    if args.components_only_json {
+        return Ok(Payload::Components(components_only::from_snapshot(
+            snapshot,
+            args.component_style,
+        )));
+    }
+    // Taken here, of the snapshot as sent, so a trimmed snapshot has its own
+    if let Some(algorithm) = args.digest {
+        snapshot.digest = None;
+        snapshot.digest = Some(snapshot_digest::compute(algorithm, &snapshot));
+    }
+    let metadata = snapshot.metadata.clone();
+    let payload = match keys {
+        [] => Payload::Snapshot(snapshot),
+        [key] if args.no_signature_reserialize => {
+            let signed = sign_snapshot(args, key.as_ref(), snapshot)?;
+            Payload::Verbatim(VerbatimSignedSnapshot {
+                metadata: metadata.clone(),
+                signed: serde_json::to_string(&signed.signed)
+                    .expect("Failed to serialize snapshot"),
+                signature: Some(signed.signature),
+                signatures: None,
+            })
+        }
+        [key] => Payload::Signed(sign_snapshot(args, key.as_ref(), snapshot)?),
+        keys if args.no_signature_reserialize => {
+            let signed = multi_sign_snapshot(args, keys, snapshot)?;
+            Payload::Verbatim(VerbatimSignedSnapshot {
+                metadata: metadata.clone(),
+                signed: serde_json::to_string(&signed.signed)
+                    .expect("Failed to serialize snapshot"),
+                signature: None,
+                signatures: Some(signed.signatures),
+            })
+        }
+        keys => Payload::MultiSigned(multi_sign_snapshot(args, keys, snapshot)?),
+    };
+
+    Ok(match encryption_key(args) {
+        Some(key) => {
+            let key = key.expect("Failed to load encryption key");
+            let plaintext = serde_json::to_vec(&payload).expect("Failed to serialize snapshot");
+            Payload::Encrypted(EncryptedSnapshot {
+                metadata,
+                envelope: key.encrypt(&plaintext),
+            })
         }
+        None => payload,
+    })
+}
+
+/// The keys from `--signing-key` and each `--signing-key-file`.
+fn signing_keys(args: &Args) -> Result<Vec<Box<dyn Signer>>, String> {
+    let from_pem = |pem: &str| -> Result<Box<dyn Signer>, String> {
+        Ok(match args.signing_algorithm {
+            SigningAlgorithm::Rsa => Box::new(SigningKey::from_pem(pem)?),
+            SigningAlgorithm::EcdsaP256 => {
+                Box::new(EcdsaSigningKey::from_pem(pem, args.ecdsa_signature_format)?)
+            }
+        })
+    };
+    let mut keys: Vec<Box<dyn Signer>> = Vec::new();
+    if let Some(pem) = &args.signing_key {
+        keys.push(from_pem(pem)?);
     }
+    for path in &args.signing_key_file {
+        let pem =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        keys.push(from_pem(&pem).map_err(|e| format!("{}: {}", path.display(), e))?);
+    }
+    Ok(keys)
 }
 
-async fn capture_snapshot(args: &Args) -> HostSnapshot {
-    // Initialize sysinfo structures
-    let mut sys = System::new_all();
-    sys.refresh_all();
+/// The key from `--encrypt-key` or `--encrypt-key-file`, if either was given.
+fn encryption_key(args: &Args) -> Option<Result<EncryptionKey, String>> {
+    if let Some(hex_key) = &args.encrypt_key {
+        return Some(EncryptionKey::from_hex(hex_key));
+    }
+    let path = args.encrypt_key_file.as_ref()?;
+    Some(
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+            .and_then(|hex_key| EncryptionKey::from_hex(&hex_key)),
+    )
+}
 
-    let disks = Disks::new_with_refreshed_list();
-    let networks = Networks::new_with_refreshed_list();
-    let components = Components::new_with_refreshed_list();
-    let users = Users::new_with_refreshed_list();
+fn sign_snapshot(
+    args: &Args,
+    key: &dyn Signer,
+    snapshot: HostSnapshot,
+) -> Result<SignedSnapshot, String> {
+    let signed = SignedContent {
+        snapshot,
+        signature_encoding: args.signature_encoding,
+        algorithm: key.algorithm().to_string(),
+        curve: key.curve(),
+        signature_format: key.signature_format(),
+        key_id: key.key_id(),
+        signed_at: capture_time(args),
+    };
+    let signed_bytes = serde_json::to_vec(&signed).expect("Failed to serialize snapshot");
+    let signature = key.sign(&signed_bytes)?;
 
-    // Determine Host ID: Argument > Hostname > "unknown"
-    let host_id = args
-        .id
-        .clone()
-        .or_else(|| System::host_name())
-        .unwrap_or_else(|| "unknown".to_string());
+    Ok(SignedSnapshot {
+        signature: args.signature_encoding.encode(&signature),
+        signed,
+    })
+}
 
-    HostSnapshot {
-        metadata: Metadata {
-            id: host_id,
-            timestamp: Utc::now(),
-        },
-        hardware: HardwareInfo {
-            cpu_info: sys
-                .cpus()
-                .iter()
-                .map(|cpu| CpuInfo {
-                    name: cpu.name().to_string(),
-                    vendor_id: cpu.vendor_id().to_string(),
-                    brand: cpu.brand().to_string(),
-                    frequency: cpu.frequency(),
-                    usage: cpu.cpu_usage(),
-                })
-                .collect(),
-            memory: MemoryInfo {
-                total_memory: sys.total_memory(),
-                used_memory: sys.used_memory(),
-                total_swap: sys.total_swap(),
-                used_swap: sys.used_swap(),
-            },
-            components: components
-                .iter()
-                .map(|c| ComponentInfo {
-                    label: c.label().to_string(),
-                    temperature: c.temperature(),
-                })
-                .collect(),
-        },
-        operating_system: OperatingSystemInfo {
-            os_name: System::name(),
-            os_version: System::os_version(),
-            kernel_version: System::kernel_version(),
-            host_name: System::host_name(),
-        },
-        network: NetworkInfo {
-            interfaces: networks
-                .iter()
-                .map(|(interface_name, network)| NetworkInterface {
-                    name: interface_name.clone(),
-                    mac_address: network.mac_address().to_string(),
-                    ips: network
-                        .ip_networks()
-                        .iter()
-                        .map(|ip| ip.addr.to_string())
-                        .collect(),
-                })
-                .collect(),
-        },
-        storage: StorageInfo {
-            disks: disks
-                .iter()
-                .map(|disk| DiskInfo {
-                    name: disk.name().to_string_lossy().to_string(),
-                    kind: format!("{:?}", disk.kind()),
-                    file_system: disk.file_system().to_string_lossy().to_string(),
-                    mount_point: disk.mount_point().to_string_lossy().to_string(),
-                    total_space: disk.total_space(),
-                    available_space: disk.available_space(),
-                    is_removable: disk.is_removable(),
-                })
-                .collect(),
-        },
-        services: vec![], // Placeholder
-        users: users
+/// Signs the snapshot with each key. They all sign the same bytes, listing every key, so
+/// each signature can be checked on its own.
+fn multi_sign_snapshot(
+    args: &Args,
+    keys: &[Box<dyn Signer>],
+    snapshot: HostSnapshot,
+) -> Result<MultiSignedSnapshot, String> {
+    let signed = MultiSignedContent {
+        snapshot,
+        signature_encoding: args.signature_encoding,
+        signers: keys
             .iter()
-            .map(|user| UserInfo {
-                name: user.name().to_string(),
-                id: user.id().to_string(),
-                groups: user.groups().iter().map(|g| g.name().to_string()).collect(),
+            .map(|key| SignerKey {
+                key_id: key.key_id(),
+                algorithm: key.algorithm().to_string(),
+                curve: key.curve(),
+                signature_format: key.signature_format(),
             })
             .collect(),
-        software_components: hsnap_purl_plugin::run_plugins(),
+        signed_at: capture_time(args),
+    };
+    let signed_bytes = serde_json::to_vec(&signed).expect("Failed to serialize snapshot");
+    let signatures = keys
+        .iter()
+        .map(|key| {
+            Ok(KeySignature {
+                key_id: key.key_id(),
+                algorithm: key.algorithm().to_string(),
+                curve: key.curve(),
+                signature_format: key.signature_format(),
+                signature: args.signature_encoding.encode(&key.sign(&signed_bytes)?),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(MultiSignedSnapshot { signed, signatures })
+}
+
+/// Delivers the snapshot to every url, returning false only if none of them accepted it.
+///
+/// With `--state-file`, a url last sent the same snapshot, going by
+/// `sections::content_digest`, is skipped.
+/// With `--delta` or `--changed-sections`, a url the state file has a record of is sent
+/// a trimmed down copy of `snapshot` instead of `payload`, unless it has snapshots
+/// spooled. Only the whole `payload` is spooled.
+async fn post_to_all(
+    args: &Args,
+    keys: &[Box<dyn Signer>],
+    snapshot: &HostSnapshot,
+    payload: &Payload,
+) -> bool {
+    let client = http_client(args).expect("Failed to build HTTP client");
+    let sinks: Vec<Box<dyn SnapshotSink>> = args
+        .url
+        .iter()
+        .map(|url| -> Box<dyn SnapshotSink> {
+            match s3::S3Location::parse(url) {
+                Some(location) => Box::new(S3Sink {
+                    client: client.clone(),
+                    url: url.clone(),
+                    location,
+                }),
+                None => Box::new(HttpSink {
+                    client: client.clone(),
+                    url: url.clone(),
+                }),
+            }
+        })
+        .collect();
+    deliver_to_all(args, keys, &sinks, snapshot, payload).await
+}
+
+/// Delivers the snapshot to each sink as `post_to_all` describes, returning false only if
+/// none of them accepted it.
+async fn deliver_to_all(
+    args: &Args,
+    keys: &[Box<dyn Signer>],
+    sinks: &[Box<dyn SnapshotSink>],
+    snapshot: &HostSnapshot,
+    payload: &Payload,
+) -> bool {
+    // A partial snapshot would show every missing section or component as changed
+    let complete = is_complete(snapshot);
+
+    let mut state = args.state_file.as_deref().map(State::load);
+
+    let max_backoff = Duration::from_secs(args.breaker_max_backoff);
+    // Not a digest of the body: that changes with the timestamp even when nothing else does
+    let digest = sections::content_digest(snapshot);
+    let mut delivered = 0;
+    for sink in sinks {
+        let target = sink.target();
+        let spool = args
+            .spool_dir
+            .as_deref()
+            .map(|dir| Spool::new(dir, &target));
+
+        // A trimmed snapshot is relative to the last one the url accepted, which anything
+        // spooled would change, so while the spool isn't empty the whole snapshot goes
+        let spooled = spool.as_ref().is_some_and(|spool| !spool.is_empty());
+        let trimmed_payload = state
+            .as_ref()
+            .filter(|_| complete && !spooled)
+            .and_then(|state| trim_snapshot(args, snapshot, state, &target))
+            .and_then(|trimmed| match build_payload(args, keys, trimmed) {
+                Ok(payload) => Some(payload),
+                Err(e) => {
+                    eprintln!("{}, sending {} the whole snapshot", e, target);
+                    None
+                }
+            });
+        let full_payload = payload;
+        let payload = trimmed_payload.as_ref().unwrap_or(payload);
+
+        // Rather than keep hammering a url that is down, hold on to the snapshot until
+        // the backoff is over
+        if let Some(retry_after) = state.as_ref().and_then(|state| state.retry_after(&target)) {
+            eprintln!(
+                "Not sending to {} after repeated failures until {}",
+                target,
+                retry_after.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            );
+            spool_snapshot(spool.as_ref(), full_payload, &target);
+            continue;
+        }
+
+        // Anything spooled goes first, so the url receives snapshots in order
+        if let Some(spool) = &spool {
+            match spool.flush(sink.as_ref()).await {
+                Ok(0) => {}
+                Ok(count) => println!("Sent {} spooled snapshot(s) to {}", count, target),
+                Err(e) => {
+                    eprintln!("Failed to send spooled snapshot to {}: {}", target, e);
+                    if let Some(state) = &mut state {
+                        state.record_failure(&target, args.breaker_threshold, max_backoff);
+                    }
+                    spool_snapshot(Some(spool), full_payload, &target);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(state) = &state {
+            if state.sent.get(&target) == Some(&digest) {
+                println!("Snapshot unchanged since last sent to {}, skipping", target);
+                delivered += 1;
+                continue;
+            }
+        }
+
+        match sink.deliver(payload).await {
+            Ok(()) => {
+                println!("Successfully sent snapshot to {}", target);
+                delivered += 1;
+                if let Some(state) = &mut state {
+                    if args.delta && complete {
+                        state
+                            .baselines
+                            .insert(target.clone(), snapshot.software_components.clone());
+                    }
+                    if args.changed_sections && complete {
+                        state
+                            .sections
+                            .insert(target.clone(), sections::digests(snapshot));
+                    }
+                    state.record_success(&target);
+                    state.sent.insert(target, digest.clone());
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to send snapshot to {}: {}", target, e);
+                if let Some(state) = &mut state {
+                    state.record_failure(&target, args.breaker_threshold, max_backoff);
+                }
+                spool_snapshot(spool.as_ref(), full_payload, &target);
+            }
+        }
+    }
+
+    if let (Some(path), Some(state)) = (&args.state_file, &state) {
+        if let Err(e) = state.save(path) {
+            eprintln!("Failed to write state file {}: {}", path.display(), e);
+        }
+    }
+
+    if delivered == 0 {
+        eprintln!(
+            "Failed to send snapshot to any of the {} url(s)",
+            sinks.len()
+        );
+    }
+    delivered > 0
+}
+
+/// The client for posting and fetching snapshots, trusting only the `--tls-roots-file`
+/// certificates if one was given.
+fn http_client(args: &Args) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().user_agent(user_agent(args));
+    if let Some(path) = &args.tls_roots_file {
+        let pem = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let roots = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if roots.is_empty() {
+            return Err(format!("{}: no certificates found", path.display()));
+        }
+        builder = roots
+            .into_iter()
+            .fold(builder.tls_built_in_root_certs(false), |builder, root| {
+                builder.add_root_certificate(root)
+            });
+    }
+    if args.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// The `--user-agent`, or `hsnap/<version>` by default.
+fn user_agent(args: &Args) -> String {
+    let user_agent = args
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("hsnap/{}", env!("CARGO_PKG_VERSION")));
+    if args.user_agent_host_id {
+        format!("{} ({})", user_agent, host_id(args))
+    } else {
+        user_agent
+    }
+}
+
+/// Keeps a payload that couldn't be delivered in `--spool-dir`, if one was given.
+fn spool_snapshot(spool: Option<&Spool>, payload: &Payload, target: &str) {
+    if let Some(spool) = spool {
+        match spool.push(payload) {
+            Ok(()) => println!("Spooled snapshot for {}", target),
+            Err(e) => eprintln!("Failed to spool snapshot for {}: {}", target, e),
+        }
+    }
+}
+
+/// Trims the snapshot down to what `target` has not already accepted, according to
+/// `--changed-sections` and `--delta`. Returns `None` if the full snapshot should be sent.
+fn trim_snapshot(
+    args: &Args,
+    snapshot: &HostSnapshot,
+    state: &State,
+    target: &str,
+) -> Option<HostSnapshot> {
+    let mut trimmed = snapshot.clone();
+    let mut was_trimmed = false;
+
+    if let Some(previous) = state.sections.get(target).filter(|_| args.changed_sections) {
+        sections::omit_unchanged(&mut trimmed, previous);
+        was_trimmed = true;
+    }
+
+    let software_omitted = trimmed
+        .omitted_sections
+        .iter()
+        .any(|name| name == sections::SOFTWARE_COMPONENTS);
+    if let Some(baseline) = state.baselines.get(target).filter(|_| args.delta) {
+        if !software_omitted {
+            let components = std::mem::take(&mut trimmed.software_components);
+            trimmed.software_delta = Some(delta::diff(baseline, &components));
+            was_trimmed = true;
+        }
+    }
+
+    was_trimmed.then_some(trimmed)
+}
+
+/// Determine Host ID: Argument > Machine ID, with --id-source machine-id > FQDN, with
+/// --fqdn-id > Hostname > "unknown"
+fn host_id(args: &Args) -> String {
+    args.id
+        .clone()
+        .or_else(|| {
+            (args.id_source == machine_id::IdSource::MachineId)
+                .then(machine_id::read)
+                .flatten()
+        })
+        .or_else(|| {
+            let host_name = System::host_name()?;
+            if args.fqdn_id {
+                hostname::fqdn(&host_name).or(Some(host_name))
+            } else {
+                Some(host_name)
+            }
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The time the snapshot was taken: `--timestamp` or `SOURCE_DATE_EPOCH` if set, or else
+/// the host's clock, or with `--deterministic`, the Unix epoch.
+fn capture_time(args: &Args) -> DateTime<Utc> {
+    match timestamp_override(args) {
+        Some(timestamp) => timestamp,
+        None if args.deterministic => DateTime::default(),
+        None => Utc::now(),
+    }
+}
+
+fn timestamp_override(args: &Args) -> Option<DateTime<Utc>> {
+    args.timestamp.or_else(|| {
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| parse_source_date_epoch(&epoch))
+    })
+}
+
+fn parse_source_date_epoch(epoch: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(epoch.trim().parse().ok()?, 0)
+}
+
+/// The privileged sections that were collected, or asked for, without the privileges to
+/// see all of them.
+fn privilege_limited(privileged: bool, args: &Args, partial: &PartialSnapshot) -> Vec<String> {
+    if privileged {
+        return Vec::new();
+    }
+    privileges::privileged_sections()
+        .into_iter()
+        .filter(|section| match *section {
+            sections::PROCESSES => partial.processes.is_some(),
+            sections::SCHEDULED_TASKS => partial.scheduled_tasks.is_some(),
+            sections::BOOT_ENTRIES => partial.boot_entries.is_some(),
+            // Listing the profiles fails outright without root
+            sections::PROFILES => args.profiles,
+            sections::SOFTWARE_COMPONENTS => partial.software_components.is_some(),
+            _ => true,
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+async fn capture_snapshot(args: &Args) -> HostSnapshot {
+    let started = Instant::now();
+    let privileged = privileges::is_privileged();
+    let mut metadata = Metadata {
+        id: host_id(args),
+        timestamp: capture_time(args),
+        clock_time: timestamp_override(args)
+            .filter(|_| !args.deterministic)
+            .map(|_| Utc::now()),
+        machine_id: machine_id::read(),
+        environment: environment::detect(),
+        capture_duration_ms: 0,
+    };
+
+    // Sections are collected on a plain thread rather than spawn_blocking, so a
+    // collection that overruns the deadline doesn't hold up the runtime's shutdown.
+    let partial = Arc::new(Mutex::new(PartialSnapshot::default()));
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    {
+        let partial = partial.clone();
+        let args = args.clone();
+        std::thread::spawn(move || {
+            collect_sections(&args, &partial);
+            let _ = done_tx.send(());
+        });
+    }
+
+    let collected = match args.deadline {
+        Some(seconds) => tokio::time::timeout(Duration::from_secs(seconds), done_rx)
+            .await
+            .ok(),
+        None => Some(done_rx.await),
+    };
+    let truncated = match collected {
+        Some(result) => {
+            result.expect("Snapshot collection failed");
+            false
+        }
+        None => {
+            eprintln!(
+                "Capture exceeded the {}s deadline, returning a partial snapshot",
+                args.deadline.unwrap_or_default()
+            );
+            true
+        }
+    };
+
+    let partial = std::mem::take(&mut *partial.lock().expect("Snapshot collection failed"));
+    let privilege_limited = privilege_limited(privileged, args, &partial);
+    if !privilege_limited.is_empty() {
+        eprintln!(
+            "Not running as root or an administrator, so {} may be incomplete",
+            privilege_limited.join(", ")
+        );
+    }
+    metadata.capture_duration_ms = started.elapsed().as_millis() as u64;
+    let mut snapshot = partial.into_snapshot(metadata, privilege_limited, truncated);
+    if args.deterministic {
+        sections::make_deterministic(&mut snapshot);
+    }
+    snapshot
+}
+
+/// Collects each section in turn, storing it as soon as it is complete. A section whose
+/// collection panics is left out and the panic recorded in `collection_errors`, so one
+/// failing section doesn't cost the rest of the snapshot.
+fn collect_sections(args: &Args, partial: &Mutex<PartialSnapshot>) {
+    let lock = || partial.lock().expect("Snapshot collection failed");
+
+    // CPU usage is measured from here to the hardware section, so the sections in
+    // between mostly cover the interval it needs
+    let cpu_usage_since = Instant::now();
+    let mut sys = hardware_system();
+
+    if let Some(operating_system) = isolate(partial, sections::OPERATING_SYSTEM, || {
+        collect_operating_system(args.no_shell)
+    }) {
+        lock().operating_system = Some(operating_system);
+    }
+
+    let users = isolate(partial, sections::USERS, collect_users);
+    let user_names: Vec<String> = users
+        .iter()
+        .flatten()
+        .map(|user| user.name.clone())
+        .collect();
+    if let Some(users) = users {
+        lock().users = Some(users);
+    }
+
+    if let Some(network) = isolate(partial, sections::NETWORK, collect_network) {
+        lock().network = Some(network);
+    }
+
+    if let Some(storage) = isolate(partial, sections::STORAGE, || {
+        collect_storage(
+            args.overlay_layers,
+            args.low_space_threshold,
+            args.dedupe_mounts,
+        )
+    }) {
+        lock().storage = Some(storage);
+    }
+
+    if let Some(hardware) = isolate(partial, sections::HARDWARE, || {
+        collect_hardware(&mut sys, cpu_usage_since)
+    }) {
+        lock().hardware = Some(hardware);
+    }
+
+    if args.repositories {
+        if let Some(repositories) = isolate(partial, sections::REPOSITORIES, repositories::collect)
+        {
+            lock().repositories = Some(repositories);
+        }
+    }
+
+    if args.scheduled_tasks {
+        if let Some(scheduled_tasks) = isolate(partial, sections::SCHEDULED_TASKS, || {
+            scheduled_tasks::collect(&user_names, args.no_shell)
+        }) {
+            lock().scheduled_tasks = Some(scheduled_tasks);
+        }
+    }
+
+    if let Some(top) = args.top_processes {
+        if let Some(processes) = isolate(partial, sections::PROCESSES, || {
+            processes::collect(top, args.top_by)
+        }) {
+            lock().processes = Some(processes);
+        }
+    }
+
+    if !args.env_vars.is_empty() {
+        lock().environment_variables = Some(collect_environment_variables(
+            &args.env_vars,
+            std::env::vars_os(),
+        ));
+    }
+
+    if args.trusted_cas {
+        if let Some(trusted_cas) = isolate(partial, sections::TRUSTED_CAS, trust_store::collect) {
+            lock().trusted_cas = Some(trusted_cas);
+        }
+    }
+
+    if args.boot_entries {
+        if let Some(boot_entries) = isolate(partial, sections::BOOT_ENTRIES, boot_entries::collect)
+        {
+            lock().boot_entries = Some(boot_entries);
+        }
+    }
+
+    if args.profiles {
+        match isolate(partial, sections::PROFILES, profiles::collect) {
+            Some(Ok(profiles)) => lock().profiles = Some(profiles),
+            Some(Err(error)) => {
+                eprintln!("Failed to collect {}: {}", sections::PROFILES, error);
+                lock().collection_errors.push(CollectionError {
+                    section: sections::PROFILES.to_string(),
+                    error,
+                });
+            }
+            None => {}
+        }
+    }
+
+    if let Some(inventory) = isolate(partial, sections::SOFTWARE_COMPONENTS, || {
+        collect_software(args)
+    }) {
+        let mut partial = lock();
+        // The components are kept, but marking the section as incomplete stops a delta or
+        // baseline from showing those the failed plugins missed as removed
+        partial
+            .collection_errors
+            .extend(inventory.errors.iter().map(|error| CollectionError {
+                section: sections::SOFTWARE_COMPONENTS.to_string(),
+                error: error.to_string(),
+            }));
+        partial.software_components = Some(inventory.components);
+        partial.components_truncated = inventory.truncated;
+        partial.skipped_probes = inventory.skipped;
+    }
+}
+
+/// Runs a section's collection, catching a panic and recording it against the section.
+fn isolate<T>(
+    partial: &Mutex<PartialSnapshot>,
+    section: &str,
+    collect: impl FnOnce() -> T,
+) -> Option<T> {
+    #[cfg(test)]
+    let collect = || {
+        let fail_section = partial
+            .lock()
+            .expect("Snapshot collection failed")
+            .fail_section;
+        if fail_section == Some(section) {
+            panic!("injected failure");
+        }
+        collect()
+    };
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(collect)) {
+        Ok(value) => Some(value),
+        Err(panic) => {
+            let error = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panicked".to_string());
+            eprintln!("Failed to collect {}: {}", section, error);
+            partial
+                .lock()
+                .expect("Snapshot collection failed")
+                .collection_errors
+                .push(CollectionError {
+                    section: section.to_string(),
+                    error,
+                });
+            None
+        }
+    }
+}
+
+/// Runs the plugins, or reuses the cached inventory, and adds any `--extra-components`.
+fn collect_software(args: &Args) -> hsnap_purl_plugin::Inventory {
+    let options = PluginOptions {
+        composer_roots: args.composer_root.clone(),
+        venv_roots: args.venv_root.clone(),
+        fonts: args.fonts,
+        root: args.root.clone(),
+        no_shell: args.no_shell,
+        max_components: args.max_components,
+        component_kinds: args.component_kinds.clone(),
+        package_lock_timeout: Duration::from_secs(args.package_lock_timeout),
+        max_command_output: Some(args.max_command_output),
+        plugin_dirs: args.plugin_dir.clone(),
+        fail_fast: args.fail_fast,
+        command_policy: CommandPolicy {
+            allow: if args.no_commands {
+                Some(Vec::new())
+            } else {
+                args.probe_allowlist.clone()
+            },
+            deny: args.probe_denylist.clone(),
+        },
+        extra_qualifiers: args.purl_qualifier.iter().fold(
+            HashMap::new(),
+            |mut qualifiers, (plugin, key, value)| {
+                qualifiers
+                    .entry(plugin.clone())
+                    .or_insert_with(Vec::new)
+                    .push((key.clone(), value.clone()));
+                qualifiers
+            },
+        ),
+    };
+    let cache = args.cache_dir.as_deref().map(|dir| {
+        InventoryCache::new(
+            dir,
+            Duration::from_secs(args.cache_ttl),
+            &host_id(args),
+            &options,
+        )
+    });
+    let mut inventory = match cache.as_ref().and_then(InventoryCache::load) {
+        Some(cached) => cached,
+        None => {
+            let inventory = hsnap_purl_plugin::run_plugins(&options);
+            for error in inventory.errors.iter().chain(&inventory.warnings) {
+                eprintln!("{}", error);
+            }
+            // An incomplete inventory isn't reused, so the next run tries again
+            if let Some(cache) = cache.as_ref().filter(|_| inventory.errors.is_empty()) {
+                if let Err(e) = cache.save(&inventory) {
+                    eprintln!("Failed to write inventory cache: {}", e);
+                }
+            }
+            inventory
+        }
+    };
+    if let Some(path) = &args.extra_components {
+        match extra_components::load(path) {
+            Ok(extra) => extra_components::merge(&mut inventory.components, extra),
+            Err(e) => eprintln!(
+                "Failed to read extra components from {}: {}",
+                path.display(),
+                e
+            ),
+        }
+        if let Some(max) = args.max_components {
+            if inventory.components.len() > max {
+                inventory.components.truncate(max);
+                inventory.truncated = true;
+            }
+        }
+    }
+    if args.enrich {
+        let mut db = enrich::EnrichDb::embedded();
+        if let Some(path) = &args.enrich_db {
+            if let Err(e) = db.load(path) {
+                eprintln!("Failed to read enrich db {}: {}", path.display(), e);
+            }
+        }
+        db.enrich(&mut inventory.components);
+    }
+    if inventory.truncated {
+        eprintln!(
+            "More than {} software components found, the rest were dropped",
+            args.max_components.unwrap_or_default()
+        );
+    }
+    inventory
+}
+
+/// A `System` with only CPUs and memory: refreshing everything would also list every
+/// process, which takes seconds on busy hosts.
+fn hardware_system() -> System {
+    System::new_with_specifics(
+        RefreshKind::nothing()
+            .with_cpu(CpuRefreshKind::everything())
+            .with_memory(MemoryRefreshKind::everything()),
+    )
+}
+
+/// Reads the hardware, with the CPU usage since `sys` was created at `since`.
+fn collect_hardware(sys: &mut System, since: Instant) -> HardwareInfo {
+    // CPU usage is measured since `sys` was created, which needs at least
+    // MINIMUM_CPU_UPDATE_INTERVAL between the two refreshes
+    std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL.saturating_sub(since.elapsed()));
+    sys.refresh_cpu_usage();
+    sys.refresh_memory();
+
+    let components = Components::new_with_refreshed_list();
+    let mac = system_profiler::hardware().unwrap_or_default();
+    let signatures = cpu_features::signatures(sys.cpus().len());
+
+    HardwareInfo {
+        cpu_info: sys
+            .cpus()
+            .iter()
+            .zip(signatures)
+            .map(|(cpu, signature)| CpuInfo {
+                name: cpu.name().to_string(),
+                vendor_id: cpu.vendor_id().to_string(),
+                brand: cpu.brand().to_string(),
+                frequency: cpu.frequency(),
+                usage: cpu.cpu_usage(),
+                family: signature.family,
+                model: signature.model,
+                stepping: signature.stepping,
+                microcode: signature.microcode,
+            })
+            .collect(),
+        cpu_architecture: Some(System::cpu_arch()).filter(|arch| !arch.is_empty()),
+        physical_core_count: System::physical_core_count(),
+        logical_core_count: sys.cpus().len(),
+        cpu_features: cpu_features::collect(),
+        numa_nodes: numa::collect(),
+        memory: MemoryInfo {
+            total_memory: sys.total_memory(),
+            used_memory: sys.used_memory(),
+            available_memory: sys.available_memory(),
+            free_memory: sys.free_memory(),
+            total_swap: sys.total_swap(),
+            used_swap: sys.used_swap(),
+            swap_devices: swaps::collect(),
+        },
+        components: components
+            .iter()
+            .map(|c| ComponentInfo {
+                label: c.label().to_string(),
+                temperature: c.temperature(),
+            })
+            .collect(),
+        model: mac.machine_model,
+        serial_number: mac.serial_number,
+        firmware: Some(FirmwareInfo {
+            version: mac.boot_rom_version,
+            os_loader_version: mac.os_loader_version,
+            secure_boot: firmware::secure_boot(),
+            tpm_present: firmware::tpm_present(),
+        })
+        .filter(|firmware| {
+            firmware.version.is_some()
+                || firmware.secure_boot.is_some()
+                || firmware.tpm_present.is_some()
+        }),
+    }
+}
+
+fn collect_operating_system(no_shell: bool) -> OperatingSystemInfo {
+    let (init_system, init_system_version) = init_system::detect(no_shell);
+    OperatingSystemInfo {
+        os_name: System::name(),
+        os_version: System::os_version(),
+        kernel_version: System::kernel_version(),
+        host_name: System::host_name(),
+        init_system,
+        init_system_version,
+        package_index_updated: package_index::last_updated(),
+    }
+}
+
+fn collect_network() -> NetworkInfo {
+    let networks = Networks::new_with_refreshed_list();
+
+    NetworkInfo {
+        interfaces: networks
+            .iter()
+            .map(|(interface_name, network)| NetworkInterface {
+                name: interface_name.clone(),
+                mac_address: network.mac_address().to_string(),
+                ips: network
+                    .ip_networks()
+                    .iter()
+                    .map(|ip| ip_address(ip.addr, ip.prefix))
+                    .collect(),
+            })
+            .collect(),
+        default_gateways: routes::default_gateways(),
+        dns_servers: routes::dns_servers(),
+    }
+}
+
+fn ip_address(addr: IpAddr, prefix_len: u8) -> IpAddress {
+    IpAddress {
+        addr: addr.to_string(),
+        family: match addr {
+            IpAddr::V4(_) => "ipv4".to_string(),
+            IpAddr::V6(_) => "ipv6".to_string(),
+        },
+        scope: ip_scope(&addr).to_string(),
+        prefix_len,
+    }
+}
+
+fn ip_scope(addr: &IpAddr) -> &'static str {
+    match addr {
+        IpAddr::V4(v4) if v4.is_loopback() => "loopback",
+        IpAddr::V4(v4) if v4.is_link_local() => "link-local",
+        IpAddr::V4(v4) if v4.is_private() => "private",
+        IpAddr::V6(v6) if v6.is_loopback() => "loopback",
+        // fe80::/10
+        IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfe80 => "link-local",
+        // fc00::/7
+        IpAddr::V6(v6) if v6.segments()[0] & 0xfe00 == 0xfc00 => "private",
+        _ => "global",
+    }
+}
+
+fn collect_storage(
+    overlay_layers: bool,
+    low_space_threshold: f64,
+    dedupe_mounts: bool,
+) -> StorageInfo {
+    let disks = Disks::new_with_refreshed_list();
+    let mounts = mounts::read_mounts();
+
+    let disks = disks
+        .iter()
+        .map(|disk| {
+            let (total_inodes, available_inodes) = mounts::inode_usage(disk.mount_point());
+            let (used_percent, low_space) = space_used(
+                disk.total_space(),
+                disk.available_space(),
+                low_space_threshold,
+            );
+            let info = DiskInfo {
+                name: disk.name().to_string_lossy().to_string(),
+                kind: format!("{:?}", disk.kind()),
+                file_system: disk.file_system().to_string_lossy().to_string(),
+                mount_point: disk.mount_point().to_string_lossy().to_string(),
+                total_space: disk.total_space(),
+                available_space: disk.available_space(),
+                used_percent,
+                low_space,
+                is_removable: disk.is_removable(),
+                mount_options: mounts::options_for(
+                    &mounts,
+                    &disk.mount_point().to_string_lossy(),
+                    &disk.file_system().to_string_lossy(),
+                )
+                .map(<[String]>::to_vec)
+                .unwrap_or_default(),
+                total_inodes,
+                available_inodes,
+                duplicate_of: None,
+                also_mounted_at: Vec::new(),
+            };
+            (info, mounts::device_id(disk.mount_point()))
+        })
+        .collect();
+    let disks = mark_duplicate_mounts(disks, dedupe_mounts);
+    StorageInfo {
+        total_physical_space: total_physical_space(&disks),
+        disks,
+        overlay_mounts: overlay_layers.then(overlay::collect),
+    }
+}
+
+/// Marks each disk whose filesystem (by device number) was already listed with
+/// `duplicate_of`, the first listing's mount point. With `dedupe`, the duplicates are
+/// dropped instead, their mount points added to the first's `also_mounted_at`.
+fn mark_duplicate_mounts(disks: Vec<(DiskInfo, Option<u64>)>, dedupe: bool) -> Vec<DiskInfo> {
+    let mut first_of_device: HashMap<u64, usize> = HashMap::new();
+    let mut marked: Vec<DiskInfo> = Vec::new();
+    for (mut disk, device) in disks {
+        let Some(device) = device else {
+            marked.push(disk);
+            continue;
+        };
+        match first_of_device.get(&device) {
+            Some(&first) if dedupe => marked[first].also_mounted_at.push(disk.mount_point),
+            Some(&first) => {
+                disk.duplicate_of = Some(marked[first].mount_point.clone());
+                marked.push(disk);
+            }
+            None => {
+                first_of_device.insert(device, marked.len());
+                marked.push(disk);
+            }
+        }
+    }
+    marked
+}
+
+/// The size of the disks, counting a filesystem mounted more than once only once.
+fn total_physical_space(disks: &[DiskInfo]) -> u64 {
+    disks
+        .iter()
+        .filter(|disk| disk.duplicate_of.is_none())
+        .map(|disk| disk.total_space)
+        .sum()
+}
+
+/// The percentage of `total` that isn't `available`, to two decimal places, and whether
+/// it is at or over `low_space_threshold`. 0 for disks with no size, such as pseudo
+/// filesystems.
+fn space_used(total: u64, available: u64, low_space_threshold: f64) -> (f64, bool) {
+    if total == 0 {
+        return (0.0, false);
+    }
+    let used = total.saturating_sub(available) as f64 / total as f64 * 100.0;
+    let used = (used * 100.0).round() / 100.0;
+    (used, used >= low_space_threshold)
+}
+
+/// The `names` variables of `environment`, hsnap's own outside the tests, that are set,
+/// and no others.
+fn collect_environment_variables<K, V>(
+    names: &[String],
+    environment: impl IntoIterator<Item = (K, V)>,
+) -> BTreeMap<String, String>
+where
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    environment
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.as_ref().to_str()?;
+            names.iter().any(|wanted| wanted == name).then(|| {
+                (
+                    name.to_string(),
+                    value.as_ref().to_string_lossy().to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn collect_users() -> Vec<UserInfo> {
+    let users = Users::new_with_refreshed_list();
+    let passwd: HashMap<String, PasswdEntry> = hsnap_purl_plugin::users::passwd_entries(None)
+        .into_iter()
+        .map(|entry| (entry.name.clone(), entry))
+        .collect();
+
+    users
+        .iter()
+        .map(|user| {
+            let entry = passwd.get(user.name());
+            UserInfo {
+                name: user.name().to_string(),
+                id: user.id().to_string(),
+                groups: user.groups().iter().map(|g| g.name().to_string()).collect(),
+                uid: entry.map(|entry| entry.uid),
+                gid: entry.map(|entry| entry.gid),
+                home_dir: entry.map(|entry| entry.home.clone()),
+                shell: entry.map(|entry| entry.shell.clone()),
+                is_system: entry
+                    .filter(|_| cfg!(target_os = "linux"))
+                    .map(PasswdEntry::is_system),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing::VerifyingKey;
+    use crate::test_support::{
+        args, empty_snapshot, to_msgpack, MockServer, TempDir, ECDSA_SIGNING_KEY_PEM,
+        ECDSA_VERIFYING_KEY_PEM, ROTATED_SIGNING_KEY_PEM, ROTATED_VERIFYING_KEY_PEM,
+        SIGNING_KEY_PEM, TLS_CA_PEM, TLS_OTHER_CA_PEM, VERIFYING_KEY_PEM,
+    };
+    use hsnap_purl_plugin::SoftwareComponent;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn collects_only_requested_environment_variables() {
+        let environment = [
+            ("HSNAP_TEST_JAVA_HOME", "/usr/lib/jvm/java-17"),
+            ("HSNAP_TEST_HTTPS_PROXY", "http://proxy.example.com:3128"),
+            ("HSNAP_TEST_API_TOKEN", "secret"),
+        ];
+
+        let args = args(&[
+            "--env-vars",
+            "HSNAP_TEST_JAVA_HOME,HSNAP_TEST_HTTPS_PROXY,HSNAP_TEST_UNSET",
+        ]);
+        assert_eq!(
+            collect_environment_variables(&args.env_vars, environment),
+            BTreeMap::from([
+                (
+                    "HSNAP_TEST_HTTPS_PROXY".to_string(),
+                    "http://proxy.example.com:3128".to_string()
+                ),
+                (
+                    "HSNAP_TEST_JAVA_HOME".to_string(),
+                    "/usr/lib/jvm/java-17".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn classifies_interface_addresses() {
+        let classified = |addr: &str, prefix_len| {
+            serde_json::to_value(ip_address(addr.parse().unwrap(), prefix_len)).unwrap()
+        };
+
+        assert_eq!(
+            classified("fe80::1c2b:3aff:fe4d:5e6f", 64),
+            serde_json::json!({
+                "addr": "fe80::1c2b:3aff:fe4d:5e6f",
+                "family": "ipv6",
+                "scope": "link-local",
+                "prefix_len": 64
+            })
+        );
+        assert_eq!(
+            classified("169.254.10.20", 16),
+            serde_json::json!({
+                "addr": "169.254.10.20",
+                "family": "ipv4",
+                "scope": "link-local",
+                "prefix_len": 16
+            })
+        );
+        for (addr, prefix_len, scope) in [
+            ("127.0.0.1", 8, "loopback"),
+            ("::1", 128, "loopback"),
+            ("192.168.1.10", 24, "private"),
+            ("fd12:3456:789a::1", 48, "private"),
+            ("203.0.113.7", 24, "global"),
+            ("2001:db8::7", 64, "global"),
+            // Just outside fe80::/10
+            ("fec0::1", 10, "global"),
+        ] {
+            let classified = classified(addr, prefix_len);
+            assert_eq!(classified["scope"], scope, "{}", addr);
+            assert_eq!(classified["prefix_len"], prefix_len, "{}", addr);
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_to_every_url() {
+        let primary = MockServer::start().await;
+        let archive = MockServer::start().await;
+        let args = args(&["--url", &primary.url, "--url", &archive.url]);
+        let snapshot = empty_snapshot();
+
+        assert!(post_to_all(&args, &[], &snapshot, &Payload::Snapshot(snapshot.clone())).await);
+
+        for server in [&primary, &archive] {
+            let requests = server.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].method, "POST");
+            assert_eq!(requests[0].path, "/");
+            assert_eq!(requests[0].header("content-type"), Some("application/json"));
+            let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+            assert_eq!(body["metadata"]["id"], "test-host");
+        }
+    }
+
+    #[tokio::test]
+    async fn identifies_itself_in_user_agent() {
+        let server = MockServer::start().await;
+        let snapshot = empty_snapshot();
+        let payload = Payload::Snapshot(snapshot.clone());
+        let version = env!("CARGO_PKG_VERSION");
+
+        for (flags, expected) in [
+            (vec![], format!("hsnap/{}", version)),
+            (
+                vec!["--id", "web-01", "--user-agent-host-id"],
+                format!("hsnap/{} (web-01)", version),
+            ),
+            (
+                vec!["--user-agent", "fleet-agent/2.1"],
+                "fleet-agent/2.1".to_string(),
+            ),
+        ] {
+            let args = args(&[&["--url", server.url.as_str()], flags.as_slice()].concat());
+            assert!(post_to_all(&args, &[], &snapshot, &payload).await);
+            let requests = server.requests();
+            assert_eq!(
+                requests.last().unwrap().header("user-agent"),
+                Some(expected.as_str())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn trusts_only_the_given_tls_roots() {
+        let server = MockServer::start_tls().await;
+        let dir = TempDir::new();
+        let ca = dir.path().join("ca.pem");
+        std::fs::write(&ca, TLS_CA_PEM).unwrap();
+        let other_ca = dir.path().join("other-ca.pem");
+        std::fs::write(&other_ca, TLS_OTHER_CA_PEM).unwrap();
+        let snapshot = empty_snapshot();
+        let payload = Payload::Snapshot(snapshot.clone());
+        let ca = ca.to_str().unwrap();
+        let other_ca = other_ca.to_str().unwrap();
+
+        for (flags, delivered) in [
+            (vec!["--tls-roots-file", ca], true),
+            (vec!["--tls-roots-file", other_ca], false),
+            // Only the built-in roots
+            (vec![], false),
+            (vec!["--danger-accept-invalid-certs"], true),
+        ] {
+            let args = args(&[&["--url", server.url.as_str()], flags.as_slice()].concat());
+            assert_eq!(
+                post_to_all(&args, &[], &snapshot, &payload).await,
+                delivered,
+                "{:?}",
+                flags
+            );
+        }
+        assert_eq!(server.requests().len(), 2);
+
+        let empty = dir.path().join("empty.pem");
+        std::fs::write(&empty, "").unwrap();
+        let args = args(&["--tls-roots-file", empty.to_str().unwrap()]);
+        assert!(http_client(&args).is_err());
+    }
+
+    #[tokio::test]
+    async fn fails_only_when_every_url_fails() {
+        let down = MockServer::respond_with(503, Vec::new(), Vec::new()).await;
+        let up = MockServer::start().await;
+        let snapshot = empty_snapshot();
+        let payload = Payload::Snapshot(snapshot.clone());
+
+        let both = args(&["--url", &down.url, "--url", &up.url]);
+        assert!(post_to_all(&both, &[], &snapshot, &payload).await);
+        let only_down = args(&["--url", &down.url]);
+        assert!(!post_to_all(&only_down, &[], &snapshot, &payload).await);
+        assert_eq!(down.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn skips_post_of_unchanged_snapshot() {
+        let server = MockServer::start().await;
+        let dir = TempDir::new();
+        let state_file = dir.path().join("state.json");
+        let args = args(&[
+            "--url",
+            &server.url,
+            "--state-file",
+            state_file.to_str().unwrap(),
+        ]);
+
+        let first = empty_snapshot();
+        assert!(post_to_all(&args, &[], &first, &Payload::Snapshot(first.clone())).await);
+        let request = &server.requests()[0];
+        assert_eq!(
+            request.header("x-content-sha256"),
+            Some(hex::encode(Sha256::digest(&request.body)).as_str())
+        );
+        // The next run, on the unchanged host
+        let mut second = empty_snapshot();
+        second.metadata.timestamp += chrono::TimeDelta::hours(1);
+        second.metadata.capture_duration_ms = 1750;
+        assert!(post_to_all(&args, &[], &second, &Payload::Snapshot(second.clone())).await);
+        assert_eq!(server.requests().len(), 1);
+
+        let mut changed = second.clone();
+        changed.services.push("sshd".to_string());
+        assert!(post_to_all(&args, &[], &changed, &Payload::Snapshot(changed.clone())).await);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    fn component(purl: &str) -> ComponentWithSource {
+        ComponentWithSource {
+            component: SoftwareComponent::Purl(purl.parse().unwrap()),
+            source: "debian".to_string(),
+            license: None,
+            homepage: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_delta_against_previous_run() {
+        let server = MockServer::start().await;
+        let dir = TempDir::new();
+        let state_file = dir.path().join("state.json");
+        let args = args(&[
+            "--url",
+            &server.url,
+            "--state-file",
+            state_file.to_str().unwrap(),
+            "--delta",
+        ]);
+
+        let mut first = empty_snapshot();
+        first.software_components = vec![
+            component("pkg:deb/debian/bash@5.2.15-2"),
+            component("pkg:deb/debian/curl@7.88.1-10"),
+        ];
+        assert!(post_to_all(&args, &[], &first, &Payload::Snapshot(first.clone())).await);
+        // No baseline yet, so the full inventory goes
+        let body: serde_json::Value = serde_json::from_slice(&server.requests()[0].body).unwrap();
+        assert_eq!(body["software_components"].as_array().unwrap().len(), 2);
+        assert!(body.get("software_delta").is_none());
+
+        // curl removed and jq installed since
+        let mut second = empty_snapshot();
+        second.software_components = vec![
+            component("pkg:deb/debian/bash@5.2.15-2"),
+            component("pkg:deb/debian/jq@1.6-2.1"),
+        ];
+        assert!(post_to_all(&args, &[], &second, &Payload::Snapshot(second.clone())).await);
+        let body: serde_json::Value = serde_json::from_slice(&server.requests()[1].body).unwrap();
+        assert!(body.get("software_components").is_none());
+        let delta = &body["software_delta"];
+        assert_eq!(
+            delta["baseline"],
+            delta::component_set_digest(&first.software_components)
+        );
+        assert_eq!(
+            delta["added"],
+            serde_json::to_value([component("pkg:deb/debian/jq@1.6-2.1")]).unwrap()
+        );
+        assert_eq!(
+            delta["removed"],
+            serde_json::to_value([component("pkg:deb/debian/curl@7.88.1-10")]).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn omits_sections_unchanged_since_previous_run() {
+        let server = MockServer::start().await;
+        let dir = TempDir::new();
+        let state_file = dir.path().join("state.json");
+        let args = args(&[
+            "--url",
+            &server.url,
+            "--state-file",
+            state_file.to_str().unwrap(),
+            "--changed-sections",
+        ]);
+        let snapshot = |used_memory: u64, packages: &[&str]| {
+            let mut snapshot = empty_snapshot();
+            snapshot.hardware = Some(
+                serde_json::from_value(serde_json::json!({
+                    "cpu_info": [],
+                    "logical_core_count": 4,
+                    "memory": {
+                        "total_memory": 8_000_000_000u64,
+                        "used_memory": used_memory,
+                        "total_swap": 0,
+                        "used_swap": 0
+                    },
+                    "components": []
+                }))
+                .unwrap(),
+            );
+            snapshot.software_components = packages.iter().map(|purl| component(purl)).collect();
+            snapshot
+        };
+
+        let first = snapshot(2_000_000_000, &["pkg:deb/debian/bash@5.2.15-2"]);
+        assert!(post_to_all(&args, &[], &first, &Payload::Snapshot(first.clone())).await);
+        let body: serde_json::Value = serde_json::from_slice(&server.requests()[0].body).unwrap();
+        assert_eq!(body["hardware"]["logical_core_count"], 4);
+        assert!(body.get("omitted_sections").is_none());
+
+        // Memory in use is a reading, not a change to the hardware
+        let second = snapshot(
+            3_000_000_000,
+            &["pkg:deb/debian/bash@5.2.15-2", "pkg:deb/debian/jq@1.6-2.1"],
+        );
+        assert!(post_to_all(&args, &[], &second, &Payload::Snapshot(second.clone())).await);
+        let body: serde_json::Value = serde_json::from_slice(&server.requests()[1].body).unwrap();
+        assert!(body.get("hardware").is_none());
+        assert_eq!(body["omitted_sections"], serde_json::json!(["hardware"]));
+        assert_eq!(body["software_components"].as_array().unwrap().len(), 2);
+    }
+
+    /// A plugin still running at the deadline loses the components, not the sections
+    /// collected before it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn deadline_returns_partial_snapshot() {
+        let dir = TempDir::new();
+        std::fs::write(
+            dir.path().join("slow.json"),
+            r#"{"name": "slow", "probes": [{"command": "sleep 10"}], "component": {"name": "slow"}}"#,
+        )
+        .unwrap();
+        let args = args(&[
+            "--deadline",
+            "2",
+            "--plugin-dir",
+            dir.path().to_str().unwrap(),
+            "--component-kinds",
+            "none",
+        ]);
+
+        let started = Instant::now();
+        let snapshot = capture_snapshot(&args).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(snapshot.truncated);
+        assert!(!snapshot.hardware.unwrap().cpu_info.is_empty());
+        assert!(snapshot.operating_system.unwrap().kernel_version.is_some());
+        assert!(snapshot.software_components.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lists_privileged_sections_collected_unprivileged() {
+        let partial = PartialSnapshot {
+            processes: Some(Vec::new()),
+            software_components: Some(Vec::new()),
+            ..PartialSnapshot::default()
+        };
+        let plain = args(&[]);
+
+        let limited = privilege_limited(false, &plain, &partial);
+        assert!(limited.contains(&sections::PROCESSES.to_string()));
+        assert!(limited.contains(&sections::SOFTWARE_COMPONENTS.to_string()));
+        // Not collected, so not limited either
+        assert!(!limited.contains(&sections::SCHEDULED_TASKS.to_string()));
+        assert!(!limited.contains(&sections::BOOT_ENTRIES.to_string()));
+        assert!(privilege_limited(true, &plain, &partial).is_empty());
+
+        // And end to end, when the tests aren't run as root
+        if !privileges::is_privileged() {
+            let top = args(&["--top-processes", "1", "--component-kinds", "none"]);
+            let snapshot = capture_snapshot(&top).await;
+            assert!(snapshot
+                .privilege_limited
+                .contains(&sections::PROCESSES.to_string()));
+            let json = serde_json::to_value(&snapshot).unwrap();
+            assert!(json["privilege_limited"]
+                .as_array()
+                .unwrap()
+                .contains(&sections::PROCESSES.into()));
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_timestamp_override_verbatim() {
+        let before = Utc::now();
+        let overridden = args(&[
+            "--timestamp",
+            "2024-01-15T09:30:00Z",
+            "--component-kinds",
+            "none",
+        ]);
+        let snapshot = capture_snapshot(&overridden).await;
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["metadata"]["timestamp"], "2024-01-15T09:30:00Z");
+        // The host's clock is kept alongside, to show the skew
+        let clock_time = snapshot.metadata.clock_time.unwrap();
+        assert!(clock_time >= before && clock_time <= Utc::now());
+
+        let offset = args(&["--timestamp", "2024-01-15T11:30:00+02:00"]);
+        assert_eq!(
+            capture_time(&offset).to_rfc3339(),
+            "2024-01-15T09:30:00+00:00"
+        );
+        assert!(parse_timestamp("2024-01-15 09:30").is_err());
+        assert_eq!(
+            parse_source_date_epoch("1705311000\n").map(|time| time.to_rfc3339()),
+            Some("2024-01-15T09:30:00+00:00".to_string())
+        );
+        assert_eq!(parse_source_date_epoch("yesterday"), None);
+    }
+
+    #[tokio::test]
+    async fn records_capture_duration() {
+        let args = args(&["--component-kinds", "none"]);
+
+        let started = Instant::now();
+        let snapshot = capture_snapshot(&args).await;
+        let elapsed = started.elapsed().as_millis() as u64;
+
+        // Measuring CPU usage alone takes a couple of hundred milliseconds
+        let duration = snapshot.metadata.capture_duration_ms;
+        assert!(
+            duration > 0 && duration <= elapsed,
+            "{} of {}",
+            duration,
+            elapsed
+        );
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["metadata"]["capture_duration_ms"], duration);
+    }
+
+    #[tokio::test]
+    async fn deterministic_runs_are_byte_identical() {
+        let args = args(&["--deterministic", "--component-kinds", "none"]);
+
+        let first = serde_json::to_vec(&capture_snapshot(&args).await).unwrap();
+        // Long enough for the clock and CPU usage to move on
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let second = serde_json::to_vec(&capture_snapshot(&args).await).unwrap();
+
+        assert!(first == second, "the two runs differ");
+        let snapshot: serde_json::Value = serde_json::from_slice(&first).unwrap();
+        assert_eq!(snapshot["metadata"]["capture_duration_ms"], 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reuses_cached_inventory_within_ttl() {
+        let plugins = TempDir::new();
+        let work = TempDir::new();
+        let runs = work.path().join("runs");
+        let manifest = serde_json::json!({
+            "name": "counted",
+            "probes": [{"command": format!("echo run >> '{}'; echo 1.0", runs.display())}],
+            "component": {"name": "counted"}
+        });
+        std::fs::write(plugins.path().join("counted.json"), manifest.to_string()).unwrap();
+        let cache_dir = work.path().join("cache");
+        let run = |ttl: &str| {
+            let args = args(&[
+                "--plugin-dir",
+                plugins.path().to_str().unwrap(),
+                "--component-kinds",
+                "none",
+                "--cache-dir",
+                cache_dir.to_str().unwrap(),
+                "--cache-ttl",
+                ttl,
+            ]);
+            let inventory = collect_software(&args);
+            assert!(inventory.components.iter().any(|found| {
+                found.source == "counted"
+                    && delta::component_key(&found.component) == "pkg:generic/counted@1.0"
+            }));
+            std::fs::read_to_string(&runs).unwrap().lines().count()
+        };
+
+        assert_eq!(run("300"), 1);
+        assert_eq!(run("300"), 1);
+        // Once the TTL is over the probes run again
+        assert_eq!(run("0"), 2);
+    }
+
+    // The Debian plugin only runs on Linux
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_packages_of_mounted_root() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(root.path().join("var/lib/dpkg")).unwrap();
+        std::fs::write(
+            root.path().join("var/lib/dpkg/status"),
+            "\
+Package: libc6
+Status: install ok installed
+Architecture: amd64
+Version: 2.36-9+deb12u4
+
+Package: vim
+Status: deinstall ok config-files
+Architecture: amd64
+Version: 2:9.0.1378-2
+
+Package: bash
+Status: install ok installed
+Architecture: amd64
+Version: 5.2.15-2+b2
+",
+        )
+        .unwrap();
+        let args = args(&["--root", root.path().to_str().unwrap()]);
+
+        let inventory = collect_software(&args);
+        assert!(inventory.errors.is_empty());
+        let found: Vec<String> = inventory
+            .components
+            .iter()
+            .map(|found| delta::component_key(&found.component))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "pkg:deb/libc6@2.36-9%2Bdeb12u4?arch=amd64",
+                "pkg:deb/bash@5.2.15-2%2Bb2?arch=amd64",
+            ]
+        );
+    }
+
+    #[test]
+    fn signs_and_verifies_with_each_encoding() {
+        let key = SigningKey::from_pem(SIGNING_KEY_PEM).unwrap();
+        let verifying_key = VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap();
+
+        for (encoding, name) in [
+            (SignatureEncoding::Hex, "hex"),
+            (SignatureEncoding::Base64, "base64"),
+        ] {
+            let args = args(&["--signature-encoding", name]);
+            let signed = sign_snapshot(&args, &key, empty_snapshot()).unwrap();
+            let json = serde_json::to_string_pretty(&signed).unwrap();
+            let envelope: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(envelope["signature_encoding"], name);
+            assert!(encoding.decode(&signed.signature).is_ok());
+
+            verify::verify(&json, &verifying_key).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_compressed_snapshot_file() {
+        let dir = TempDir::new();
+        let public_key = dir.path().join("key.pub.pem");
+        std::fs::write(&public_key, VERIFYING_KEY_PEM).unwrap();
+        let client = http_client(&args(&[])).unwrap();
+        for (name, magic) in [
+            ("snapshot.json.gz", &[0x1f, 0x8b, 0x08][..]),
+            ("snapshot.json.zst", &[0x28, 0xb5, 0x2f, 0xfd][..]),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, magic).unwrap();
+            let source = verify::Source::File(path);
+
+            let err = verify::run(&source, &public_key, &client)
+                .await
+                .unwrap_err();
+            assert!(
+                err.contains("Compressed snapshots aren't supported"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn verifies_snapshot_fetched_from_url() {
+        let key = SigningKey::from_pem(SIGNING_KEY_PEM).unwrap();
+        let signed = sign_snapshot(&args(&[]), &key, empty_snapshot()).unwrap();
+        let server = MockServer::respond_with(
+            200,
+            vec![("content-type".to_string(), "application/json".to_string())],
+            serde_json::to_vec(&signed).unwrap(),
+        )
+        .await;
+        let dir = TempDir::new();
+        let public_key = dir.path().join("key.pub.pem");
+        std::fs::write(&public_key, VERIFYING_KEY_PEM).unwrap();
+        let other_key = dir.path().join("other.pub.pem");
+        std::fs::write(&other_key, ECDSA_VERIFYING_KEY_PEM).unwrap();
+        let client = http_client(&args(&[])).unwrap();
+        let source = verify::Source::Url(format!("{}/snapshots/test-host", server.url));
+
+        verify::run(&source, &public_key, &client).await.unwrap();
+        assert!(verify::run(&source, &other_key, &client).await.is_err());
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/snapshots/test-host");
+    }
+
+    #[tokio::test]
+    async fn verifies_msgpack_snapshot_only_with_its_signed_bytes() {
+        let signing_key = format!("--signing-key={}", SIGNING_KEY_PEM);
+        let dir = TempDir::new();
+        let public_key = dir.path().join("key.pub.pem");
+        std::fs::write(&public_key, VERIFYING_KEY_PEM).unwrap();
+        let client = http_client(&args(&[])).unwrap();
+        for (reserialize, verifies) in [(None, false), (Some("--no-signature-reserialize"), true)] {
+            let mut flags = vec![signing_key.as_str()];
+            flags.extend(reserialize);
+            let args = args(&flags);
+            let keys = signing_keys(&args).unwrap();
+            let envelope =
+                serde_json::to_value(build_payload(&args, &keys, empty_snapshot()).unwrap())
+                    .unwrap();
+            let server = MockServer::respond_with(
+                200,
+                vec![(
+                    "content-type".to_string(),
+                    "application/msgpack".to_string(),
+                )],
+                to_msgpack(&envelope),
+            )
+            .await;
+            let source = verify::Source::Url(format!("{}/snapshots/test-host", server.url));
+
+            let result = verify::run(&source, &public_key, &client).await;
+            match verifies {
+                true => result.unwrap(),
+                false => assert!(result.unwrap_err().contains("--no-signature-reserialize")),
+            }
+        }
+    }
+
+    #[test]
+    fn hardware_section_leaves_processes_unrefreshed() {
+        let mut sys = hardware_system();
+        let hardware = collect_hardware(&mut sys, Instant::now());
+        assert!(!hardware.cpu_info.is_empty());
+        assert!(hardware.memory.total_memory > 0);
+        assert!(sys.processes().is_empty());
+    }
+
+    #[test]
+    fn encrypted_payload_decrypts_to_signed_snapshot() {
+        use aes_gcm::aead::{Aead, KeyInit};
+        use aes_gcm::{Aes256Gcm, Nonce};
+        use base64::Engine;
+
+        let hex_key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let signing_key = format!("--signing-key={}", SIGNING_KEY_PEM);
+        let args = args(&[&signing_key, "--encrypt-key", hex_key]);
+        let mut snapshot = empty_snapshot();
+        snapshot.services.push("sshd".to_string());
+
+        let payload =
+            build_payload(&args, &signing_keys(&args).unwrap(), snapshot.clone()).unwrap();
+        let envelope = serde_json::to_value(&payload).unwrap();
+        assert_eq!(envelope["algorithm"], "A256GCM");
+        assert!(envelope.get("metadata").is_none());
+
+        // As the receiver would, with the shared key
+        let base64 = base64::engine::general_purpose::STANDARD;
+        let field = |name: &str| base64.decode(envelope[name].as_str().unwrap()).unwrap();
+        let mut sealed = field("ciphertext");
+        sealed.extend(field("tag"));
+        let cipher = Aes256Gcm::new_from_slice(&hex::decode(hex_key).unwrap()).unwrap();
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&field("nonce")), sealed.as_slice())
+            .unwrap();
+
+        // Signed before it was encrypted
+        let signed = String::from_utf8(plaintext).unwrap();
+        verify::verify(&signed, &VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap()).unwrap();
+        let signed: serde_json::Value = serde_json::from_str(&signed).unwrap();
+        assert_eq!(signed["snapshot"], serde_json::to_value(&snapshot).unwrap());
+    }
+
+    #[test]
+    fn includes_empty_lists_when_asked() {
+        let mut snapshot = empty_snapshot();
+        snapshot.hardware = Some(
+            serde_json::from_value(serde_json::json!({
+                "cpu_info": [],
+                "logical_core_count": 4,
+                "memory": {"total_memory": 0, "used_memory": 0, "total_swap": 0, "used_swap": 0},
+                "components": []
+            }))
+            .unwrap(),
+        );
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert!(json.get("services").is_none());
+        assert!(json["hardware"].get("cpu_features").is_none());
+
+        INCLUDE_EMPTY_IN_TEST.with(|include| include.set(true));
+        let json = serde_json::to_value(&snapshot).unwrap();
+        INCLUDE_EMPTY_IN_TEST.with(|include| include.set(false));
+        assert_eq!(json["services"], serde_json::json!([]));
+        assert_eq!(json["software_components"], serde_json::json!([]));
+        assert_eq!(json["hardware"]["cpu_features"], serde_json::json!([]));
+        assert_eq!(json["hardware"]["numa_nodes"], serde_json::json!([]));
+    }
+
+    /// A sink that keeps what it is given, as an integrator's own sink might.
+    struct RecordingSink {
+        delivered: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl SnapshotSink for RecordingSink {
+        fn target(&self) -> String {
+            "recording".to_string()
+        }
+
+        async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+            let json = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+            self.delivered.lock().unwrap().push(json);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_to_custom_sink() {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sinks: Vec<Box<dyn SnapshotSink>> = vec![Box::new(RecordingSink {
+            delivered: delivered.clone(),
+        })];
+        let mut snapshot = empty_snapshot();
+        snapshot.services.push("sshd".to_string());
+
+        let payload = Payload::Snapshot(snapshot.clone());
+        assert!(deliver_to_all(&args(&[]), &[], &sinks, &snapshot, &payload).await);
+
+        let delivered = delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0]["metadata"]["id"], "test-host");
+        assert_eq!(delivered[0]["services"], serde_json::json!(["sshd"]));
+    }
+
+    /// A sink whose endpoint can be taken down and brought back up.
+    struct FlakySink {
+        up: Arc<AtomicBool>,
+        attempts: Arc<Mutex<usize>>,
+        delivered: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl SnapshotSink for FlakySink {
+        fn target(&self) -> String {
+            "flaky".to_string()
+        }
+
+        async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+            *self.attempts.lock().unwrap() += 1;
+            if !self.up.load(Ordering::Relaxed) {
+                return Err("HTTP 503 Service Unavailable".to_string());
+            }
+            let json = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+            self.delivered.lock().unwrap().push(json);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_spooled_snapshots_once_endpoint_recovers() {
+        let up = Arc::new(AtomicBool::new(false));
+        let attempts = Arc::new(Mutex::new(0));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sinks: Vec<Box<dyn SnapshotSink>> = vec![Box::new(FlakySink {
+            up: up.clone(),
+            attempts: attempts.clone(),
+            delivered: delivered.clone(),
+        })];
+        let dir = TempDir::new();
+        let state_file = dir.path().join("state.json");
+        let spool_dir = dir.path().join("spool");
+        let args = args(&[
+            "--state-file",
+            state_file.to_str().unwrap(),
+            "--spool-dir",
+            spool_dir.to_str().unwrap(),
+            "--breaker-threshold",
+            "2",
+        ]);
+        let run = |service: &str| {
+            let mut snapshot = empty_snapshot();
+            snapshot.services.push(service.to_string());
+            let payload = Payload::Snapshot(snapshot.clone());
+            let (args, sinks) = (&args, &sinks);
+            async move { deliver_to_all(args, &[], sinks, &snapshot, &payload).await }
+        };
+
+        assert!(!run("a").await);
+        assert!(!run("b").await);
+        assert_eq!(*attempts.lock().unwrap(), 2);
+        // The breaker is open, so the endpoint is left alone
+        assert!(!run("c").await);
+        assert_eq!(*attempts.lock().unwrap(), 2);
+        let mut state = State::load(&state_file);
+        assert!(state.retry_after("flaky").is_some());
+
+        // Once the backoff is over, and the endpoint is back
+        state.failures.get_mut("flaky").unwrap().retry_after = Some(Utc::now());
+        state.save(&state_file).unwrap();
+        up.store(true, Ordering::Relaxed);
+        assert!(run("d").await);
+
+        let services: Vec<serde_json::Value> = delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|json| json["services"][0].clone())
+            .collect();
+        assert_eq!(services, ["a", "b", "c", "d"]);
+        assert!(State::load(&state_file).failures.is_empty());
+        // Each url spools to a directory of its own
+        let spooled = std::fs::read_dir(&spool_dir)
+            .unwrap()
+            .flatten()
+            .flat_map(|url_dir| std::fs::read_dir(url_dir.path()).unwrap().flatten());
+        assert_eq!(spooled.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn sends_whole_snapshots_while_delta_payloads_are_spooled() {
+        let up = Arc::new(AtomicBool::new(true));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sinks: Vec<Box<dyn SnapshotSink>> = vec![Box::new(FlakySink {
+            up: up.clone(),
+            attempts: Arc::new(Mutex::new(0)),
+            delivered: delivered.clone(),
+        })];
+        let dir = TempDir::new();
+        let state_file = dir.path().join("state.json");
+        let spool_dir = dir.path().join("spool");
+        let args = args(&[
+            "--state-file",
+            state_file.to_str().unwrap(),
+            "--spool-dir",
+            spool_dir.to_str().unwrap(),
+            "--delta",
+        ]);
+        let snapshots: Vec<HostSnapshot> = ["bash", "curl", "jq", "git"]
+            .iter()
+            .scan(Vec::new(), |components, package| {
+                components.push(component(&format!("pkg:deb/debian/{}@1.0", package)));
+                let mut snapshot = empty_snapshot();
+                snapshot.software_components = components.clone();
+                Some(snapshot)
+            })
+            .collect();
+        let run = |snapshot: &HostSnapshot| {
+            let payload = Payload::Snapshot(snapshot.clone());
+            let (args, sinks, snapshot) = (&args, &sinks, snapshot.clone());
+            async move { deliver_to_all(args, &[], sinks, &snapshot, &payload).await }
+        };
+
+        assert!(run(&snapshots[0]).await);
+        // Down, so the delta against snapshots[0] isn't sent and the whole snapshot is spooled
+        up.store(false, Ordering::Relaxed);
+        assert!(!run(&snapshots[1]).await);
+        up.store(true, Ordering::Relaxed);
+        // The spooled snapshot goes first, and with it the baseline moves on, so this one
+        // goes whole too
+        assert!(run(&snapshots[2]).await);
+        assert!(run(&snapshots[3]).await);
+
+        let delivered = delivered.lock().unwrap();
+        let components =
+            |json: &serde_json::Value| json["software_components"].as_array().map(Vec::len);
+        assert_eq!(delivered.len(), 4);
+        assert_eq!(components(&delivered[0]), Some(1));
+        assert_eq!(components(&delivered[1]), Some(2));
+        assert_eq!(components(&delivered[2]), Some(3));
+        assert!(delivered[2].get("software_delta").is_none());
+        let delta = &delivered[3]["software_delta"];
+        assert_eq!(
+            delta["baseline"],
+            delta::component_set_digest(&snapshots[2].software_components)
+        );
+        assert_eq!(
+            delta["added"],
+            serde_json::to_value([component("pkg:deb/debian/git@1.0")]).unwrap()
+        );
+    }
+
+    #[test]
+    fn altering_signed_envelope_fails_verification() {
+        let key = SigningKey::from_pem(SIGNING_KEY_PEM).unwrap();
+        let verifying_key = VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap();
+        let signed = sign_snapshot(&args(&[]), &key, empty_snapshot()).unwrap();
+        assert_eq!(signed.signed.key_id, verifying_key.key_id());
+        assert_eq!(signed.signed.algorithm, "RS256");
+        // Altered as text: a serde_json::Value would reorder the signed fields
+        let envelope = serde_json::to_string(&signed).unwrap();
+        verify::verify(&envelope, &verifying_key).unwrap();
+
+        let tamper = |from: String, to: &str| {
+            assert!(envelope.contains(&from));
+            verify::verify(&envelope.replacen(&from, to, 1), &verifying_key)
+        };
+        let signed_at = format!(
+            "\"signed_at\":{}",
+            serde_json::to_string(&signed.signed.signed_at).unwrap()
+        );
+        assert_eq!(
+            tamper(signed_at, "\"signed_at\":\"2030-01-01T00:00:00Z\""),
+            Err("Signature does not match the snapshot".to_string())
+        );
+        assert!(tamper("\"id\":\"test-host\"".to_string(), "\"id\":\"other\"").is_err());
+        assert!(tamper(
+            "\"algorithm\":\"RS256\"".to_string(),
+            "\"algorithm\":\"none\""
+        )
+        .is_err());
+        assert!(tamper(
+            format!("\"key_id\":\"{}\"", verifying_key.key_id()),
+            &format!("\"key_id\":\"{}\"", "0".repeat(64))
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn routes_each_section_to_its_own_url() {
+        let processes_server = MockServer::start().await;
+        let software_server = MockServer::start().await;
+        let processes_route = format!("processes={}/processes", processes_server.url);
+        let software_route = format!("software_components={}/software", software_server.url);
+        let args = args(&["--route", &processes_route, "--route", &software_route]);
+        let mut snapshot = empty_snapshot();
+        snapshot.processes = Some(vec![processes::ProcessInfo {
+            pid: 1,
+            name: "systemd".to_string(),
+            exe: None,
+            user: Some("root".to_string()),
+            cpu_usage: 0.5,
+            memory: 4096,
+        }]);
+        snapshot.software_components = vec![
+            component("pkg:deb/debian/bash@5.2.15-2"),
+            component("pkg:deb/debian/curl@7.88.1-10"),
+        ];
+
+        assert!(route_sections(&args, &mut snapshot).await);
+        let lines = |server: &MockServer| -> Vec<serde_json::Value> {
+            let requests = server.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(
+                requests[0].header("content-type"),
+                Some("application/x-ndjson")
+            );
+            String::from_utf8(requests[0].body.clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+        let processes = lines(&processes_server);
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0]["id"], "test-host");
+        assert_eq!(processes[0]["record"]["name"], "systemd");
+        let software = lines(&software_server);
+        assert_eq!(software.len(), 2);
+        assert_eq!(software[1]["record"]["details"]["name"], "curl");
+
+        // Taken out of the snapshot itself
+        assert!(snapshot.processes.is_none());
+        assert!(snapshot.software_components.is_empty());
+        assert_eq!(
+            snapshot.routed_sections,
+            ["processes", "software_components"]
+        );
+    }
+
+    #[test]
+    fn counts_device_mounted_twice_once() {
+        let disk = |name: &str, mount_point: &str, total_space: u64| DiskInfo {
+            name: name.to_string(),
+            kind: "SSD".to_string(),
+            file_system: "ext4".to_string(),
+            mount_point: mount_point.to_string(),
+            total_space,
+            available_space: total_space / 2,
+            used_percent: 50.0,
+            low_space: false,
+            is_removable: false,
+            mount_options: vec!["rw".to_string()],
+            total_inodes: None,
+            available_inodes: None,
+            duplicate_of: None,
+            also_mounted_at: Vec::new(),
+        };
+        let gib = 1 << 30;
+        let disks = || {
+            vec![
+                (disk("/dev/sda1", "/", 100 * gib), Some(0x801)),
+                (disk("/dev/sdb1", "/srv", 500 * gib), Some(0x811)),
+                // The root filesystem again, as a bind mount
+                (disk("/dev/sda1", "/var/lib/docker", 100 * gib), Some(0x801)),
+                // No device number to compare
+                (disk("tmpfs", "/run", gib), None),
+            ]
+        };
+
+        let marked = mark_duplicate_mounts(disks(), false);
+        let duplicates: Vec<_> = marked
+            .iter()
+            .map(|disk| disk.duplicate_of.as_deref())
+            .collect();
+        assert_eq!(duplicates, [None, None, Some("/"), None]);
+        assert_eq!(total_physical_space(&marked), 601 * gib);
+
+        let deduped = mark_duplicate_mounts(disks(), true);
+        let mount_points: Vec<_> = deduped.iter().map(|disk| &disk.mount_point).collect();
+        assert_eq!(mount_points, ["/", "/srv", "/run"]);
+        assert_eq!(deduped[0].also_mounted_at, ["/var/lib/docker"]);
+        assert_eq!(total_physical_space(&deduped), 601 * gib);
+    }
+
+    #[test]
+    fn flags_mostly_full_disk_as_low_on_space() {
+        let threshold = args(&[]).low_space_threshold;
+        assert_eq!(threshold, 90.0);
+        // 100 GiB with 7.5 GiB left
+        let total = 100 << 30;
+        let available = 15 << 29;
+        assert_eq!(space_used(total, available, threshold), (92.5, true));
+        assert_eq!(space_used(total, available, 95.0), (92.5, false));
+        assert_eq!(space_used(total, total / 3, threshold), (66.67, false));
+        assert_eq!(space_used(0, 0, threshold), (0.0, false));
+        // At the threshold counts
+        assert_eq!(space_used(10, 1, threshold), (90.0, true));
+        assert!(Args::try_parse_from(["hsnap", "--low-space-threshold", "101"]).is_err());
+    }
+
+    #[test]
+    fn forwarded_args_are_checked() {
+        let forwarded = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(
+            check_forwarded_args(&forwarded(&["--url", "https://inventory.example.com"])).is_ok()
+        );
+        let error = check_forwarded_args(&forwarded(&["--urll", "https://inventory.example.com"]))
+            .unwrap_err();
+        assert!(error.contains("--urll"), "{}", error);
+    }
+
+    #[test]
+    fn digest_matches_independent_hash() {
+        use sha2::Sha512;
+
+        let mut snapshot = empty_snapshot();
+        snapshot.services.push("sshd".to_string());
+        for (algorithm, length) in [("sha256", 64), ("sha512", 128)] {
+            let args = args(&["--digest", algorithm]);
+            let emitted =
+                serde_json::to_string_pretty(&build_payload(&args, &[], snapshot.clone()).unwrap())
+                    .unwrap();
+
+            // As a consumer would: drop the digest and hash the rest, compact with sorted keys
+            let mut json: serde_json::Value = serde_json::from_str(&emitted).unwrap();
+            let digest = json.as_object_mut().unwrap().remove("digest").unwrap();
+            assert_eq!(digest["algorithm"], algorithm);
+            let canonical = serde_json::to_vec(&json).unwrap();
+            let expected = match algorithm {
+                "sha256" => hex::encode(Sha256::digest(&canonical)),
+                _ => hex::encode(Sha512::digest(&canonical)),
+            };
+            assert_eq!(expected.len(), length);
+            assert_eq!(digest["value"], expected);
+        }
+        let emitted =
+            serde_json::to_value(build_payload(&args(&[]), &[], snapshot).unwrap()).unwrap();
+        assert!(emitted.get("digest").is_none());
+    }
+
+    #[test]
+    fn components_only_json_is_a_bare_array() {
+        let mut snapshot = empty_snapshot();
+        snapshot.software_components = vec![
+            component("pkg:deb/debian/bash@5.2.15-2?arch=amd64"),
+            component("pkg:deb/debian/curl@7.88.1-10?arch=amd64"),
+        ];
+
+        let objects = args(&["--components-only-json"]);
+        let json =
+            serde_json::to_value(build_payload(&objects, &[], snapshot.clone()).unwrap()).unwrap();
+        let components = json.as_array().unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0]["type"], "Purl");
+        assert_eq!(components[0]["details"]["name"], "bash");
+        assert_eq!(components[0]["source"], "debian");
+
+        let purls = args(&["--components-only-json", "--component-style", "purl"]);
+        let json = serde_json::to_value(build_payload(&purls, &[], snapshot).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                "pkg:deb/debian/bash@5.2.15-2?arch=amd64",
+                "pkg:deb/debian/curl@7.88.1-10?arch=amd64",
+            ])
+        );
+    }
+
+    #[test]
+    fn emits_the_exact_signed_bytes() {
+        let signing_key = format!("--signing-key={}", SIGNING_KEY_PEM);
+        let args = args(&[&signing_key, "--no-signature-reserialize"]);
+        let keys = signing_keys(&args).unwrap();
+        let emitted =
+            serde_json::to_string_pretty(&build_payload(&args, &keys, empty_snapshot()).unwrap())
+                .unwrap();
+
+        let envelope: serde_json::Value = serde_json::from_str(&emitted).unwrap();
+        let signed = envelope["signed"].as_str().unwrap();
+        let signature = hex::decode(envelope["signature"].as_str().unwrap()).unwrap();
+        // PKCS#1 v1.5 is deterministic, so signing the emitted bytes again gives the same
+        // signature
+        let key = SigningKey::from_pem(SIGNING_KEY_PEM).unwrap();
+        assert_eq!(key.sign(signed.as_bytes()).unwrap(), signature);
+        let verifying_key = VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap();
+        assert!(verifying_key.verify(signed.as_bytes(), &signature, None));
+        verify::verify(&emitted, &verifying_key).unwrap();
+
+        let fields: serde_json::Value = serde_json::from_str(signed).unwrap();
+        assert_eq!(fields["snapshot"]["metadata"]["id"], "test-host");
+        assert_eq!(fields["key_id"], verifying_key.key_id());
+    }
+
+    #[test]
+    fn signs_with_each_signing_key_file() {
+        let dir = TempDir::new();
+        let old_key = dir.path().join("old.pem");
+        std::fs::write(&old_key, SIGNING_KEY_PEM).unwrap();
+        let new_key = dir.path().join("new.pem");
+        std::fs::write(&new_key, ROTATED_SIGNING_KEY_PEM).unwrap();
+        let verifying_keys = [VERIFYING_KEY_PEM, ROTATED_VERIFYING_KEY_PEM]
+            .map(|pem| VerifyingKey::from_pem(pem).unwrap());
+        for reserialize in [None, Some("--no-signature-reserialize")] {
+            let mut flags = vec![
+                "--signing-key-file",
+                old_key.to_str().unwrap(),
+                "--signing-key-file",
+                new_key.to_str().unwrap(),
+            ];
+            flags.extend(reserialize);
+            let args = args(&flags);
+            let keys = signing_keys(&args).unwrap();
+            let envelope =
+                serde_json::to_string(&build_payload(&args, &keys, empty_snapshot()).unwrap())
+                    .unwrap();
+
+            let fields: serde_json::Value = serde_json::from_str(&envelope).unwrap();
+            let key_ids: Vec<_> = fields["signatures"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|signature| signature["key_id"].as_str().unwrap())
+                .collect();
+            assert_eq!(key_ids, verifying_keys.each_ref().map(|key| key.key_id()));
+            for key in &verifying_keys {
+                verify::verify(&envelope, key).unwrap();
+            }
+            let ecdsa_key = VerifyingKey::from_pem(ECDSA_VERIFYING_KEY_PEM).unwrap();
+            assert!(verify::verify(&envelope, &ecdsa_key).is_err());
+        }
+    }
+
+    #[test]
+    fn signing_failure_is_returned_not_panicked() {
+        struct FailingSigner;
+        impl Signer for FailingSigner {
+            fn algorithm(&self) -> &'static str {
+                "RS256"
+            }
+
+            fn key_id(&self) -> String {
+                "0".repeat(64)
+            }
+
+            fn sign(&self, _: &[u8]) -> Result<Vec<u8>, String> {
+                Err("token removed".to_string())
+            }
+        }
+
+        let signing_key = format!("--signing-key={}", SIGNING_KEY_PEM);
+        let args = args(&[&signing_key]);
+        let mut keys = signing_keys(&args).unwrap();
+        keys.push(Box::new(FailingSigner));
+        for keys in [&keys[1..], &keys[..]] {
+            let error = build_payload(&args, keys, empty_snapshot()).err();
+            assert_eq!(error.as_deref(), Some("token removed"));
+        }
+    }
+
+    #[test]
+    fn signers_are_covered_by_every_signature() {
+        let dir = TempDir::new();
+        let old_key = dir.path().join("old.pem");
+        std::fs::write(&old_key, SIGNING_KEY_PEM).unwrap();
+        let new_key = dir.path().join("new.pem");
+        std::fs::write(&new_key, ROTATED_SIGNING_KEY_PEM).unwrap();
+        let args = args(&[
+            "--signing-key-file",
+            old_key.to_str().unwrap(),
+            "--signing-key-file",
+            new_key.to_str().unwrap(),
+        ]);
+        let keys = signing_keys(&args).unwrap();
+        let envelope =
+            serde_json::to_string(&build_payload(&args, &keys, empty_snapshot()).unwrap()).unwrap();
+        let verifying_key = VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap();
+        verify::verify(&envelope, &verifying_key).unwrap();
+
+        let fields: serde_json::Value = serde_json::from_str(&envelope).unwrap();
+        let key_details = |list: &str| -> Vec<_> {
+            fields[list]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| (entry["key_id"].clone(), entry["algorithm"].clone()))
+                .collect()
+        };
+        assert_eq!(key_details("signers"), key_details("signatures"));
+
+        // What a signature says about its key has to agree with the signed list
+        let relabelled = envelope.replacen(r#""RS256","signature""#, r#""ES256","signature""#, 1);
+        assert_ne!(relabelled, envelope);
+        let error = verify::verify(&relabelled, &verifying_key).unwrap_err();
+        assert!(error.contains("signed list of signers"), "{}", error);
+
+        // The list itself is signed
+        let second_signer = format!(
+            r#",{{"key_id":{},"algorithm":"RS256"}}"#,
+            fields["signers"][1]["key_id"]
+        );
+        let unlisted = envelope.replacen(&second_signer, "", 1);
+        assert_ne!(unlisted, envelope);
+        let error = verify::verify(&unlisted, &verifying_key).unwrap_err();
+        assert_eq!(error, "Signature does not match the snapshot");
+    }
+
+    #[test]
+    fn signs_and_verifies_with_ecdsa_p256() {
+        let verifying_key = VerifyingKey::from_pem(ECDSA_VERIFYING_KEY_PEM).unwrap();
+        let signing_key = format!("--signing-key={}", ECDSA_SIGNING_KEY_PEM);
+        for format in ["der", "raw"] {
+            let args = args(&[
+                &signing_key,
+                "--signing-algorithm",
+                "ecdsa-p256",
+                "--ecdsa-signature-format",
+                format,
+            ]);
+            // Altered as text: a serde_json::Value would reorder the signed fields
+            let keys = signing_keys(&args).unwrap();
+            let envelope =
+                serde_json::to_string(&build_payload(&args, &keys, empty_snapshot()).unwrap())
+                    .unwrap();
+            let fields: serde_json::Value = serde_json::from_str(&envelope).unwrap();
+            assert_eq!(fields["algorithm"], "ES256");
+            assert_eq!(fields["curve"], "P-256");
+            assert_eq!(fields["signature_format"], format);
+            assert_eq!(fields["key_id"], verifying_key.key_id());
+            let signature = hex::decode(fields["signature"].as_str().unwrap()).unwrap();
+            match format {
+                "raw" => assert_eq!(signature.len(), 64),
+                _ => assert_eq!(signature[0], 0x30),
+            }
+            verify::verify(&envelope, &verifying_key).unwrap();
+
+            let tampered = envelope.replacen("\"id\":\"test-host\"", "\"id\":\"other\"", 1);
+            assert_eq!(
+                verify::verify(&tampered, &verifying_key),
+                Err("Signature does not match the snapshot".to_string())
+            );
+            // The signature can't be read in the other format
+            let other = if format == "der" { "raw" } else { "der" };
+            let reformatted = envelope.replacen(
+                &format!("\"signature_format\":\"{}\"", format),
+                &format!("\"signature_format\":\"{}\"", other),
+                1,
+            );
+            assert!(verify::verify(&reformatted, &verifying_key).is_err());
+        }
+
+        let rsa_key = VerifyingKey::from_pem(VERIFYING_KEY_PEM).unwrap();
+        assert_eq!(verifying_key.algorithm(), "ES256");
+        assert_eq!(rsa_key.algorithm(), "RS256");
+    }
+
+    #[tokio::test]
+    async fn failing_plugin_exits_non_zero_under_fail_fast() {
+        let dir = TempDir::new();
+        std::fs::write(dir.path().join("broken.json"), "{\"name\": ").unwrap();
+        let plugin_dir = dir.path().to_str().unwrap();
+
+        let fail_fast = args(&["--plugin-dir", plugin_dir, "--fail-fast"]);
+        let snapshot = capture_snapshot(&fail_fast).await;
+        assert_eq!(snapshot.collection_errors.len(), 1);
+        assert_eq!(
+            snapshot.collection_errors[0].section,
+            sections::SOFTWARE_COMPONENTS
+        );
+        assert!(snapshot.collection_errors[0].error.contains("broken.json"));
+        assert_eq!(fail_fast_status(&fail_fast, &snapshot), Some(1));
+
+        // Best effort by default, but still recorded
+        let best_effort = args(&["--plugin-dir", plugin_dir]);
+        let snapshot = capture_snapshot(&best_effort).await;
+        assert_eq!(snapshot.collection_errors.len(), 1);
+        assert_eq!(fail_fast_status(&best_effort, &snapshot), None);
+    }
+
+    #[tokio::test]
+    async fn failing_plugin_leaves_baseline_untouched() {
+        let dir = TempDir::new();
+        std::fs::write(dir.path().join("broken.json"), "{\"name\": ").unwrap();
+        let state_dir = TempDir::new();
+        let baseline_file = state_dir.path().join("baseline.json");
+        let baseline = vec![ComponentWithSource {
+            component: SoftwareComponent::Purl("pkg:deb/bash@5.2".parse().unwrap()),
+            source: "debian-dpkg".to_string(),
+            license: None,
+            homepage: None,
+        }];
+        baseline::save(&baseline_file, &baseline).unwrap();
+
+        let args = args(&[
+            "--plugin-dir",
+            dir.path().to_str().unwrap(),
+            "--component-kinds",
+            "none",
+            "--baseline-file",
+            baseline_file.to_str().unwrap(),
+        ]);
+        let mut snapshot = capture_snapshot(&args).await;
+        assert!(!is_complete(&snapshot));
+        assert!(apply_baseline(&args, &mut snapshot).is_none());
+        // Output in full, rather than as bash having been removed
+        assert!(snapshot.software_delta.is_none());
+        assert!(snapshot.hardware.is_some());
+        assert_eq!(
+            serde_json::to_value(baseline::load(&baseline_file).unwrap()).unwrap(),
+            serde_json::to_value(&baseline).unwrap()
+        );
+
+        // Once the plugin is fixed, the baseline is replaced
+        std::fs::remove_file(dir.path().join("broken.json")).unwrap();
+        let mut snapshot = capture_snapshot(&args).await;
+        let (path, components) = apply_baseline(&args, &mut snapshot).unwrap();
+        assert_eq!(path, &baseline_file);
+        assert!(components.is_empty());
+        assert!(snapshot.software_delta.is_some());
+    }
+
+    #[test]
+    fn failing_section_leaves_rest_of_snapshot() {
+        let args = args(&["--top-processes", "5", "--component-kinds", "none"]);
+        let partial = Mutex::new(PartialSnapshot {
+            fail_section: Some(sections::HARDWARE),
+            ..PartialSnapshot::default()
+        });
+        collect_sections(&args, &partial);
+
+        let partial = partial.into_inner().unwrap();
+        assert!(partial.hardware.is_none());
+        assert_eq!(partial.collection_errors.len(), 1);
+        assert_eq!(partial.collection_errors[0].section, "hardware");
+        assert_eq!(partial.collection_errors[0].error, "injected failure");
+        // Sections collected both before and after the failing one
+        assert!(partial.operating_system.is_some());
+        assert!(partial.network.is_some());
+        assert!(partial.storage.is_some());
+        assert!(partial
+            .processes
+            .as_ref()
+            .is_some_and(|processes| !processes.is_empty()));
+        assert!(partial.software_components.is_some());
+
+        // Left out of the snapshot, rather than output as zeroed readings
+        let metadata = Metadata {
+            id: "test-host".to_string(),
+            timestamp: Utc::now(),
+            clock_time: None,
+            machine_id: None,
+            environment: environment::detect(),
+            capture_duration_ms: 0,
+        };
+        let json =
+            serde_json::to_value(partial.into_snapshot(metadata, Vec::new(), false)).unwrap();
+        assert!(json.get("hardware").is_none());
+        assert!(json["operating_system"].is_object());
     }
 }
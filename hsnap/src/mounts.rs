@@ -0,0 +1,189 @@
+use std::path::Path;
+
+/// A single line of `/proc/mounts`.
+pub struct MountEntry {
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+/// Reads the mount table. Empty on platforms without `/proc/mounts`.
+pub fn read_mounts() -> Vec<MountEntry> {
+    std::fs::read_to_string("/proc/mounts")
+        .map(|contents| parse_mounts(&contents))
+        .unwrap_or_default()
+}
+
+pub fn parse_mounts(contents: &str) -> Vec<MountEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            Some(MountEntry {
+                mount_point: unescape(fields.next()?),
+                fs_type: fields.next()?.to_string(),
+                options: fields.next()?.split(',').map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Options of the mount at `mount_point`. When mounts are stacked (e.g. a bind or
+/// overlay mount over an existing one) the last mount with a matching filesystem
+/// type is preferred, falling back to the last, visible mount.
+pub fn options_for<'a>(
+    mounts: &'a [MountEntry],
+    mount_point: &str,
+    fs_type: &str,
+) -> Option<&'a [String]> {
+    let mut at_point = mounts
+        .iter()
+        .rev()
+        .filter(|mount| mount.mount_point == mount_point);
+    at_point
+        .clone()
+        .find(|mount| mount.fs_type == fs_type)
+        .or_else(|| at_point.next())
+        .map(|mount| mount.options.as_slice())
+}
+
+/// Total and available inodes of the filesystem mounted at `mount_point`, or `None`
+/// where the filesystem or platform doesn't report them.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // fsfilcnt_t is narrower than u64 on some platforms
+pub fn inode_usage(mount_point: &Path) -> (Option<u64>, Option<u64>) {
+    match nix::sys::statvfs::statvfs(mount_point) {
+        // Filesystems without a fixed inode table (e.g. btrfs, vfat) report zero
+        Ok(stat) if stat.files() > 0 => (
+            Some(stat.files() as u64),
+            Some(stat.files_available() as u64),
+        ),
+        _ => (None, None),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn inode_usage(_mount_point: &Path) -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+/// The device number of the filesystem mounted at `mount_point`, which is the same for
+/// every mount of it, bind mounts included. `None` where the platform has none.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // dev_t is narrower than u64 on some platforms
+pub fn device_id(mount_point: &Path) -> Option<u64> {
+    nix::sys::stat::stat(mount_point)
+        .ok()
+        .map(|stat| stat.st_dev as u64)
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_mount_point: &Path) -> Option<u64> {
+    None
+}
+
+/// Undoes the octal escaping (`\040` for a space, etc.) the kernel applies to mount fields.
+pub fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes.get(i + 1..i + 4) {
+            Some(digits)
+                if bytes[i] == b'\\' && digits.iter().all(|b| (b'0'..=b'7').contains(b)) =>
+            {
+                out.push(
+                    digits
+                        .iter()
+                        .fold(0u8, |acc, b| acc.wrapping_mul(8) + (b - b'0')),
+                );
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+/dev/sda1 / ext4 rw,relatime,errors=remount-ro 0 0
+tmpfs /tmp tmpfs rw,nosuid,nodev,noexec,size=2097152k 0 0
+/dev/sda1 /srv/data ext4 ro,relatime 0 0
+/dev/sdb1 /mnt/usb\\040stick vfat rw,noexec,uid=1000 0 0
+/dev/sda2 /var/lib/docker ext4 rw,relatime 0 0
+overlay /var/lib/docker overlay rw,relatime,lowerdir=/l1:/l2,upperdir=/u,workdir=/w 0 0
+";
+
+    #[test]
+    fn parses_options_for_mount() {
+        let mounts = parse_mounts(PROC_MOUNTS);
+        assert_eq!(mounts.len(), 7);
+
+        assert_eq!(
+            options_for(&mounts, "/tmp", "tmpfs").unwrap(),
+            ["rw", "nosuid", "nodev", "noexec", "size=2097152k"]
+        );
+        // A bind mount has options of its own
+        assert_eq!(
+            options_for(&mounts, "/srv/data", "ext4").unwrap(),
+            ["ro", "relatime"]
+        );
+        assert_eq!(
+            options_for(&mounts, "/mnt/usb stick", "vfat").unwrap(),
+            ["rw", "noexec", "uid=1000"]
+        );
+        assert!(options_for(&mounts, "/home", "ext4").is_none());
+    }
+
+    #[test]
+    fn prefers_stacked_mount_of_same_type() {
+        let mounts = parse_mounts(PROC_MOUNTS);
+
+        assert!(options_for(&mounts, "/var/lib/docker", "overlay")
+            .unwrap()
+            .contains(&"upperdir=/u".to_string()));
+        assert_eq!(
+            options_for(&mounts, "/var/lib/docker", "ext4").unwrap(),
+            ["rw", "relatime"]
+        );
+        // Falls back to the topmost mount when none has the type
+        assert!(options_for(&mounts, "/var/lib/docker", "xfs")
+            .unwrap()
+            .contains(&"workdir=/w".to_string()));
+    }
+
+    #[test]
+    fn unescapes_octal_sequences() {
+        assert_eq!(unescape("/mnt/my\\040disk\\011tab"), "/mnt/my disk\ttab");
+        assert_eq!(unescape("/back\\134slash"), "/back\\slash");
+        assert_eq!(unescape("/not\\08escape\\"), "/not\\08escape\\");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reports_inodes_of_root_filesystem() {
+        // Unless / is a filesystem without an inode table, e.g. btrfs
+        let root_fs = read_mounts()
+            .into_iter()
+            .rev()
+            .find(|mount| mount.mount_point == "/")
+            .map(|mount| mount.fs_type)
+            .unwrap_or_default();
+        if ["btrfs", "vfat", "zfs"].contains(&root_fs.as_str()) {
+            return;
+        }
+
+        let (total, available) = inode_usage(Path::new("/"));
+        let total = total.unwrap();
+        assert!(total > 0);
+        assert!(available.unwrap() <= total);
+    }
+}
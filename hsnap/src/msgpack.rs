@@ -0,0 +1,130 @@
+/// How deeply arrays and maps may nest, so a hostile document can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Transcodes a MessagePack document to compact JSON, keeping map entries in their order.
+/// Binary and extension values, and maps with keys other than strings, have no JSON
+/// equivalent and are rejected.
+pub fn to_json(data: &[u8]) -> Result<String, String> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut out = String::new();
+    reader.value(&mut out, 0)?;
+    if reader.pos != data.len() {
+        return Err("trailing bytes after MessagePack value".to_string());
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or("truncated MessagePack value")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// A big endian unsigned integer of `len` bytes.
+    fn uint(&mut self, len: usize) -> Result<u64, String> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as u64))
+    }
+
+    /// A big endian signed integer of `len` bytes.
+    fn int(&mut self, len: usize) -> Result<i64, String> {
+        let shift = 64 - 8 * len as u32;
+        Ok(((self.uint(len)? << shift) as i64) >> shift)
+    }
+
+    fn value(&mut self, out: &mut String, depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err("MessagePack value nested too deeply".to_string());
+        }
+        let marker = self.bytes(1)?[0];
+        match marker {
+            0x00..=0x7f => out.push_str(&marker.to_string()),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, out, depth)?,
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, out, depth)?,
+            0xa0..=0xbf => self.string((marker & 0x1f) as usize, out)?,
+            0xc0 => out.push_str("null"),
+            0xc2 => out.push_str("false"),
+            0xc3 => out.push_str("true"),
+            0xca => {
+                let value = f32::from_bits(self.uint(4)? as u32);
+                out.push_str(&serde_json::to_string(&value).map_err(|e| e.to_string())?);
+            }
+            0xcb => {
+                let value = f64::from_bits(self.uint(8)?);
+                out.push_str(&serde_json::to_string(&value).map_err(|e| e.to_string())?);
+            }
+            0xcc..=0xcf => {
+                let value = self.uint(1 << (marker - 0xcc))?;
+                out.push_str(&value.to_string());
+            }
+            0xd0..=0xd3 => {
+                let value = self.int(1 << (marker - 0xd0))?;
+                out.push_str(&value.to_string());
+            }
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))? as usize;
+                self.string(len, out)?;
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(2 << (marker - 0xdc))? as usize;
+                self.array(len, out, depth)?;
+            }
+            0xde | 0xdf => {
+                let len = self.uint(2 << (marker - 0xde))? as usize;
+                self.map(len, out, depth)?;
+            }
+            0xe0..=0xff => out.push_str(&(marker as i8).to_string()),
+            0xc4..=0xc6 => return Err("MessagePack binary values have no JSON form".to_string()),
+            _ => return Err(format!("Unsupported MessagePack type {:#04x}", marker)),
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, len: usize, out: &mut String) -> Result<(), String> {
+        let text = std::str::from_utf8(self.bytes(len)?).map_err(|e| e.to_string())?;
+        out.push_str(&serde_json::to_string(text).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+
+    fn array(&mut self, len: usize, out: &mut String, depth: usize) -> Result<(), String> {
+        out.push('[');
+        for i in 0..len {
+            if i > 0 {
+                out.push(',');
+            }
+            self.value(out, depth + 1)?;
+        }
+        out.push(']');
+        Ok(())
+    }
+
+    fn map(&mut self, len: usize, out: &mut String, depth: usize) -> Result<(), String> {
+        out.push('{');
+        for i in 0..len {
+            if i > 0 {
+                out.push(',');
+            }
+            let key_start = out.len();
+            self.value(out, depth + 1)?;
+            if !out[key_start..].starts_with('"') {
+                return Err("MessagePack map keys must be strings".to_string());
+            }
+            out.push(':');
+            self.value(out, depth + 1)?;
+        }
+        out.push('}');
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where Linux lists the NUMA nodes, as `node0`, `node1`, ...
+const NODE_DIR: &str = "/sys/devices/system/node";
+
+/// A NUMA node: a set of CPUs and the memory closest to them.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NumaNode {
+    pub id: u32,
+    /// The logical CPUs on the node, e.g. `[0, 1, 2, 3]`.
+    pub cpus: Vec<u32>,
+    /// In bytes.
+    pub total_memory: u64,
+    /// In bytes.
+    pub free_memory: u64,
+}
+
+/// The NUMA nodes in sysfs, sorted by id. Empty on platforms without it, and on kernels
+/// built without NUMA support.
+pub fn collect() -> Vec<NumaNode> {
+    nodes_in(Path::new(NODE_DIR))
+}
+
+/// The `node*` directories in `dir`, read as nodes.
+fn nodes_in(dir: &Path) -> Vec<NumaNode> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<NumaNode> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let id = name.to_str()?.strip_prefix("node")?.parse().ok()?;
+            Some(read_node(&entry.path(), id))
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+fn read_node(dir: &Path, id: u32) -> NumaNode {
+    let cpus = std::fs::read_to_string(dir.join("cpulist"))
+        .map(|contents| parse_cpulist(&contents))
+        .unwrap_or_default();
+    let (total_memory, free_memory) = std::fs::read_to_string(dir.join("meminfo"))
+        .map(|contents| parse_node_meminfo(&contents))
+        .unwrap_or_default();
+    NumaNode {
+        id,
+        cpus,
+        total_memory,
+        free_memory,
+    }
+}
+
+/// Parses a kernel CPU list, ranges and single CPUs separated by commas, e.g. `0-3,8-11,16`.
+/// A node without CPUs, such as one of only memory, has an empty list.
+fn parse_cpulist(contents: &str) -> Vec<u32> {
+    let mut cpus = Vec::new();
+    for part in contents.trim().split(',').filter(|part| !part.is_empty()) {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        if let (Ok(first), Ok(last)) = (first.parse::<u32>(), last.parse::<u32>()) {
+            cpus.extend(first..=last);
+        }
+    }
+    cpus
+}
+
+/// The total and free memory, in bytes, of a node's `meminfo`, which gives sizes in KiB:
+///
+/// ```text
+/// Node 0 MemTotal:       65842216 kB
+/// Node 0 MemFree:        41207104 kB
+/// Node 0 MemUsed:        24635112 kB
+/// ```
+fn parse_node_meminfo(contents: &str) -> (u64, u64) {
+    let mut total = 0;
+    let mut free = 0;
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace().skip(2);
+        let (Some(key), Some(value)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Ok(kib) = value.parse::<u64>() else {
+            continue;
+        };
+        match key {
+            "MemTotal:" => total = kib * 1024,
+            "MemFree:" => free = kib * 1024,
+            _ => {}
+        }
+    }
+    (total, free)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn reads_sysfs_node_layout() {
+        let dir = TempDir::new();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("possible", "0-1\n");
+        write("node0/cpulist", "0-3,8-11\n");
+        write(
+            "node0/meminfo",
+            "Node 0 MemTotal:       65842216 kB\n\
+             Node 0 MemFree:        41207104 kB\n\
+             Node 0 MemUsed:        24635112 kB\n",
+        );
+        write("node1/cpulist", "4-7,12-15,16\n");
+        // A node of only memory
+        write("node2/cpulist", "\n");
+
+        let nodes = nodes_in(dir.path());
+        let layout: Vec<_> = nodes.iter().map(|node| (node.id, &node.cpus[..])).collect();
+        assert_eq!(
+            layout,
+            [
+                (0, &[0, 1, 2, 3, 8, 9, 10, 11][..]),
+                (1, &[4, 5, 6, 7, 12, 13, 14, 15, 16][..]),
+                (2, &[][..]),
+            ]
+        );
+        assert_eq!(nodes[0].total_memory, 65842216 * 1024);
+        assert_eq!(nodes[0].free_memory, 41207104 * 1024);
+        assert_eq!(nodes[1].total_memory, 0);
+        assert!(nodes_in(&dir.path().join("missing")).is_empty());
+    }
+}
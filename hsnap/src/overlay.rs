@@ -0,0 +1,170 @@
+use crate::mounts::{self, MountEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Docker's layer database: a directory per layer chain, holding the layer's diff ID in
+/// `diff` and the name of its directory under `/var/lib/docker/overlay2` in `cache-id`.
+const DOCKER_LAYERDB: &str = "/var/lib/docker/image/overlay2/layerdb/sha256";
+
+/// An overlay filesystem mount, such as a container's root filesystem.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OverlayMount {
+    pub mount_point: String,
+    /// The read-only layers, topmost first.
+    pub lower_dirs: Vec<OverlayLayer>,
+    /// The writable layer.
+    pub upper_dir: Option<String>,
+    pub work_dir: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OverlayLayer {
+    pub path: String,
+    /// The layer's diff ID, e.g. `sha256:...`. Only known for docker's layers; containerd
+    /// keeps its snapshots' digests in a database we don't read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// The overlay mounts in `/proc/mounts`, with the image layer digests docker records for
+/// their lower directories.
+pub fn collect() -> Vec<OverlayMount> {
+    let digests = docker_layer_digests();
+    mounts::read_mounts()
+        .iter()
+        .filter_map(parse_overlay)
+        .map(|mut mount| {
+            for layer in &mut mount.lower_dirs {
+                layer.digest = layer_cache_id(&layer.path)
+                    .and_then(|cache_id| digests.get(&cache_id))
+                    .cloned();
+            }
+            mount
+        })
+        .collect()
+}
+
+/// The layers of an overlay mount, from its `lowerdir=`, `upperdir=` and `workdir=`
+/// options, e.g. `lowerdir=/var/lib/docker/overlay2/l/ABC:/var/lib/docker/overlay2/l/DEF`.
+fn parse_overlay(mount: &MountEntry) -> Option<OverlayMount> {
+    if mount.fs_type != "overlay" {
+        return None;
+    }
+    let option = |name: &str| {
+        mount
+            .options
+            .iter()
+            .find_map(|option| option.strip_prefix(name)?.strip_prefix('='))
+            .map(mounts::unescape)
+    };
+    Some(OverlayMount {
+        mount_point: mount.mount_point.clone(),
+        lower_dirs: option("lowerdir")
+            .map(|dirs| {
+                dirs.split(':')
+                    .filter(|dir| !dir.is_empty())
+                    .map(|dir| OverlayLayer {
+                        path: dir.to_string(),
+                        digest: None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        upper_dir: option("upperdir"),
+        work_dir: option("workdir"),
+    })
+}
+
+/// The directory name docker gave a layer, from its `.../overlay2/<cache id>/diff` path.
+/// Docker mounts layers by the shorter links in `overlay2/l`, so those are resolved first.
+fn layer_cache_id(path: &str) -> Option<String> {
+    let path = std::fs::canonicalize(path).ok()?;
+    if path.file_name()? != "diff" {
+        return None;
+    }
+    let layer = path.parent()?;
+    (layer.parent()?.file_name()? == "overlay2")
+        .then(|| layer.file_name()?.to_str().map(str::to_string))
+        .flatten()
+}
+
+/// Each docker layer's diff ID, keyed by its cache ID.
+fn docker_layer_digests() -> HashMap<String, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|contents| contents.trim().to_string())
+    };
+    std::fs::read_dir(DOCKER_LAYERDB)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let dir = entry.path();
+                    Some((read(&dir.join("cache-id"))?, read(&dir.join("diff"))?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_overlay_mount_line() {
+        let mounts = mounts::parse_mounts(
+            "proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0\n\
+             overlay /var/lib/docker/overlay2/9f3c/merged overlay rw,relatime,\
+             lowerdir=/var/lib/docker/overlay2/l/QK7Z:/var/lib/docker/overlay2/l/A2BD,\
+             upperdir=/var/lib/docker/overlay2/9f3c/diff,\
+             workdir=/var/lib/docker/overlay2/9f3c/work,nouserxattr 0 0\n",
+        );
+        let overlays: Vec<OverlayMount> = mounts.iter().filter_map(parse_overlay).collect();
+
+        assert_eq!(overlays.len(), 1);
+        let overlay = &overlays[0];
+        assert_eq!(overlay.mount_point, "/var/lib/docker/overlay2/9f3c/merged");
+        let lower: Vec<&str> = overlay
+            .lower_dirs
+            .iter()
+            .map(|layer| layer.path.as_str())
+            .collect();
+        assert_eq!(
+            lower,
+            [
+                "/var/lib/docker/overlay2/l/QK7Z",
+                "/var/lib/docker/overlay2/l/A2BD"
+            ]
+        );
+        assert_eq!(
+            overlay.upper_dir.as_deref(),
+            Some("/var/lib/docker/overlay2/9f3c/diff")
+        );
+        assert_eq!(
+            overlay.work_dir.as_deref(),
+            Some("/var/lib/docker/overlay2/9f3c/work")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_layer_link_to_cache_id() {
+        let dir = crate::test_support::TempDir::new();
+        let overlay2 = dir.path().join("overlay2");
+        std::fs::create_dir_all(overlay2.join("4e1a7c/diff")).unwrap();
+        std::fs::create_dir_all(overlay2.join("l")).unwrap();
+        std::os::unix::fs::symlink("../4e1a7c/diff", overlay2.join("l/QK7Z")).unwrap();
+
+        let link = overlay2.join("l/QK7Z");
+        assert_eq!(
+            layer_cache_id(link.to_str().unwrap()).as_deref(),
+            Some("4e1a7c")
+        );
+        // Not a layer's diff directory
+        let work = overlay2.join("4e1a7c");
+        assert_eq!(layer_cache_id(work.to_str().unwrap()), None);
+    }
+}
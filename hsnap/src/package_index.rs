@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+/// Where apt, dnf and pacman keep the package indexes they download, whose directories
+/// are rewritten each time the indexes are refreshed.
+const INDEX_DIRS: [&str; 3] = [
+    "/var/lib/apt/lists",
+    "/var/cache/dnf",
+    "/var/lib/pacman/sync",
+];
+
+/// When the package manager's index was last refreshed (`apt update`, `dnf makecache`,
+/// `pacman -Sy`), from the modification time of its index directory. The latest of them
+/// on a host with more than one, `None` on one with none.
+pub fn last_updated() -> Option<DateTime<Utc>> {
+    newest_modified(INDEX_DIRS.iter().map(Path::new))
+}
+
+/// The latest modification time of the directories that exist.
+fn newest_modified<'a>(dirs: impl IntoIterator<Item = &'a Path>) -> Option<DateTime<Utc>> {
+    dirs.into_iter()
+        .filter_map(|dir| std::fs::metadata(dir).ok())
+        .filter(|metadata| metadata.is_dir())
+        .filter_map(|metadata| metadata.modified().ok())
+        .max()
+        .map(DateTime::<Utc>::from)
+}
+
+// Opening a directory to set its times takes extra flags on Windows
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn takes_newest_index_directory_mtime() {
+        let dir = TempDir::new();
+        let set_modified = |relative: &str, seconds: u64| {
+            let path = dir.path().join(relative);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::File::open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+            path
+        };
+        // 2024-03-01T12:00:00Z and a day earlier
+        let apt = set_modified("var/lib/apt/lists", 1_709_294_400);
+        let dnf = set_modified("var/cache/dnf", 1_709_208_000);
+        std::fs::write(dir.path().join("not-a-dir"), "").unwrap();
+        let missing = dir.path().join("var/lib/pacman/sync");
+
+        let updated = newest_modified([&*dnf, &*apt, &*missing]);
+        assert_eq!(
+            updated.map(|time| time.to_rfc3339()).as_deref(),
+            Some("2024-03-01T12:00:00+00:00")
+        );
+        assert_eq!(
+            newest_modified([&*dnf]).map(|time| time.timestamp()),
+            Some(1_709_208_000)
+        );
+        assert_eq!(
+            newest_modified([&*missing, &*dir.path().join("not-a-dir")]),
+            None
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use crate::sections;
+
+/// The sections that see less of the host when hsnap isn't privileged:
+///
+/// - `hardware`: Windows only tells administrators whether there is a TPM.
+/// - `processes`: other users' executables can't be read.
+/// - `scheduled_tasks`: other users' crontabs, or on Windows their tasks, can't be read.
+/// - `boot_entries`: GRUB's configuration is often only readable by root.
+/// - `profiles`: macOS only lists every user's configuration profiles to root.
+/// - `software_components`: other users' home directories, with their browser extensions
+///   and pipx venvs, are often unreadable.
+pub fn privileged_sections() -> Vec<&'static str> {
+    let mut privileged = vec![
+        sections::PROCESSES,
+        sections::SCHEDULED_TASKS,
+        sections::SOFTWARE_COMPONENTS,
+    ];
+    if cfg!(target_os = "windows") {
+        privileged.push(sections::HARDWARE);
+    }
+    if cfg!(target_os = "linux") {
+        privileged.push(sections::BOOT_ENTRIES);
+    }
+    if cfg!(target_os = "macos") {
+        privileged.push(sections::PROFILES);
+    }
+    privileged
+}
+
+/// Whether hsnap runs as root, or on Windows, elevated as an administrator.
+#[cfg(unix)]
+pub fn is_privileged() -> bool {
+    nix::unistd::geteuid().is_root()
+}
+
+#[cfg(windows)]
+pub fn is_privileged() -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    // SAFETY: the token is only used while open, and `elevation` is the size the
+    // TokenElevation class is written to.
+    unsafe {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut size = 0u32;
+        let queried = GetTokenInformation(
+            token,
+            TokenElevation,
+            (&mut elevation as *mut TOKEN_ELEVATION).cast(),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+        queried != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Assumed privileged where there's no telling, so no section is listed as limited.
+#[cfg(not(any(unix, windows)))]
+pub fn is_privileged() -> bool {
+    true
+}
@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sysinfo::{ProcessesToUpdate, System, Users};
+
+/// What `--top-processes` ranks processes by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum TopBy {
+    #[default]
+    Cpu,
+    Mem,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub exe: Option<String>,
+    pub user: Option<String>,
+    /// Percent of one CPU, so it can exceed 100 on a multi-core host.
+    pub cpu_usage: f32,
+    /// Resident memory, in bytes.
+    pub memory: u64,
+}
+
+/// The `top` processes using the most CPU or memory.
+pub fn collect(top: usize, by: TopBy) -> Vec<ProcessInfo> {
+    let mut sys = System::new();
+    // CPU usage is measured between two refreshes
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+    let users = Users::new_with_refreshed_list();
+
+    let processes = sys
+        .processes()
+        .values()
+        // Linux lists each thread as well
+        .filter(|process| process.thread_kind().is_none())
+        .map(|process| ProcessInfo {
+            pid: process.pid().as_u32(),
+            name: process.name().to_string_lossy().to_string(),
+            exe: process.exe().map(|exe| exe.to_string_lossy().to_string()),
+            user: process
+                .user_id()
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map(|user| user.name().to_string()),
+            cpu_usage: process.cpu_usage(),
+            memory: process.memory(),
+        })
+        .collect();
+    top_n(processes, top, by)
+}
+
+/// Keeps the `top` processes by `by`, heaviest first. Ties go to the lowest pid, so the
+/// same host gives the same list.
+fn top_n(mut processes: Vec<ProcessInfo>, top: usize, by: TopBy) -> Vec<ProcessInfo> {
+    processes.sort_by(|a, b| {
+        let heavier = match by {
+            TopBy::Cpu => b.cpu_usage.total_cmp(&a.cpu_usage),
+            TopBy::Mem => b.memory.cmp(&a.memory),
+        };
+        heavier.then(a.pid.cmp(&b.pid))
+    });
+    processes.truncate(top);
+    processes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, cpu_usage: f32, memory: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            exe: None,
+            user: None,
+            cpu_usage,
+            memory,
+        }
+    }
+
+    fn fake_processes() -> Vec<ProcessInfo> {
+        vec![
+            process(1, "systemd", 0.1, 12 << 20),
+            process(812, "postgres", 35.0, 900 << 20),
+            process(1204, "java", 180.5, 2 << 30),
+            process(1530, "nginx", 4.2, 30 << 20),
+            process(2048, "ffmpeg", 310.0, 250 << 20),
+            process(2049, "gzip", 35.0, 2 << 20),
+        ]
+    }
+
+    fn names(processes: &[ProcessInfo]) -> Vec<&str> {
+        processes
+            .iter()
+            .map(|process| process.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_top_processes_by_cpu() {
+        let top = top_n(fake_processes(), 4, TopBy::Cpu);
+        // postgres and gzip tie, and the lower pid goes first
+        assert_eq!(names(&top), ["ffmpeg", "java", "postgres", "gzip"]);
+    }
+
+    #[test]
+    fn keeps_top_processes_by_memory() {
+        let top = top_n(fake_processes(), 2, TopBy::Mem);
+        assert_eq!(names(&top), ["java", "postgres"]);
+    }
+
+    #[test]
+    fn keeps_every_process_when_there_are_fewer_than_top() {
+        assert_eq!(top_n(fake_processes(), 10, TopBy::Mem).len(), 6);
+        assert!(top_n(fake_processes(), 0, TopBy::Cpu).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::process::Command;
+
+/// The key of `profiles`' output listing the profiles installed for the whole computer.
+/// The others are user names, listing each user's profiles.
+const COMPUTER_LEVEL: &str = "_computerlevel";
+
+/// A configuration profile installed on a Mac, e.g. by an MDM server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// e.g. `com.example.wifi`.
+    pub identifier: Option<String>,
+    pub name: Option<String>,
+    pub organization: Option<String>,
+    pub uuid: Option<String>,
+    /// e.g. `Configuration`.
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// As `profiles` reports it, e.g. `2024-01-15 09:30:00 +0000`.
+    pub install_date: Option<String>,
+    /// e.g. `verified` for a signed profile or `unsigned`.
+    pub verification_state: Option<String>,
+    /// The user it is installed for, or `None` for the whole computer.
+    pub user: Option<String>,
+}
+
+/// The installed configuration profiles, from `profiles -P -o stdout-xml`. Listing every
+/// user's profiles needs root, so this fails when run as another user. Empty on platforms
+/// other than macOS.
+pub fn collect() -> Result<Vec<Profile>, String> {
+    if !cfg!(target_os = "macos") {
+        return Ok(Vec::new());
+    }
+    let output = Command::new("profiles")
+        .args(["-P", "-o", "stdout-xml"])
+        .output()
+        .map_err(|e| format!("Failed to run profiles: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("profiles failed with {}: {}", output.status, line.trim()),
+            None => format!("profiles failed with {}", output.status),
+        });
+    }
+    parse_profiles(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| "profiles printed an unexpected property list".to_string())
+}
+
+/// Parses the property list `profiles` prints, the profiles of the computer and of each
+/// user:
+///
+/// ```text
+/// <plist version="1.0">
+/// <dict>
+///     <key>_computerlevel</key>
+///     <array>
+///         <dict>
+///             <key>ProfileDisplayName</key>
+///             <string>Wi-Fi</string>
+///             <key>ProfileIdentifier</key>
+///             <string>com.example.wifi</string>
+///             ...
+/// ```
+///
+/// An empty output, as when no profile is installed, has none.
+fn parse_profiles(xml: &str) -> Option<Vec<Profile>> {
+    if xml.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    let Value::Object(levels) = parse_plist(xml)? else {
+        return None;
+    };
+    let mut profiles = Vec::new();
+    for (level, list) in &levels {
+        let user = (level != COMPUTER_LEVEL).then(|| level.clone());
+        for profile in list.as_array().into_iter().flatten() {
+            let string = |key: &str| profile.get(key).and_then(Value::as_str).map(str::to_string);
+            profiles.push(Profile {
+                identifier: string("ProfileIdentifier"),
+                name: string("ProfileDisplayName"),
+                organization: string("ProfileOrganization"),
+                uuid: string("ProfileUUID"),
+                kind: string("ProfileType"),
+                install_date: string("ProfileInstallDate"),
+                verification_state: string("ProfileVerificationState"),
+                user: user.clone(),
+            });
+        }
+    }
+    Some(profiles)
+}
+
+/// Parses an XML property list into the JSON value it maps to: dicts to objects, arrays
+/// to arrays, and strings, dates and base64 data to strings.
+fn parse_plist(xml: &str) -> Option<Value> {
+    let mut reader = PlistReader { rest: xml };
+    loop {
+        let tag = reader.next_tag()?;
+        if tag.name != "plist" && !tag.closing {
+            return reader.value(&tag);
+        }
+    }
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    /// e.g. `<true/>` or an empty `<array/>`.
+    empty: bool,
+}
+
+struct PlistReader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> PlistReader<'a> {
+    /// The next element tag, skipping text, the XML declaration, the doctype and comments.
+    fn next_tag(&mut self) -> Option<Tag<'a>> {
+        loop {
+            let start = self.rest.find('<')?;
+            self.rest = &self.rest[start + 1..];
+            if let Some(comment) = self.rest.strip_prefix("!--") {
+                self.rest = &comment[comment.find("-->")? + 3..];
+                continue;
+            }
+            let end = self.rest.find('>')?;
+            let inner = &self.rest[..end];
+            self.rest = &self.rest[end + 1..];
+            if inner.starts_with('?') || inner.starts_with('!') {
+                continue;
+            }
+            let closing = inner.starts_with('/');
+            let empty = inner.ends_with('/');
+            let name = inner
+                .trim_start_matches('/')
+                .trim_end_matches('/')
+                .split_whitespace()
+                .next()?;
+            return Some(Tag {
+                name,
+                closing,
+                empty,
+            });
+        }
+    }
+
+    /// The text up to the element's closing tag, with entities replaced.
+    fn text(&mut self, name: &str) -> Option<String> {
+        let close = format!("</{}>", name);
+        let end = self.rest.find(&close)?;
+        let text = unescape(&self.rest[..end]);
+        self.rest = &self.rest[end + close.len()..];
+        Some(text)
+    }
+
+    /// The value of the element `tag` opens.
+    fn value(&mut self, tag: &Tag) -> Option<Value> {
+        match tag.name {
+            "dict" => {
+                let mut dict = Map::new();
+                if tag.empty {
+                    return Some(Value::Object(dict));
+                }
+                loop {
+                    let key = self.next_tag()?;
+                    if key.closing {
+                        return Some(Value::Object(dict));
+                    }
+                    if key.name != "key" {
+                        return None;
+                    }
+                    let key = if key.empty {
+                        String::new()
+                    } else {
+                        self.text("key")?
+                    };
+                    let tag = self.next_tag()?;
+                    dict.insert(key, self.value(&tag)?);
+                }
+            }
+            "array" => {
+                let mut array = Vec::new();
+                if tag.empty {
+                    return Some(Value::Array(array));
+                }
+                loop {
+                    let tag = self.next_tag()?;
+                    if tag.closing {
+                        return Some(Value::Array(array));
+                    }
+                    array.push(self.value(&tag)?);
+                }
+            }
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ if tag.empty => Some(Value::String(String::new())),
+            "string" | "date" => self.text(tag.name).map(Value::String),
+            // Base64 may be wrapped over several lines
+            "data" => self
+                .text("data")
+                .map(|data| Value::String(data.split_whitespace().collect())),
+            "integer" => Some(Value::from(
+                self.text("integer")?.trim().parse::<i64>().ok()?,
+            )),
+            "real" => Some(Value::from(self.text("real")?.trim().parse::<f64>().ok()?)),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces the predefined XML entities and character references.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_profiles_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>_computerlevel</key>
+	<array>
+		<dict>
+			<key>ProfileDisplayName</key>
+			<string>Wi-Fi &amp; VPN</string>
+			<key>ProfileIdentifier</key>
+			<string>com.example.wifi</string>
+			<key>ProfileInstallDate</key>
+			<string>2024-01-15 09:30:00 +0000</string>
+			<key>ProfileItems</key>
+			<array>
+				<dict>
+					<key>PayloadContent</key>
+					<dict>
+						<key>AutoJoin</key>
+						<true/>
+						<key>Priority</key>
+						<integer>10</integer>
+					</dict>
+					<key>PayloadType</key>
+					<string>com.apple.wifi.managed</string>
+				</dict>
+			</array>
+			<key>ProfileOrganization</key>
+			<string>Example Corp</string>
+			<key>ProfileType</key>
+			<string>Configuration</string>
+			<key>ProfileUUID</key>
+			<string>6F1B2C3D-4E5F-4A6B-8C7D-9E0F1A2B3C4D</string>
+			<key>ProfileVerificationState</key>
+			<string>verified</string>
+		</dict>
+	</array>
+	<key>jdoe</key>
+	<array>
+		<dict>
+			<key>ProfileDisplayName</key>
+			<string>Mail</string>
+			<key>ProfileIdentifier</key>
+			<string>com.example.mail</string>
+			<key>ProfileVerificationState</key>
+			<string>unsigned</string>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+        assert_eq!(
+            parse_profiles(xml).unwrap(),
+            [
+                Profile {
+                    identifier: Some("com.example.wifi".to_string()),
+                    name: Some("Wi-Fi & VPN".to_string()),
+                    organization: Some("Example Corp".to_string()),
+                    uuid: Some("6F1B2C3D-4E5F-4A6B-8C7D-9E0F1A2B3C4D".to_string()),
+                    kind: Some("Configuration".to_string()),
+                    install_date: Some("2024-01-15 09:30:00 +0000".to_string()),
+                    verification_state: Some("verified".to_string()),
+                    user: None,
+                },
+                Profile {
+                    identifier: Some("com.example.mail".to_string()),
+                    name: Some("Mail".to_string()),
+                    organization: None,
+                    uuid: None,
+                    kind: None,
+                    install_date: None,
+                    verification_state: Some("unsigned".to_string()),
+                    user: Some("jdoe".to_string()),
+                },
+            ]
+        );
+        // No profiles installed
+        assert_eq!(parse_profiles("\n").unwrap(), []);
+        assert_eq!(parse_profiles("<html>"), None);
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const YUM_REPOS_DIR: &str = "/etc/yum.repos.d";
+const APT_SOURCES_LIST: &str = "/etc/apt/sources.list";
+const APT_SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+
+/// A configured package repository.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RepoInfo {
+    /// `yum` for yum/dnf repos, `apt` for apt sources.
+    pub kind: String,
+    /// The repo id for yum. For apt, the whole source, e.g. `deb http://deb.debian.org/debian bookworm main`.
+    pub id: String,
+    pub name: Option<String>,
+    /// The baseurl (or mirrorlist/metalink when there is none) for yum, the URI for apt.
+    pub base_url: Option<String>,
+    pub enabled: bool,
+}
+
+/// Reads every yum/dnf repo and apt source configured on the host.
+pub fn collect() -> Vec<RepoInfo> {
+    let mut repos = Vec::new();
+
+    for path in files_with_extension(Path::new(YUM_REPOS_DIR), "repo") {
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            repos.extend(parse_yum_repo(&contents));
+        }
+    }
+
+    if let Ok(contents) = std::fs::read_to_string(APT_SOURCES_LIST) {
+        repos.extend(parse_apt_list(&contents));
+    }
+    for path in files_with_extension(Path::new(APT_SOURCES_DIR), "list") {
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            repos.extend(parse_apt_list(&contents));
+        }
+    }
+    for path in files_with_extension(Path::new(APT_SOURCES_DIR), "sources") {
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            repos.extend(parse_apt_sources(&contents));
+        }
+    }
+
+    repos
+}
+
+/// Files in `dir` with the given extension, sorted so the output is stable.
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<std::path::PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Parses a yum/dnf `.repo` file: an INI file with a `[id]` section per repo.
+fn parse_yum_repo(contents: &str) -> Vec<RepoInfo> {
+    let mut repos: Vec<RepoInfo> = Vec::new();
+    let mut fallback_url: Option<String> = None;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            finish_yum_repo(&mut repos, fallback_url.take());
+            repos.push(RepoInfo {
+                kind: "yum".to_string(),
+                id: id.trim().to_string(),
+                name: None,
+                base_url: None,
+                // Repos are enabled unless they say otherwise
+                enabled: true,
+            });
+            continue;
+        }
+
+        let (Some(repo), Some((key, value))) = (repos.last_mut(), line.split_once('=')) else {
+            // Continuation lines of multi-valued keys (e.g. extra baseurls) are ignored
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "name" => repo.name = Some(value),
+            // baseurl may list several urls, the first is the primary
+            "baseurl" => repo.base_url = value.split_whitespace().next().map(str::to_string),
+            "mirrorlist" | "metalink" => {
+                fallback_url.get_or_insert(value);
+            }
+            "enabled" => repo.enabled = matches!(value.as_str(), "1" | "yes" | "true" | "True"),
+            _ => {}
+        }
+    }
+    finish_yum_repo(&mut repos, fallback_url);
+    repos
+}
+
+fn finish_yum_repo(repos: &mut [RepoInfo], fallback_url: Option<String>) {
+    if let Some(repo) = repos.last_mut() {
+        if repo.base_url.is_none() {
+            repo.base_url = fallback_url;
+        }
+    }
+}
+
+/// Parses the one-line format of `sources.list`: `deb [options] uri suite [components...]`.
+/// Commented out sources are reported as disabled.
+fn parse_apt_list(contents: &str) -> Vec<RepoInfo> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (enabled, line) = match line.strip_prefix('#') {
+                Some(commented) => (false, commented.trim()),
+                None => (true, line),
+            };
+
+            let mut fields = line.split_whitespace().peekable();
+            let kind = fields.next().filter(|t| *t == "deb" || *t == "deb-src")?;
+            // Skip `[arch=amd64 signed-by=...]` options, which may contain spaces
+            if fields.peek().is_some_and(|f| f.starts_with('[')) {
+                for field in fields.by_ref() {
+                    if field.ends_with(']') {
+                        break;
+                    }
+                }
+            }
+            let uri = fields.next()?;
+            let suite = fields.next()?;
+            let components: Vec<&str> = fields.collect();
+
+            Some(RepoInfo {
+                kind: "apt".to_string(),
+                id: std::iter::once(kind)
+                    .chain([uri, suite])
+                    .chain(components)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                name: None,
+                base_url: Some(uri.to_string()),
+                enabled,
+            })
+        })
+        .collect()
+}
+
+/// Parses the deb822 format of `.sources` files: blank line separated stanzas of
+/// `Types:`, `URIs:`, `Suites:`, `Components:` and optionally `Enabled:`.
+fn parse_apt_sources(contents: &str) -> Vec<RepoInfo> {
+    let mut repos = Vec::new();
+    for stanza in contents.split("\n\n") {
+        let field = |name: &str| {
+            stanza.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.trim()
+                    .eq_ignore_ascii_case(name)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        let (Some(types), Some(uris), Some(suites)) =
+            (field("Types"), field("URIs"), field("Suites"))
+        else {
+            continue;
+        };
+        let components = field("Components").unwrap_or_default();
+        let enabled = field("Enabled").is_none_or(|enabled| enabled != "no");
+
+        for kind in types.split_whitespace() {
+            for uri in uris.split_whitespace() {
+                for suite in suites.split_whitespace() {
+                    let id = [kind, uri, suite, components.as_str()]
+                        .iter()
+                        .filter(|part| !part.is_empty())
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    repos.push(RepoInfo {
+                        kind: "apt".to_string(),
+                        id,
+                        name: None,
+                        base_url: Some(uri.to_string()),
+                        enabled,
+                    });
+                }
+            }
+        }
+    }
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(kind: &str, id: &str, name: Option<&str>, base_url: &str, enabled: bool) -> RepoInfo {
+        RepoInfo {
+            kind: kind.to_string(),
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            base_url: Some(base_url.to_string()),
+            enabled,
+        }
+    }
+
+    #[test]
+    fn parses_yum_repo_file() {
+        let contents = "\
+[baseos]
+name=Rocky Linux $releasever - BaseOS
+mirrorlist=https://mirrors.rockylinux.org/mirrorlist?arch=$basearch&repo=BaseOS-$releasever
+#baseurl=http://dl.rockylinux.org/$contentdir/$releasever/BaseOS/$basearch/os/
+gpgcheck=1
+enabled=1
+
+[epel]
+name = Extra Packages for Enterprise Linux 9
+baseurl = https://dl.fedoraproject.org/pub/epel/9/Everything/$basearch/
+  https://mirror.example.com/epel/9/
+enabled = 0
+
+[local]
+baseurl=file:///srv/repo
+";
+        assert_eq!(
+            parse_yum_repo(contents),
+            [
+                repo(
+                    "yum",
+                    "baseos",
+                    Some("Rocky Linux $releasever - BaseOS"),
+                    "https://mirrors.rockylinux.org/mirrorlist?arch=$basearch&repo=BaseOS-$releasever",
+                    true
+                ),
+                repo(
+                    "yum",
+                    "epel",
+                    Some("Extra Packages for Enterprise Linux 9"),
+                    "https://dl.fedoraproject.org/pub/epel/9/Everything/$basearch/",
+                    false
+                ),
+                repo("yum", "local", None, "file:///srv/repo", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_apt_sources() {
+        let list = "\
+deb http://deb.debian.org/debian bookworm main contrib
+# deb-src http://deb.debian.org/debian bookworm main
+deb [arch=amd64 signed-by=/usr/share/keyrings/docker.gpg] https://download.docker.com/linux/debian bookworm stable
+";
+        assert_eq!(
+            parse_apt_list(list),
+            [
+                repo(
+                    "apt",
+                    "deb http://deb.debian.org/debian bookworm main contrib",
+                    None,
+                    "http://deb.debian.org/debian",
+                    true
+                ),
+                repo(
+                    "apt",
+                    "deb-src http://deb.debian.org/debian bookworm main",
+                    None,
+                    "http://deb.debian.org/debian",
+                    false
+                ),
+                repo(
+                    "apt",
+                    "deb https://download.docker.com/linux/debian bookworm stable",
+                    None,
+                    "https://download.docker.com/linux/debian",
+                    true
+                ),
+            ]
+        );
+
+        let sources = "\
+Types: deb
+URIs: http://deb.debian.org/debian
+Suites: bookworm bookworm-updates
+Components: main
+
+Types: deb
+URIs: http://security.debian.org/debian-security
+Suites: bookworm-security
+Components: main
+Enabled: no
+";
+        assert_eq!(
+            parse_apt_sources(sources),
+            [
+                repo(
+                    "apt",
+                    "deb http://deb.debian.org/debian bookworm main",
+                    None,
+                    "http://deb.debian.org/debian",
+                    true
+                ),
+                repo(
+                    "apt",
+                    "deb http://deb.debian.org/debian bookworm-updates main",
+                    None,
+                    "http://deb.debian.org/debian",
+                    true
+                ),
+                repo(
+                    "apt",
+                    "deb http://security.debian.org/debian-security bookworm-security main",
+                    None,
+                    "http://security.debian.org/debian-security",
+                    false
+                ),
+            ]
+        );
+    }
+}
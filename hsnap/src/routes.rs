@@ -0,0 +1,228 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::process::Command;
+
+/// The stub resolver systemd-resolved points `/etc/resolv.conf` at.
+const RESOLVED_STUB: &str = "127.0.0.53";
+/// The upstream servers systemd-resolved forwards to.
+const RESOLVED_UPSTREAM: &str = "/run/systemd/resolve/resolv.conf";
+
+/// Gateways of the IPv4 and IPv6 default routes.
+pub fn default_gateways() -> Vec<String> {
+    let gateways = if cfg!(target_os = "linux") {
+        let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+        let mut gateways = parse_proc_net_route(&read("/proc/net/route"));
+        gateways.extend(parse_ipv6_route(&read("/proc/net/ipv6_route")));
+        gateways
+    } else if cfg!(target_os = "windows") {
+        parse_route_print(&command_output("route", &["print"]))
+    } else if cfg!(target_os = "macos") {
+        parse_netstat_routes(&command_output("netstat", &["-rn"]))
+    } else {
+        Vec::new()
+    };
+    dedup(gateways)
+}
+
+/// Configured DNS resolvers, IPv4 and IPv6.
+pub fn dns_servers() -> Vec<String> {
+    let servers = if cfg!(target_os = "windows") {
+        windows_dns_servers()
+    } else {
+        let servers =
+            parse_resolv_conf(&std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default());
+        if servers.iter().all(|server| server == RESOLVED_STUB) {
+            match std::fs::read_to_string(RESOLVED_UPSTREAM) {
+                Ok(upstream) => parse_resolv_conf(&upstream),
+                Err(_) => servers,
+            }
+        } else {
+            servers
+        }
+    };
+    dedup(servers)
+}
+
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).to_string())
+        .unwrap_or_default()
+}
+
+fn dedup(values: Vec<String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    for value in values {
+        if !seen.contains(&value) {
+            seen.push(value);
+        }
+    }
+    seen
+}
+
+/// Parses `/proc/net/route`, where addresses are little-endian hex, e.g. `0102A8C0` is 192.168.2.1.
+fn parse_proc_net_route(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (destination, gateway, mask) = (fields.get(1)?, fields.get(2)?, fields.get(7)?);
+            if *destination != "00000000" || *mask != "00000000" {
+                return None;
+            }
+            let gateway = u32::from_str_radix(gateway, 16).ok().filter(|g| *g != 0)?;
+            Some(Ipv4Addr::from(gateway.to_le_bytes()).to_string())
+        })
+        .collect()
+}
+
+/// Parses `/proc/net/ipv6_route`, whose fields are destination, prefix length, source,
+/// source prefix length and next hop, with addresses as 32 hex digits.
+fn parse_ipv6_route(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (destination, prefix, next_hop) = (fields.first()?, fields.get(1)?, fields.get(4)?);
+            if u128::from_str_radix(destination, 16).ok()? != 0 || *prefix != "00" {
+                return None;
+            }
+            let next_hop = u128::from_str_radix(next_hop, 16)
+                .ok()
+                .filter(|n| *n != 0)?;
+            Some(Ipv6Addr::from(next_hop).to_string())
+        })
+        .collect()
+}
+
+fn parse_resolv_conf(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => fields.next().map(str::to_string),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses the default routes out of Windows' `route print`. IPv4 routes are listed as
+/// `0.0.0.0  0.0.0.0  <gateway>  <interface>  <metric>`, IPv6 ones as
+/// `<if>  <metric>  ::/0  <gateway>`, with a long gateway wrapped onto the next line.
+fn parse_route_print(contents: &str) -> Vec<String> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut gateways = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let gateway = match fields.as_slice() {
+            ["0.0.0.0", "0.0.0.0", gateway, ..] => Some(*gateway),
+            [_, _, "::/0", gateway, ..] => Some(*gateway),
+            [_, _, "::/0"] => lines.get(i + 1).map(|next| next.trim()),
+            _ => None,
+        };
+        if let Some(gateway) = gateway.filter(|g| g.parse::<std::net::IpAddr>().is_ok()) {
+            gateways.push(gateway.to_string());
+        }
+    }
+    gateways
+}
+
+/// Parses the `default` routes out of macOS' `netstat -rn`.
+fn parse_netstat_routes(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some("default"), Some(gateway)) => {
+                    // Link-local IPv6 gateways carry an interface scope, e.g. fe80::1%en0
+                    let address = gateway.split('%').next().unwrap_or(gateway);
+                    address
+                        .parse::<std::net::IpAddr>()
+                        .is_ok()
+                        .then(|| gateway.to_string())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads the static and DHCP assigned name servers of every interface from the registry.
+fn windows_dns_servers() -> Vec<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+
+        let mut servers = Vec::new();
+        for stack in ["Tcpip", "Tcpip6"] {
+            let path = format!(
+                "SYSTEM\\CurrentControlSet\\Services\\{}\\Parameters\\Interfaces",
+                stack
+            );
+            let interfaces = match RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(path) {
+                Ok(interfaces) => interfaces,
+                Err(_) => continue,
+            };
+            for name in interfaces.enum_keys().filter_map(|name| name.ok()) {
+                if let Ok(interface) = interfaces.open_subkey(&name) {
+                    for value in ["NameServer", "DhcpNameServer"] {
+                        let list: String = interface.get_value(value).unwrap_or_default();
+                        servers.extend(
+                            list.split([',', ' '])
+                                .filter(|server| !server.is_empty())
+                                .map(str::to_string),
+                        );
+                    }
+                }
+            }
+        }
+        servers
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_gateway_from_proc_net_route() {
+        let route = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0102A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0002A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+docker0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+";
+        assert_eq!(parse_proc_net_route(route), ["192.168.2.1"]);
+    }
+
+    #[test]
+    fn parses_default_gateway_from_ipv6_route() {
+        let route = "\
+20010db8000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001     eth0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000002 00000000 00450003     eth0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo
+";
+        assert_eq!(parse_ipv6_route(route), ["fe80::1"]);
+    }
+
+    #[test]
+    fn parses_nameservers_from_resolv_conf() {
+        let resolv_conf = "\
+# Generated by NetworkManager
+search example.com
+nameserver 10.0.0.2
+nameserver 2001:db8::53
+options edns0
+";
+        assert_eq!(parse_resolv_conf(resolv_conf), ["10.0.0.2", "2001:db8::53"]);
+    }
+}
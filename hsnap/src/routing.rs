@@ -0,0 +1,103 @@
+use crate::{sections, HostSnapshot, Metadata};
+use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// The sections `--route` can send to their own url: the lists, sent a record per line.
+pub const ROUTABLE: [&str; 8] = [
+    sections::SOFTWARE_COMPONENTS,
+    sections::PROCESSES,
+    sections::USERS,
+    sections::REPOSITORIES,
+    sections::SCHEDULED_TASKS,
+    sections::TRUSTED_CAS,
+    sections::BOOT_ENTRIES,
+    sections::PROFILES,
+];
+
+/// A line of a routed section: one of its records, with the snapshot it belongs to.
+#[derive(Serialize)]
+struct RoutedRecord<'a, T> {
+    id: &'a str,
+    timestamp: DateTime<Utc>,
+    record: T,
+}
+
+/// Takes `section` out of the snapshot, listing it in `routed_sections`, and renders it
+/// as JSON Lines. `None` when the section wasn't collected.
+pub fn take_section(snapshot: &mut HostSnapshot, section: &str) -> Option<Vec<u8>> {
+    let metadata = &snapshot.metadata;
+    let lines = match section {
+        sections::SOFTWARE_COMPONENTS => Some(json_lines(
+            metadata,
+            std::mem::take(&mut snapshot.software_components),
+        )),
+        sections::PROCESSES => snapshot
+            .processes
+            .take()
+            .map(|processes| json_lines(metadata, processes)),
+        sections::USERS => snapshot
+            .users
+            .take()
+            .map(|users| json_lines(metadata, users)),
+        sections::REPOSITORIES => snapshot
+            .repositories
+            .take()
+            .map(|repositories| json_lines(metadata, repositories)),
+        sections::SCHEDULED_TASKS => snapshot
+            .scheduled_tasks
+            .take()
+            .map(|scheduled_tasks| json_lines(metadata, scheduled_tasks)),
+        sections::TRUSTED_CAS => snapshot
+            .trusted_cas
+            .take()
+            .map(|trusted_cas| json_lines(metadata, trusted_cas)),
+        sections::BOOT_ENTRIES => snapshot
+            .boot_entries
+            .take()
+            .map(|boot_entries| json_lines(metadata, boot_entries)),
+        sections::PROFILES => snapshot
+            .profiles
+            .take()
+            .map(|profiles| json_lines(metadata, profiles)),
+        _ => None,
+    };
+    if lines.is_some() {
+        snapshot.routed_sections.push(section.to_string());
+    }
+    lines
+}
+
+fn json_lines<T: Serialize>(metadata: &Metadata, records: Vec<T>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for record in records {
+        let line = RoutedRecord {
+            id: &metadata.id,
+            timestamp: metadata.timestamp,
+            record,
+        };
+        serde_json::to_writer(&mut out, &line).expect("Failed to serialize snapshot");
+        out.push(b'\n');
+    }
+    out
+}
+
+/// POSTs a routed section, with an `X-Content-SHA256` header of the body as for snapshots.
+pub async fn post(client: &Client, url: &str, body: Vec<u8>) -> Result<(), String> {
+    let digest = hex::encode(Sha256::digest(&body));
+    let res = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/x-ndjson")
+        .header("X-Content-SHA256", digest)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if res.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Status {}", res.status()))
+    }
+}
@@ -0,0 +1,262 @@
+use hsnap_purl_plugin::extract::parse_csv_row;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+const SYSTEM_CRONTAB: &str = "/etc/crontab";
+const CRON_D: &str = "/etc/cron.d";
+/// Where cron keeps users' crontabs, on Debian and on RHEL.
+const USER_CRONTAB_DIRS: [&str; 2] = ["/var/spool/cron/crontabs", "/var/spool/cron"];
+
+/// A cron job or Task Scheduler task.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledTask {
+    /// `cron` or `schtasks`.
+    pub kind: String,
+    /// The task's path in the Task Scheduler. Cron jobs have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The crontab the job is in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Who it runs as.
+    pub user: Option<String>,
+    /// The cron schedule, e.g. `*/5 * * * *` or `@reboot`, or the Task Scheduler's
+    /// schedule type, e.g. `Daily`.
+    pub schedule: String,
+    pub command: String,
+}
+
+/// Reads the system crontabs and each of `users`' crontabs on Unix, or the Task
+/// Scheduler's tasks on Windows. `no_shell` reads users' crontabs from cron's spool
+/// directory instead of running `crontab -l`.
+pub fn collect(users: &[String], no_shell: bool) -> Vec<ScheduledTask> {
+    if cfg!(target_os = "windows") {
+        return Command::new("schtasks")
+            .args(["/query", "/fo", "CSV", "/v"])
+            .output()
+            .map(|out| parse_schtasks(&String::from_utf8_lossy(&out.stdout)))
+            .unwrap_or_default();
+    }
+
+    let mut tasks = Vec::new();
+    let mut system_crontabs = vec![Path::new(SYSTEM_CRONTAB).to_path_buf()];
+    let mut cron_d: Vec<_> = std::fs::read_dir(CRON_D)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                // cron ignores editor backups and package manager leftovers such as .dpkg-old
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| !name.to_string_lossy().contains(['.', '~']))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    cron_d.sort();
+    system_crontabs.extend(cron_d);
+    for path in system_crontabs {
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            let source = path.to_string_lossy().to_string();
+            tasks.extend(parse_crontab(&contents, &source, None));
+        }
+    }
+
+    for user in users {
+        if no_shell {
+            for dir in USER_CRONTAB_DIRS {
+                let path = Path::new(dir).join(user);
+                if let Ok(contents) = std::fs::read_to_string(&path) {
+                    let source = path.to_string_lossy().to_string();
+                    tasks.extend(parse_crontab(&contents, &source, Some(user)));
+                    break;
+                }
+            }
+        } else {
+            // Exits non-zero when the user has no crontab, or we may not read it
+            let output = Command::new("crontab").args(["-l", "-u", user]).output();
+            if let Some(output) = output.ok().filter(|output| output.status.success()) {
+                let contents = String::from_utf8_lossy(&output.stdout);
+                tasks.extend(parse_crontab(&contents, "crontab -l", Some(user)));
+            }
+        }
+    }
+    tasks
+}
+
+/// Parses a crontab. A user's crontab (`user` is given) has five schedule fields and
+/// then the command; system crontabs have the user to run as in between. Schedules
+/// may also be one of the `@reboot`, `@daily`, ... shorthands.
+fn parse_crontab(contents: &str, source: &str, user: Option<&str>) -> Vec<ScheduledTask> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let mut fields = Fields(line);
+            let schedule = if line.starts_with('@') {
+                fields.next()?.to_string()
+            } else {
+                let schedule: Vec<&str> = (0..5).map_while(|_| fields.next()).collect();
+                // Anything else, such as `SHELL=/bin/sh` or `MAILTO=root`, sets a variable
+                if schedule.len() < 5 || schedule.iter().any(|field| field.contains('=')) {
+                    return None;
+                }
+                schedule.join(" ")
+            };
+            let user = match user {
+                Some(user) => user.to_string(),
+                None => fields.next()?.to_string(),
+            };
+            let command = fields.0.trim();
+            if command.is_empty() {
+                return None;
+            }
+            Some(ScheduledTask {
+                kind: "cron".to_string(),
+                name: None,
+                source: Some(source.to_string()),
+                user: Some(user),
+                schedule,
+                command: command.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Splits whitespace separated fields off the front of a line, leaving the rest in `.0`.
+struct Fields<'a>(&'a str);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.0.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.0 = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+/// Parses the verbose CSV output of `schtasks /query /fo CSV /v`. The header row is
+/// repeated before each folder's tasks, and a task with several triggers has a row for
+/// each, so rows are keyed by the header and tasks are deduplicated. Tasks are only
+/// recognized with English column names.
+fn parse_schtasks(output: &str) -> Vec<ScheduledTask> {
+    let mut tasks: Vec<ScheduledTask> = Vec::new();
+    let mut header: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let row = parse_csv_row(line);
+        if row.first().is_some_and(|field| field == "HostName") {
+            header = row;
+            continue;
+        }
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|column| column == name)
+                .and_then(|i| row.get(i))
+                .map(|value| value.trim().to_string())
+        };
+        let (Some(name), Some(command)) = (column("TaskName"), column("Task To Run")) else {
+            continue;
+        };
+        let task = ScheduledTask {
+            kind: "schtasks".to_string(),
+            name: Some(name),
+            source: None,
+            user: column("Run As User").filter(|user| !user.is_empty()),
+            schedule: column("Schedule Type").unwrap_or_default(),
+            command,
+        };
+        if !tasks.contains(&task) {
+            tasks.push(task);
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(user: &str, schedule: &str, command: &str) -> ScheduledTask {
+        ScheduledTask {
+            kind: "cron".to_string(),
+            name: None,
+            source: Some("/etc/crontab".to_string()),
+            user: Some(user.to_string()),
+            schedule: schedule.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_system_crontab() {
+        let crontab = "\
+SHELL=/bin/sh
+PATH=/usr/local/sbin:/usr/local/bin:/sbin:/bin:/usr/sbin:/usr/bin
+
+# m h dom mon dow user	command
+17 *	* * *	root    cd / && run-parts --report /etc/cron.hourly
+*/5 * * * * www-data /usr/bin/php /var/www/cron.php  >/dev/null 2>&1
+@reboot root /usr/local/bin/start-agent --quiet
+";
+        assert_eq!(
+            parse_crontab(crontab, "/etc/crontab", None),
+            vec![
+                task(
+                    "root",
+                    "17 * * * *",
+                    "cd / && run-parts --report /etc/cron.hourly"
+                ),
+                task(
+                    "www-data",
+                    "*/5 * * * *",
+                    "/usr/bin/php /var/www/cron.php  >/dev/null 2>&1"
+                ),
+                task("root", "@reboot", "/usr/local/bin/start-agent --quiet"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_user_crontab() {
+        let tasks = parse_crontab(
+            "MAILTO=alice@example.com\n0 3 * * 1 /home/alice/backup.sh\n",
+            "crontab -l",
+            Some("alice"),
+        );
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].user.as_deref(), Some("alice"));
+        assert_eq!(tasks[0].schedule, "0 3 * * 1");
+        assert_eq!(tasks[0].command, "/home/alice/backup.sh");
+    }
+    #[test]
+    fn parses_schtasks_csv() {
+        let header = r#""HostName","TaskName","Next Run Time","Status","Task To Run","Run As User","Schedule Type""#;
+        let output = format!(
+            "{}\n{}\n{}\n\n{}\n{}\n",
+            header,
+            r#""HOST","\Backup","N/A","Ready","C:\Tools\backup.exe /full","SYSTEM","Daily""#,
+            // A second trigger of the same task
+            r#""HOST","\Backup","N/A","Ready","C:\Tools\backup.exe /full","SYSTEM","Daily""#,
+            header,
+            r#""HOST","\Microsoft\Windows\Defrag\ScheduledDefrag","N/A","Ready","%windir%\system32\defrag.exe -c","","Weekly""#,
+        );
+        let tasks = parse_schtasks(&output);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].name.as_deref(), Some("\\Backup"));
+        assert_eq!(tasks[0].user.as_deref(), Some("SYSTEM"));
+        assert_eq!(tasks[0].schedule, "Daily");
+        assert_eq!(tasks[0].command, "C:\\Tools\\backup.exe /full");
+        assert_eq!(tasks[1].user, None);
+        assert_eq!(tasks[1].command, "%windir%\\system32\\defrag.exe -c");
+    }
+}
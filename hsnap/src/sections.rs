@@ -0,0 +1,248 @@
+use crate::{delta, HardwareInfo, HostSnapshot, NetworkInfo, StorageInfo, UserInfo};
+use chrono::DateTime;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+pub const HARDWARE: &str = "hardware";
+pub const OPERATING_SYSTEM: &str = "operating_system";
+pub const NETWORK: &str = "network";
+pub const STORAGE: &str = "storage";
+pub const USERS: &str = "users";
+pub const REPOSITORIES: &str = "repositories";
+pub const SCHEDULED_TASKS: &str = "scheduled_tasks";
+pub const PROCESSES: &str = "processes";
+pub const ENVIRONMENT_VARIABLES: &str = "environment_variables";
+pub const TRUSTED_CAS: &str = "trusted_cas";
+pub const BOOT_ENTRIES: &str = "boot_entries";
+pub const PROFILES: &str = "profiles";
+pub const SOFTWARE_COMPONENTS: &str = "software_components";
+
+fn digest<T: Serialize>(section: &T) -> String {
+    let json = serde_json::to_vec(section).expect("Failed to serialize snapshot");
+    hex::encode(Sha256::digest(json))
+}
+
+/// Zeroes the readings that differ on every run: CPU usage and frequency, memory in use,
+/// free and available, swap in use, and temperatures.
+fn clear_hardware_readings(hardware: &mut HardwareInfo) {
+    for cpu in &mut hardware.cpu_info {
+        cpu.usage = 0.0;
+        cpu.frequency = 0;
+    }
+    hardware.memory.used_memory = 0;
+    hardware.memory.available_memory = 0;
+    hardware.memory.free_memory = 0;
+    hardware.memory.used_swap = 0;
+    for device in &mut hardware.memory.swap_devices {
+        device.used = 0;
+    }
+    for node in &mut hardware.numa_nodes {
+        node.free_memory = 0;
+    }
+    for component in &mut hardware.components {
+        component.temperature = None;
+    }
+}
+
+/// Zeroes free disk space and inodes, and the usage worked out from them.
+fn clear_storage_readings(storage: &mut StorageInfo) {
+    for disk in &mut storage.disks {
+        disk.available_space = 0;
+        disk.used_percent = 0.0;
+        disk.low_space = false;
+        disk.available_inodes = None;
+    }
+}
+
+/// Interfaces and their addresses come back in no particular order.
+fn sort_network(network: &mut NetworkInfo) {
+    network.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    for interface in &mut network.interfaces {
+        interface.ips.sort();
+    }
+}
+
+/// As do users and their groups.
+fn sort_users(users: &mut [UserInfo]) {
+    users.sort_by(|a, b| a.name.cmp(&b.name));
+    for user in users {
+        user.groups.sort();
+    }
+}
+
+/// For `--deterministic`: clears the readings that differ on every run and puts the lists
+/// that come back in no particular order in order, so an unchanged host gives the same
+/// snapshot each time.
+pub fn make_deterministic(snapshot: &mut HostSnapshot) {
+    snapshot.metadata.capture_duration_ms = 0;
+    if let Some(hardware) = &mut snapshot.hardware {
+        clear_hardware_readings(hardware);
+    }
+    if let Some(network) = &mut snapshot.network {
+        sort_network(network);
+    }
+    if let Some(storage) = &mut snapshot.storage {
+        clear_storage_readings(storage);
+        storage
+            .disks
+            .sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    }
+    if let Some(users) = &mut snapshot.users {
+        sort_users(users);
+    }
+}
+
+/// Hashes the whole snapshot, for `--state-file` to tell whether it changed since it was
+/// last sent. Like `--deterministic`, it leaves out the readings that differ on every run,
+/// along with when the snapshot was taken and how long that took.
+pub fn content_digest(snapshot: &HostSnapshot) -> String {
+    let mut snapshot = snapshot.clone();
+    snapshot.metadata.timestamp = DateTime::UNIX_EPOCH;
+    snapshot.metadata.clock_time = None;
+    snapshot.digest = None;
+    make_deterministic(&mut snapshot);
+    digest(&snapshot)
+}
+
+/// Hashes each top-level section of a snapshot for `--changed-sections`.
+///
+/// Readings that differ on every run (CPU usage and frequency, memory in use, free and
+/// available, swap in use, temperatures, free disk space and inodes) are left out, so a section only counts as
+/// changed when what it describes changes.
+pub fn digests(snapshot: &HostSnapshot) -> HashMap<String, String> {
+    let mut digests = HashMap::new();
+
+    if let Some(hardware) = &snapshot.hardware {
+        let mut hardware = hardware.clone();
+        clear_hardware_readings(&mut hardware);
+        digests.insert(HARDWARE.to_string(), digest(&hardware));
+    }
+
+    if let Some(operating_system) = &snapshot.operating_system {
+        digests.insert(OPERATING_SYSTEM.to_string(), digest(operating_system));
+    }
+
+    if let Some(network) = &snapshot.network {
+        let mut network = network.clone();
+        sort_network(&mut network);
+        digests.insert(NETWORK.to_string(), digest(&network));
+    }
+
+    if let Some(storage) = &snapshot.storage {
+        let mut storage = storage.clone();
+        clear_storage_readings(&mut storage);
+        digests.insert(STORAGE.to_string(), digest(&storage));
+    }
+
+    if let Some(users) = &snapshot.users {
+        let mut users = users.clone();
+        sort_users(&mut users);
+        digests.insert(USERS.to_string(), digest(&users));
+    }
+
+    if let Some(repositories) = &snapshot.repositories {
+        digests.insert(REPOSITORIES.to_string(), digest(repositories));
+    }
+
+    if let Some(scheduled_tasks) = &snapshot.scheduled_tasks {
+        digests.insert(SCHEDULED_TASKS.to_string(), digest(scheduled_tasks));
+    }
+
+    if let Some(environment_variables) = &snapshot.environment_variables {
+        digests.insert(
+            ENVIRONMENT_VARIABLES.to_string(),
+            digest(environment_variables),
+        );
+    }
+
+    if let Some(trusted_cas) = &snapshot.trusted_cas {
+        digests.insert(TRUSTED_CAS.to_string(), digest(trusted_cas));
+    }
+
+    if let Some(boot_entries) = &snapshot.boot_entries {
+        digests.insert(BOOT_ENTRIES.to_string(), digest(boot_entries));
+    }
+
+    if let Some(profiles) = &snapshot.profiles {
+        digests.insert(PROFILES.to_string(), digest(profiles));
+    }
+
+    // Processes aren't hashed: they change on every run, so they are always sent
+
+    // Routed components aren't in the snapshot to compare
+    if !snapshot
+        .routed_sections
+        .iter()
+        .any(|name| name == SOFTWARE_COMPONENTS)
+    {
+        digests.insert(
+            SOFTWARE_COMPONENTS.to_string(),
+            delta::component_set_digest(&snapshot.software_components),
+        );
+    }
+
+    digests
+}
+
+/// Drops every section whose digest matches `previous`, listing it in `omitted_sections`.
+pub fn omit_unchanged(snapshot: &mut HostSnapshot, previous: &HashMap<String, String>) {
+    let current = digests(snapshot);
+    let unchanged = |name: &str| {
+        current
+            .get(name)
+            .is_some_and(|digest| previous.get(name) == Some(digest))
+    };
+
+    let mut omitted = Vec::new();
+    if unchanged(HARDWARE) {
+        snapshot.hardware = None;
+        omitted.push(HARDWARE);
+    }
+    if unchanged(OPERATING_SYSTEM) {
+        snapshot.operating_system = None;
+        omitted.push(OPERATING_SYSTEM);
+    }
+    if unchanged(NETWORK) {
+        snapshot.network = None;
+        omitted.push(NETWORK);
+    }
+    if unchanged(STORAGE) {
+        snapshot.storage = None;
+        omitted.push(STORAGE);
+    }
+    if unchanged(USERS) {
+        snapshot.users = None;
+        omitted.push(USERS);
+    }
+    if unchanged(REPOSITORIES) {
+        snapshot.repositories = None;
+        omitted.push(REPOSITORIES);
+    }
+    if unchanged(SCHEDULED_TASKS) {
+        snapshot.scheduled_tasks = None;
+        omitted.push(SCHEDULED_TASKS);
+    }
+    if unchanged(ENVIRONMENT_VARIABLES) {
+        snapshot.environment_variables = None;
+        omitted.push(ENVIRONMENT_VARIABLES);
+    }
+    if unchanged(TRUSTED_CAS) {
+        snapshot.trusted_cas = None;
+        omitted.push(TRUSTED_CAS);
+    }
+    if unchanged(BOOT_ENTRIES) {
+        snapshot.boot_entries = None;
+        omitted.push(BOOT_ENTRIES);
+    }
+    if unchanged(PROFILES) {
+        snapshot.profiles = None;
+        omitted.push(PROFILES);
+    }
+    if unchanged(SOFTWARE_COMPONENTS) {
+        snapshot.software_components.clear();
+        omitted.push(SOFTWARE_COMPONENTS);
+    }
+
+    snapshot.omitted_sections = omitted.into_iter().map(str::to_string).collect();
+}
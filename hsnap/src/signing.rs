@@ -0,0 +1,277 @@
+use base64::Engine;
+use clap::ValueEnum;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, UnparsedPublicKey};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::der::Document;
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey, ObjectIdentifier, SubjectPublicKeyInfoRef};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// id-ecPublicKey, the algorithm of an EC public key, and prime256v1, its P-256 curve.
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+/// The DER of a P-256 SubjectPublicKeyInfo up to the key itself, a 65 byte uncompressed
+/// point.
+const P256_PUBLIC_KEY_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// The kind of key `--signing-key` and `--signing-key-file` hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum SigningAlgorithm {
+    /// An RSA key as a PKCS#1 PEM, signing RS256.
+    #[default]
+    Rsa,
+    /// An EC key on the P-256 curve as a PKCS#8 PEM, signing ES256.
+    EcdsaP256,
+}
+
+/// How an ECDSA signature's r and s are written, recorded as `signature_format` in the
+/// signed envelope.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EcdsaSignatureFormat {
+    /// An ASN.1 DER sequence of the two integers, as OpenSSL writes them.
+    #[default]
+    Der,
+    /// r and s as 32 byte big-endian integers, one after the other, as JOSE writes them.
+    Raw,
+}
+
+/// How the signature bytes are written into `SignedSnapshot.signature`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+impl SignatureEncoding {
+    pub fn encode(&self, signature: &[u8]) -> String {
+        match self {
+            SignatureEncoding::Hex => hex::encode(signature),
+            SignatureEncoding::Base64 => {
+                base64::engine::general_purpose::STANDARD.encode(signature)
+            }
+        }
+    }
+
+    pub fn decode(&self, signature: &str) -> Result<Vec<u8>, String> {
+        match self {
+            SignatureEncoding::Hex => hex::decode(signature).map_err(|e| e.to_string()),
+            SignatureEncoding::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Something snapshots can be signed with: an RSA or a P-256 key.
+pub trait Signer {
+    /// The JOSE name of the signature algorithm.
+    fn algorithm(&self) -> &'static str;
+
+    /// The curve of an elliptic curve key.
+    fn curve(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// How an ECDSA signature is written.
+    fn signature_format(&self) -> Option<EcdsaSignatureFormat> {
+        None
+    }
+
+    /// Hex SHA256 fingerprint of the DER-encoded public key (SubjectPublicKeyInfo).
+    fn key_id(&self) -> String;
+
+    /// Signs `bytes`, hashing them as the algorithm requires.
+    fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// An RSA private key used to sign snapshots.
+pub struct SigningKey {
+    private_key: RsaPrivateKey,
+}
+
+impl SigningKey {
+    pub fn from_pem(private_key_pem: &str) -> Result<SigningKey, String> {
+        RsaPrivateKey::from_pkcs1_pem(private_key_pem)
+            .map(|private_key| SigningKey { private_key })
+            .map_err(|e| format!("Failed to parse private key: {}", e))
+    }
+}
+
+impl Signer for SigningKey {
+    /// RSA PKCS#1 v1.5 over SHA256.
+    fn algorithm(&self) -> &'static str {
+        "RS256"
+    }
+
+    fn key_id(&self) -> String {
+        let public_key_der = self
+            .private_key
+            .to_public_key()
+            .to_public_key_der()
+            .expect("Failed to encode public key");
+        hex::encode(Sha256::digest(public_key_der.as_bytes()))
+    }
+
+    fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let digest = Sha256::digest(bytes);
+        self.private_key
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &digest)
+            .map_err(|e| format!("Unable to sign snapshot with private key: {}", e))
+    }
+}
+
+/// An EC private key on the P-256 curve used to sign snapshots.
+pub struct EcdsaSigningKey {
+    key_pair: EcdsaKeyPair,
+    format: EcdsaSignatureFormat,
+    rng: SystemRandom,
+}
+
+impl EcdsaSigningKey {
+    /// Reads a `PRIVATE KEY` (PKCS#8) PEM, such as `openssl genpkey -algorithm EC -pkeyopt
+    /// ec_paramgen_curve:P-256` writes.
+    pub fn from_pem(
+        private_key_pem: &str,
+        format: EcdsaSignatureFormat,
+    ) -> Result<EcdsaSigningKey, String> {
+        let (label, der) = Document::from_pem(private_key_pem)
+            .map_err(|e| format!("Failed to parse private key: {}", e))?;
+        if label != "PRIVATE KEY" {
+            return Err(format!(
+                "Failed to parse private key: expected a PKCS#8 PRIVATE KEY, got {}",
+                label
+            ));
+        }
+        let algorithm = match format {
+            EcdsaSignatureFormat::Der => &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            EcdsaSignatureFormat::Raw => &ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+        };
+        let rng = SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(algorithm, der.as_bytes(), &rng)
+            .map_err(|e| format!("Failed to parse private key: {}", e))?;
+        Ok(EcdsaSigningKey {
+            key_pair,
+            format,
+            rng,
+        })
+    }
+}
+
+impl Signer for EcdsaSigningKey {
+    /// ECDSA on P-256 over SHA256.
+    fn algorithm(&self) -> &'static str {
+        "ES256"
+    }
+
+    fn curve(&self) -> Option<&'static str> {
+        Some("P-256")
+    }
+
+    fn signature_format(&self) -> Option<EcdsaSignatureFormat> {
+        Some(self.format)
+    }
+
+    fn key_id(&self) -> String {
+        let mut public_key_der = P256_PUBLIC_KEY_PREFIX.to_vec();
+        public_key_der.extend_from_slice(self.key_pair.public_key().as_ref());
+        hex::encode(Sha256::digest(public_key_der))
+    }
+
+    fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        self.key_pair
+            .sign(&self.rng, bytes)
+            .map(|signature| signature.as_ref().to_vec())
+            .map_err(|e| format!("Unable to sign snapshot with private key: {}", e))
+    }
+}
+
+/// A public key that snapshots' signatures are checked against.
+pub enum VerifyingKey {
+    Rsa(RsaPublicKey),
+    /// The uncompressed point of a P-256 key.
+    EcdsaP256(Vec<u8>),
+}
+
+impl VerifyingKey {
+    /// Reads a `PUBLIC KEY` (SubjectPublicKeyInfo) PEM of an RSA or P-256 key, or an
+    /// `RSA PUBLIC KEY` (PKCS#1) PEM.
+    pub fn from_pem(public_key_pem: &str) -> Result<VerifyingKey, String> {
+        if let Ok(public_key) = RsaPublicKey::from_public_key_pem(public_key_pem)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(public_key_pem))
+        {
+            return Ok(VerifyingKey::Rsa(public_key));
+        }
+        let (_, der) = Document::from_pem(public_key_pem)
+            .map_err(|e| format!("Failed to parse public key: {}", e))?;
+        let info = SubjectPublicKeyInfoRef::try_from(der.as_bytes())
+            .map_err(|e| format!("Failed to parse public key: {}", e))?;
+        let curve = info
+            .algorithm
+            .parameters_oid()
+            .map_err(|e| format!("Failed to parse public key: {}", e))?;
+        if info.algorithm.oid != EC_PUBLIC_KEY || curve != P256 {
+            return Err("Failed to parse public key: not an RSA or P-256 key".to_string());
+        }
+        let point = info
+            .subject_public_key
+            .as_bytes()
+            .ok_or("Failed to parse public key: bad EC point")?;
+        Ok(VerifyingKey::EcdsaP256(point.to_vec()))
+    }
+
+    /// The JOSE name of the algorithm this key's signatures are made with.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            VerifyingKey::Rsa(_) => "RS256",
+            VerifyingKey::EcdsaP256(_) => "ES256",
+        }
+    }
+
+    /// The same fingerprint `Signer::key_id` gives the private key.
+    pub fn key_id(&self) -> String {
+        let public_key_der = match self {
+            VerifyingKey::Rsa(public_key) => public_key
+                .to_public_key_der()
+                .expect("Failed to encode public key")
+                .into_vec(),
+            VerifyingKey::EcdsaP256(point) => [&P256_PUBLIC_KEY_PREFIX[..], point].concat(),
+        };
+        hex::encode(Sha256::digest(public_key_der))
+    }
+
+    /// Whether `signature` is a signature of `bytes` by this key, with `format` saying
+    /// how an ECDSA signature is written.
+    pub fn verify(
+        &self,
+        bytes: &[u8],
+        signature: &[u8],
+        format: Option<EcdsaSignatureFormat>,
+    ) -> bool {
+        match self {
+            VerifyingKey::Rsa(public_key) => {
+                let digest = Sha256::digest(bytes);
+                public_key
+                    .verify(Pkcs1v15Sign::new::<Sha256>(), &digest, signature)
+                    .is_ok()
+            }
+            VerifyingKey::EcdsaP256(point) => {
+                let algorithm = match format {
+                    Some(EcdsaSignatureFormat::Der) => &ring::signature::ECDSA_P256_SHA256_ASN1,
+                    Some(EcdsaSignatureFormat::Raw) => &ring::signature::ECDSA_P256_SHA256_FIXED,
+                    None => return false,
+                };
+                UnparsedPublicKey::new(algorithm, point)
+                    .verify(bytes, signature)
+                    .is_ok()
+            }
+        }
+    }
+}
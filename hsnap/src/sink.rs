@@ -0,0 +1,215 @@
+use crate::components_only::ComponentList;
+use crate::s3::{self, S3Config, S3Location};
+use crate::spool::SpooledSnapshot;
+use crate::{
+    EncryptedSnapshot, HostSnapshot, Metadata, MultiSignedSnapshot, SignedSnapshot,
+    VerbatimSignedSnapshot,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::{CONTENT_TYPE, DATE};
+use reqwest::{Client, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// How far a receiver's clock may be from the host's before it is warned about.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+/// What gets delivered: the snapshot itself, the snapshot wrapped with its signature (or
+/// signatures), possibly as the verbatim signed bytes, or any of those encrypted. A spooled payload is any of those read back from
+/// `--spool-dir`. With `--components-only-json`, it is only the software components.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Payload {
+    Snapshot(HostSnapshot),
+    Signed(SignedSnapshot),
+    MultiSigned(MultiSignedSnapshot),
+    Verbatim(VerbatimSignedSnapshot),
+    Encrypted(EncryptedSnapshot),
+    Spooled(SpooledSnapshot),
+    Components(ComponentList),
+}
+
+impl Payload {
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Payload::Snapshot(snapshot) => &snapshot.metadata,
+            Payload::Signed(signed) => &signed.signed.snapshot.metadata,
+            Payload::MultiSigned(signed) => &signed.signed.snapshot.metadata,
+            Payload::Verbatim(signed) => &signed.metadata,
+            Payload::Encrypted(encrypted) => &encrypted.metadata,
+            Payload::Spooled(spooled) => &spooled.metadata,
+            Payload::Components(components) => &components.metadata,
+        }
+    }
+}
+
+/// A destination a snapshot can be delivered to. Implement this to add new outputs.
+#[async_trait]
+pub trait SnapshotSink: Send + Sync {
+    /// Where this sink delivers to, used in messages and as its key in `--state-file`.
+    fn target(&self) -> String;
+
+    async fn deliver(&self, payload: &Payload) -> Result<(), String>;
+}
+
+/// Pretty prints the snapshot to stdout.
+pub struct StdoutSink;
+
+#[async_trait]
+impl SnapshotSink for StdoutSink {
+    fn target(&self) -> String {
+        "stdout".to_string()
+    }
+
+    async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+        // Serialize straight into stdout rather than building the whole string first
+        let mut out = BufWriter::new(std::io::stdout().lock());
+        serde_json::to_writer_pretty(&mut out, payload).map_err(|e| e.to_string())?;
+        writeln!(out)
+            .and_then(|_| out.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Writes the pretty printed snapshot to a file.
+pub struct FileSink {
+    pub path: PathBuf,
+}
+
+#[async_trait]
+impl SnapshotSink for FileSink {
+    fn target(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+        let file = File::create(&self.path).map_err(|e| e.to_string())?;
+        let mut out = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut out, payload).map_err(|e| e.to_string())?;
+        out.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// POSTs the snapshot as JSON, with an `X-Content-SHA256` header of the body.
+pub struct HttpSink {
+    pub client: Client,
+    pub url: String,
+}
+
+#[async_trait]
+impl SnapshotSink for HttpSink {
+    fn target(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+        // Buffered rather than streamed, as the digest header has to be sent before the body
+        let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+        let digest = hex::encode(Sha256::digest(&body));
+        let res = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header("X-Content-SHA256", digest)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        warn_clock_skew(&self.url, &res);
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Status {}", res.status()))
+        }
+    }
+}
+
+/// Warns when the time the receiver dated its response (its `Date` header) is more than
+/// `MAX_CLOCK_SKEW` from the host's clock, which then dates snapshots wrongly.
+fn warn_clock_skew(url: &str, res: &Response) {
+    let Some(server_time) = res
+        .headers()
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+    else {
+        return;
+    };
+    let skew = Utc::now().signed_duration_since(server_time);
+    if skew.abs() > MAX_CLOCK_SKEW {
+        eprintln!(
+            "This host's clock is {}s {} {}'s; --timestamp can date the snapshot instead",
+            skew.num_seconds().abs(),
+            if skew > TimeDelta::zero() {
+                "ahead of"
+            } else {
+                "behind"
+            },
+            url
+        );
+    }
+}
+
+/// Uploads the snapshot to S3-compatible storage as `<prefix>/<id>/<timestamp>-<digest>.json`.
+pub struct S3Sink {
+    pub client: Client,
+    pub url: String,
+    pub location: S3Location,
+}
+
+#[async_trait]
+impl SnapshotSink for S3Sink {
+    fn target(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn deliver(&self, payload: &Payload) -> Result<(), String> {
+        let config = S3Config::load()?;
+        let metadata = payload.metadata();
+        let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+        let key = self
+            .location
+            .object_key(&metadata.id, &metadata.timestamp, &body);
+        s3::put_object(&self.client, &config, &self.location, &key, body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{empty_snapshot, TempDir};
+    use hsnap_purl_plugin::{ComponentWithSource, SoftwareComponent};
+
+    #[tokio::test]
+    async fn streams_large_snapshot_to_file() {
+        let mut snapshot = empty_snapshot();
+        snapshot.software_components = (0..10_000)
+            .map(|i| ComponentWithSource {
+                component: SoftwareComponent::Purl(
+                    format!("pkg:deb/debian/package-{}@1.{}-1?arch=amd64", i, i)
+                        .parse()
+                        .unwrap(),
+                ),
+                source: "debian-dpkg".to_string(),
+                license: None,
+                homepage: None,
+            })
+            .collect();
+        let payload = Payload::Snapshot(snapshot);
+        let expected = serde_json::to_string_pretty(&payload).unwrap();
+        let dir = TempDir::new();
+
+        let sink = FileSink {
+            path: dir.path().join("snapshot.json"),
+        };
+        sink.deliver(&payload).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&sink.path).unwrap(),
+            expected.trim_end()
+        );
+    }
+}
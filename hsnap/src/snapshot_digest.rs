@@ -0,0 +1,31 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+/// The hash `--digest` takes of the snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+/// The snapshot's `digest`: the hex hash of its canonical JSON without the `digest`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotDigest {
+    pub algorithm: DigestAlgorithm,
+    pub value: String,
+}
+
+/// Hashes `value`'s canonical JSON: compact, with the keys of every object sorted.
+pub fn compute<T: Serialize>(algorithm: DigestAlgorithm, value: &T) -> SnapshotDigest {
+    // serde_json's maps are sorted, so going through a Value sorts the struct fields
+    let canonical = serde_json::to_value(value)
+        .and_then(|value| serde_json::to_vec(&value))
+        .expect("Failed to serialize snapshot");
+    let value = match algorithm {
+        DigestAlgorithm::Sha256 => hex::encode(Sha256::digest(&canonical)),
+        DigestAlgorithm::Sha512 => hex::encode(Sha512::digest(&canonical)),
+    };
+    SnapshotDigest { algorithm, value }
+}
@@ -0,0 +1,117 @@
+use crate::sink::{Payload, SnapshotSink};
+use crate::Metadata;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Snapshots kept per url. Beyond this the oldest are dropped, so an endpoint that never
+/// comes back can't fill the disk.
+const MAX_SPOOLED: usize = 100;
+
+/// A spooled payload, delivered exactly as it was first serialized so signatures and
+/// digests still match.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct SpooledSnapshot {
+    // Encrypted payloads don't carry their metadata, so it is kept alongside
+    #[serde(skip)]
+    pub metadata: Metadata,
+    pub payload: Box<RawValue>,
+}
+
+/// A spool file.
+#[derive(Serialize, Deserialize)]
+struct SpoolEntry {
+    metadata: Metadata,
+    payload: Box<RawValue>,
+}
+
+/// Snapshots that couldn't be delivered to a url, kept under `--spool-dir` until it
+/// accepts them again.
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    pub fn new(spool_dir: &Path, target: &str) -> Spool {
+        // A url doesn't make a file name, so each gets a directory named after its hash
+        let hash = hex::encode(Sha256::digest(target));
+        Spool {
+            dir: spool_dir.join(&hash[..16]),
+        }
+    }
+
+    /// Whether nothing is spooled.
+    pub fn is_empty(&self) -> bool {
+        self.files().is_empty()
+    }
+
+    /// The spooled snapshots, oldest first.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+        files
+    }
+
+    pub fn push(&self, payload: &Payload) -> Result<(), String> {
+        let entry = SpoolEntry {
+            metadata: payload.metadata().clone(),
+            payload: serde_json::value::to_raw_value(payload).map_err(|e| e.to_string())?,
+        };
+        let contents = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        // Named by when they were spooled, so they sort oldest first
+        let name = format!("{}.json", Utc::now().format("%Y%m%dT%H%M%S%.6fZ"));
+        std::fs::write(self.dir.join(name), contents).map_err(|e| e.to_string())?;
+
+        let files = self.files();
+        for oldest in &files[..files.len().saturating_sub(MAX_SPOOLED)] {
+            let _ = std::fs::remove_file(oldest);
+        }
+        Ok(())
+    }
+
+    /// Delivers the spooled snapshots oldest first, removing each once delivered, and
+    /// returns how many there were. Stops at the first that fails.
+    pub async fn flush(&self, sink: &dyn SnapshotSink) -> Result<usize, String> {
+        let mut delivered = 0;
+        for path in self.files() {
+            let entry = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| {
+                    serde_json::from_str::<SpoolEntry>(&contents).map_err(|e| e.to_string())
+                });
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!(
+                        "Dropping unreadable spooled snapshot {}: {}",
+                        path.display(),
+                        e
+                    );
+                    let _ = std::fs::remove_file(&path);
+                    continue;
+                }
+            };
+
+            let payload = Payload::Spooled(SpooledSnapshot {
+                metadata: entry.metadata,
+                payload: entry.payload,
+            });
+            sink.deliver(&payload).await?;
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+}
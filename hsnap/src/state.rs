@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+use hsnap_purl_plugin::ComponentWithSource;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the circuit breaker first waits once open. Each further failure doubles it.
+const BASE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Local state persisted between runs when `--state-file` is given.
+#[derive(Serialize, Deserialize, Default)]
+pub struct State {
+    /// `sections::content_digest` of the last snapshot successfully delivered to each url.
+    #[serde(default)]
+    pub sent: HashMap<String, String>,
+    /// The software components last delivered to each url, used as the baseline for `--delta`.
+    #[serde(default)]
+    pub baselines: HashMap<String, Vec<ComponentWithSource>>,
+    /// Digest of each section last delivered to each url, used by `--changed-sections`.
+    #[serde(default)]
+    pub sections: HashMap<String, HashMap<String, String>>,
+    /// Consecutive failed deliveries to each url, for the circuit breaker.
+    #[serde(default)]
+    pub failures: HashMap<String, Failures>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Failures {
+    pub consecutive: u32,
+    /// Once the breaker has opened, no delivery is attempted before this.
+    pub retry_after: Option<DateTime<Utc>>,
+}
+
+impl State {
+    /// Loads the state file, starting fresh if it is missing or unreadable.
+    pub fn load(path: &Path) -> State {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// When delivery to `target` may next be attempted, if its circuit breaker is open.
+    pub fn retry_after(&self, target: &str) -> Option<DateTime<Utc>> {
+        self.failures
+            .get(target)?
+            .retry_after
+            .filter(|retry_after| *retry_after > Utc::now())
+    }
+
+    /// Counts a failed delivery to `target`, opening its circuit breaker after
+    /// `threshold` in a row (0 never opens it). The wait doubles with each failure after
+    /// that, up to `max_backoff`.
+    pub fn record_failure(&mut self, target: &str, threshold: u32, max_backoff: Duration) {
+        let failures = self.failures.entry(target.to_string()).or_default();
+        failures.consecutive += 1;
+        if threshold > 0 && failures.consecutive >= threshold {
+            let doublings = (failures.consecutive - threshold).min(20);
+            let backoff = (BASE_BACKOFF * 2u32.pow(doublings)).min(max_backoff);
+            failures.retry_after = chrono::Duration::from_std(backoff)
+                .ok()
+                .map(|backoff| Utc::now() + backoff);
+        }
+    }
+
+    /// Closes the circuit breaker of `target` after a successful delivery.
+    pub fn record_success(&mut self, target: &str) {
+        self.failures.remove(target);
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let contents = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, contents)
+    }
+}
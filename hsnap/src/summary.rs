@@ -0,0 +1,122 @@
+use crate::HostSnapshot;
+use hsnap_purl_plugin::SoftwareComponent;
+
+/// A human readable summary of a snapshot for `--summary`: a line about the host, then a
+/// table of its software components.
+pub fn render(snapshot: &HostSnapshot) -> String {
+    let os = snapshot.operating_system.as_ref().and_then(|os| {
+        let name = os.os_name.as_deref()?;
+        Some(match &os.os_version {
+            Some(version) => format!("{} {}", name, version),
+            None => name.to_string(),
+        })
+    });
+    let mut out = format!(
+        "{} ({}), captured {}: {} software components\n",
+        snapshot.metadata.id,
+        os.as_deref().unwrap_or("unknown OS"),
+        snapshot.metadata.timestamp.to_rfc3339(),
+        snapshot.software_components.len()
+    );
+
+    let mut rows = vec![[
+        "NAME".to_string(),
+        "VERSION".to_string(),
+        "TYPE".to_string(),
+        "SOURCE".to_string(),
+    ]];
+    for component in &snapshot.software_components {
+        let (name, version, kind) = match &component.component {
+            SoftwareComponent::Purl(purl) => (
+                match purl.namespace() {
+                    Some(namespace) => format!("{}/{}", namespace, purl.name()),
+                    None => purl.name().to_string(),
+                },
+                purl.version().unwrap_or_default().to_string(),
+                purl.ty().to_string(),
+            ),
+            SoftwareComponent::WindowsComponent { name, version, .. } => (
+                name.clone(),
+                version.clone(),
+                "windows-component".to_string(),
+            ),
+            SoftwareComponent::BrowserExtension { name, version, .. } => (
+                name.clone(),
+                version.clone(),
+                "browser-extension".to_string(),
+            ),
+            SoftwareComponent::WindowsDriver { name, .. } => {
+                (name.clone(), String::new(), "windows-driver".to_string())
+            }
+        };
+        rows.push([name, version, kind, component.source.clone()]);
+    }
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::empty_snapshot;
+    use hsnap_purl_plugin::ComponentWithSource;
+
+    fn found(component: SoftwareComponent, source: &str) -> ComponentWithSource {
+        ComponentWithSource {
+            component,
+            source: source.to_string(),
+            license: None,
+            homepage: None,
+        }
+    }
+
+    #[test]
+    fn renders_host_line_and_component_table() {
+        let mut snapshot = empty_snapshot();
+        snapshot.software_components = vec![
+            found(
+                SoftwareComponent::Purl("pkg:deb/debian/openssl@3.0.11-1".parse().unwrap()),
+                "debian-dpkg",
+            ),
+            found(
+                SoftwareComponent::Purl("pkg:pypi/requests@2.31.0".parse().unwrap()),
+                "python-venv",
+            ),
+            found(
+                SoftwareComponent::BrowserExtension {
+                    browser: "chrome".to_string(),
+                    id: "cjpalhdlnbpafiamejdnhcphjbkeiagm".to_string(),
+                    name: "uBlock Origin".to_string(),
+                    version: "1.54.0".to_string(),
+                    profile: "Default".to_string(),
+                    user: None,
+                },
+                "browser-extensions",
+            ),
+        ];
+
+        assert_eq!(
+            render(&snapshot),
+            "test-host (unknown OS), captured 2024-05-01T12:00:00+00:00: 3 software components\n\
+             NAME            VERSION   TYPE               SOURCE\n\
+             debian/openssl  3.0.11-1  deb                debian-dpkg\n\
+             requests        2.31.0    pypi               python-venv\n\
+             uBlock Origin   1.54.0    browser-extension  browser-extensions\n"
+        );
+    }
+}
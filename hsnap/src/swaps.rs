@@ -0,0 +1,82 @@
+use crate::mounts;
+use serde::{Deserialize, Serialize};
+
+/// A swap partition or file in use.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SwapDevice {
+    pub path: String,
+    /// `partition` or `file`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// In bytes.
+    pub size: u64,
+    /// In bytes.
+    pub used: u64,
+    /// Higher priority devices are used first.
+    pub priority: i32,
+}
+
+/// The swap devices in `/proc/swaps`. Empty on platforms without it.
+pub fn collect() -> Vec<SwapDevice> {
+    std::fs::read_to_string("/proc/swaps")
+        .map(|contents| parse_swaps(&contents))
+        .unwrap_or_default()
+}
+
+/// Parses `/proc/swaps`, a header and then a line per device, sizes in KiB:
+///
+/// ```text
+/// Filename                                Type            Size            Used            Priority
+/// /dev/sda2                               partition       8388604         0               -2
+/// /swapfile                               file            2097148         1024            -3
+/// ```
+fn parse_swaps(contents: &str) -> Vec<SwapDevice> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(SwapDevice {
+                // Spaces in the path are escaped as in /proc/mounts
+                path: mounts::unescape(fields.next()?),
+                kind: fields.next()?.to_string(),
+                size: fields.next()?.parse::<u64>().ok()? * 1024,
+                used: fields.next()?.parse::<u64>().ok()? * 1024,
+                priority: fields.next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_swaps() {
+        let contents = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+            /dev/sda2                               partition\t8388604\t\t0\t\t-2\n\
+            /var/swap\\040files/one                  file\t\t2097148\t\t1024\t\t-3\n\
+            /dev/zram0                              partition\tbogus\t\t0\t\t100\n";
+        assert_eq!(
+            serde_json::to_value(parse_swaps(contents)).unwrap(),
+            serde_json::json!([
+                {
+                    "path": "/dev/sda2",
+                    "type": "partition",
+                    "size": 8388604u64 * 1024,
+                    "used": 0,
+                    "priority": -2,
+                },
+                {
+                    "path": "/var/swap files/one",
+                    "type": "file",
+                    "size": 2097148u64 * 1024,
+                    "used": 1024 * 1024,
+                    "priority": -3,
+                },
+            ])
+        );
+        assert!(parse_swaps("Filename\tType\tSize\tUsed\tPriority\n").is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use serde::Deserialize;
+use std::process::Command;
+
+/// The fields of `system_profiler SPHardwareDataType -json` that sysinfo doesn't provide.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+pub struct MacHardware {
+    /// Model identifier, e.g. `MacBookPro18,3`.
+    pub machine_model: Option<String>,
+    pub serial_number: Option<String>,
+    pub boot_rom_version: Option<String>,
+    pub os_loader_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HardwareReport {
+    #[serde(rename = "SPHardwareDataType", default)]
+    hardware: Vec<MacHardware>,
+}
+
+/// Runs system_profiler on macOS. `None` elsewhere, or if it fails or prints something unexpected.
+pub fn hardware() -> Option<MacHardware> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    let output = Command::new("system_profiler")
+        .args(["SPHardwareDataType", "-json"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_hardware(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_hardware(json: &str) -> Option<MacHardware> {
+    let report: HardwareReport = serde_json::from_str(json).ok()?;
+    report.hardware.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hardware_report() {
+        let json = r#"{
+  "SPHardwareDataType" : [
+    {
+      "_name" : "hardware_overview",
+      "activation_lock_status" : "activation_lock_disabled",
+      "boot_rom_version" : "10151.101.3",
+      "chip_type" : "Apple M1 Pro",
+      "machine_model" : "MacBookPro18,3",
+      "machine_name" : "MacBook Pro",
+      "model_number" : "Z15G000CHLL/A",
+      "number_processors" : "proc 10:8:2",
+      "os_loader_version" : "10151.101.3",
+      "physical_memory" : "32 GB",
+      "platform_UUID" : "5C2C8D6F-1E4B-5D0A-9E6B-3B0F6A2C1D7E",
+      "provisioning_UDID" : "00006000-001A2B3C4D5E6F70",
+      "serial_number" : "C02XK1ABCDEF"
+    }
+  ]
+}"#;
+        assert_eq!(
+            parse_hardware(json),
+            Some(MacHardware {
+                machine_model: Some("MacBookPro18,3".to_string()),
+                serial_number: Some("C02XK1ABCDEF".to_string()),
+                boot_rom_version: Some("10151.101.3".to_string()),
+                os_loader_version: Some("10151.101.3".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unexpected_output() {
+        assert_eq!(parse_hardware(""), None);
+        assert_eq!(parse_hardware(r#"{"SPHardwareDataType": []}"#), None);
+        assert_eq!(
+            parse_hardware(r#"{"SPHardwareDataType": [{}]}"#),
+            Some(MacHardware::default())
+        );
+    }
+}
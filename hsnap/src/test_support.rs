@@ -0,0 +1,273 @@
+//! Helpers shared by the unit tests.
+
+use crate::{Args, HostSnapshot};
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A directory under the system temp directory, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "hsnap-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A 2048-bit RSA key pair used only by the tests.
+pub const SIGNING_KEY_PEM: &str = include_str!("../testdata/signing_key.pem");
+pub const VERIFYING_KEY_PEM: &str = include_str!("../testdata/signing_key.pub.pem");
+/// A second RSA key pair, as the new key during a rotation.
+pub const ROTATED_SIGNING_KEY_PEM: &str = include_str!("../testdata/rotated_signing_key.pem");
+pub const ROTATED_VERIFYING_KEY_PEM: &str = include_str!("../testdata/rotated_signing_key.pub.pem");
+
+/// A P-256 key pair used only by the tests.
+pub const ECDSA_SIGNING_KEY_PEM: &str = include_str!("../testdata/ecdsa_p256_key.pem");
+pub const ECDSA_VERIFYING_KEY_PEM: &str = include_str!("../testdata/ecdsa_p256_key.pub.pem");
+
+/// The CA issuing `MockServer::start_tls`'s certificate, and one that didn't, used only by
+/// the tests.
+pub const TLS_CA_PEM: &str = include_str!("../testdata/tls_ca.pem");
+pub const TLS_OTHER_CA_PEM: &str = include_str!("../testdata/tls_other_ca.pem");
+const TLS_SERVER_PEM: &str = include_str!("../testdata/tls_server.pem");
+const TLS_SERVER_KEY_PEM: &str = include_str!("../testdata/tls_server_key.pem");
+
+/// Parses command line arguments, without the program name.
+pub fn args(args: &[&str]) -> Args {
+    Args::parse_from(std::iter::once("hsnap").chain(args.iter().copied()))
+}
+
+/// A snapshot with nothing collected.
+pub fn empty_snapshot() -> HostSnapshot {
+    serde_json::from_value(serde_json::json!({
+        "metadata": {
+            "id": "test-host",
+            "timestamp": "2024-05-01T12:00:00Z",
+            "environment": "bare_metal",
+            "capture_duration_ms": 1500
+        },
+        "services": [],
+        "software_components": []
+    }))
+    .unwrap()
+}
+
+/// Encodes `value` as MessagePack, with the widest form of each type.
+pub fn to_msgpack(value: &serde_json::Value) -> Vec<u8> {
+    use serde_json::Value;
+    let mut out = Vec::new();
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(b) => out.push(0xc2 | *b as u8),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => out.extend([0xcf].into_iter().chain(n.to_be_bytes())),
+            (None, Some(n)) => out.extend([0xd3].into_iter().chain(n.to_be_bytes())),
+            _ => out.extend([0xcb].into_iter().chain(n.as_f64().unwrap().to_be_bytes())),
+        },
+        Value::String(s) => {
+            out.push(0xdb);
+            out.extend((s.len() as u32).to_be_bytes());
+            out.extend(s.as_bytes());
+        }
+        Value::Array(items) => {
+            out.push(0xdd);
+            out.extend((items.len() as u32).to_be_bytes());
+            items.iter().for_each(|item| out.extend(to_msgpack(item)));
+        }
+        Value::Object(members) => {
+            out.push(0xdf);
+            out.extend((members.len() as u32).to_be_bytes());
+            for (name, member) in members {
+                out.extend(to_msgpack(&Value::String(name.clone())));
+                out.extend(to_msgpack(member));
+            }
+        }
+    }
+    out
+}
+
+/// A request received by a `MockServer`.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// With lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP server on localhost giving every request the same response, and recording the
+/// requests it was sent.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Answers every request with an empty `200 OK`.
+    pub async fn start() -> MockServer {
+        MockServer::respond_with(200, Vec::new(), Vec::new()).await
+    }
+
+    pub async fn respond_with(
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> MockServer {
+        MockServer::listen(None, status, headers, body).await
+    }
+
+    /// Answers every request with an empty `200 OK` over HTTPS, with a certificate for
+    /// `localhost` issued by `TLS_CA_PEM`.
+    pub async fn start_tls() -> MockServer {
+        use tokio_rustls::rustls::pki_types::pem::PemObject;
+        use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+        use tokio_rustls::rustls::ServerConfig;
+
+        let chain = CertificateDer::pem_slice_iter(TLS_SERVER_PEM.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_slice(TLS_SERVER_KEY_PEM.as_bytes()).unwrap();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        MockServer::listen(Some(acceptor), 200, Vec::new(), Vec::new()).await
+    }
+
+    async fn listen(
+        tls: Option<tokio_rustls::TlsAcceptor>,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let url = match tls {
+            Some(_) => format!("https://localhost:{}", port),
+            None => format!("http://127.0.0.1:{}", port),
+        };
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let tls = tls.clone();
+                let headers = headers.clone();
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let request = match tls {
+                        // A client that doesn't trust the certificate gives up on the handshake
+                        Some(tls) => match tls.accept(stream).await {
+                            Ok(stream) => serve(stream, status, &headers, &body).await,
+                            Err(_) => None,
+                        },
+                        None => serve(stream, status, &headers, &body).await,
+                    };
+                    if let Some(request) = request {
+                        recorded.lock().unwrap().push(request);
+                    }
+                });
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Reads one request from `stream` and sends the response.
+async fn serve(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    status: u16,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Option<Request> {
+    let mut received = Vec::new();
+    let mut buf = [0; 8192];
+    let head_end = loop {
+        if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        let len = stream.read(&mut buf).await.ok()?;
+        if len == 0 {
+            return None;
+        }
+        received.extend_from_slice(&buf[..len]);
+    };
+    let head = String::from_utf8_lossy(&received[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let request_headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let length: usize = request_headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut request_body = received[head_end + 4..].to_vec();
+    while request_body.len() < length {
+        let len = stream.read(&mut buf).await.ok()?;
+        if len == 0 {
+            break;
+        }
+        request_body.extend_from_slice(&buf[..len]);
+    }
+
+    let mut response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    stream.write_all(&response).await.ok()?;
+    stream.shutdown().await.ok()?;
+
+    Some(Request {
+        method,
+        path,
+        headers: request_headers,
+        body: request_body,
+    })
+}
@@ -0,0 +1,353 @@
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// The CA bundles of Debian (and derivatives) and of RHEL (and derivatives).
+const CA_BUNDLES: [&str; 2] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+];
+const CA_DIR: &str = "/etc/ssl/certs";
+
+/// A CA certificate the host trusts.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CertSummary {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: DateTime<Utc>,
+    /// SHA256 of the DER certificate, in hex.
+    pub sha256_fingerprint: String,
+    pub expired: bool,
+}
+
+/// The certificates in the system trust store: the CA bundle and `/etc/ssl/certs` on
+/// Linux, the Root, AuthRoot and CA stores on Windows. Each is listed once, ordered by
+/// subject.
+pub fn collect() -> Vec<CertSummary> {
+    let certificates = if cfg!(target_os = "windows") {
+        windows_certificates()
+    } else {
+        let mut paths: Vec<_> = CA_BUNDLES
+            .iter()
+            .map(|path| Path::new(path).to_path_buf())
+            .collect();
+        let mut dir: Vec<_> = std::fs::read_dir(CA_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file())
+                    .collect()
+            })
+            .unwrap_or_default();
+        dir.sort();
+        paths.extend(dir);
+        paths
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .flat_map(|contents| parse_pem_bundle(&contents))
+            .collect()
+    };
+    summaries(certificates, Utc::now())
+}
+
+/// Summarizes each distinct certificate, flagging those expired by `now`.
+fn summaries(certificates: Vec<Vec<u8>>, now: DateTime<Utc>) -> Vec<CertSummary> {
+    let mut summaries: Vec<CertSummary> = Vec::new();
+    for der in certificates {
+        let Some(mut summary) = summarize(&der) else {
+            continue;
+        };
+        summary.expired = summary.not_after < now;
+        // Bundles and the hash links in /etc/ssl/certs repeat the same certificates
+        if !summaries
+            .iter()
+            .any(|seen| seen.sha256_fingerprint == summary.sha256_fingerprint)
+        {
+            summaries.push(summary);
+        }
+    }
+    summaries.sort_by(|a, b| {
+        (&a.subject, &a.sha256_fingerprint).cmp(&(&b.subject, &b.sha256_fingerprint))
+    });
+    summaries
+}
+
+/// The DER of each `CERTIFICATE` in a PEM file.
+fn parse_pem_bundle(contents: &str) -> Vec<Vec<u8>> {
+    let mut certificates = Vec::new();
+    let mut base64: Option<String> = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        match (&mut base64, line) {
+            (None, "-----BEGIN CERTIFICATE-----" | "-----BEGIN TRUSTED CERTIFICATE-----") => {
+                base64 = Some(String::new())
+            }
+            (Some(data), "-----END CERTIFICATE-----" | "-----END TRUSTED CERTIFICATE-----") => {
+                if let Ok(der) = base64::engine::general_purpose::STANDARD.decode(&data) {
+                    certificates.push(der);
+                }
+                base64 = None;
+            }
+            (Some(data), line) => data.push_str(line),
+            (None, _) => {}
+        }
+    }
+    certificates
+}
+
+/// Reads the subject, issuer and expiry of a DER X.509 certificate. `expired` is left
+/// for the caller to set.
+fn summarize(der: &[u8]) -> Option<CertSummary> {
+    let (_, certificate, _) = read_tlv(der).filter(|(tag, _, _)| *tag == SEQUENCE)?;
+    let (_, tbs, _) = read_tlv(certificate).filter(|(tag, _, _)| *tag == SEQUENCE)?;
+
+    let mut fields = Tlvs(tbs);
+    let mut field = fields.next()?;
+    // The version is only there for v2 and v3 certificates
+    if field.0 == 0xa0 {
+        field = fields.next()?;
+    }
+    let _serial = field;
+    let _signature_algorithm = fields.next()?;
+    let issuer = fields.next()?;
+    let validity = fields.next()?;
+    let subject = fields.next()?;
+
+    let mut times = Tlvs(validity.1);
+    let _not_before = times.next()?;
+    let not_after = parse_time(times.next()?)?;
+
+    Some(CertSummary {
+        subject: format_name(subject.1),
+        issuer: format_name(issuer.1),
+        not_after,
+        sha256_fingerprint: hex::encode(Sha256::digest(der)),
+        expired: false,
+    })
+}
+
+const SEQUENCE: u8 = 0x30;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+
+/// Splits the first DER value off `input`, as its tag, contents and what follows it.
+fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, &rest[count..])
+    };
+    (rest.len() >= length).then(|| (tag, &rest[..length], &rest[length..]))
+}
+
+/// The values in a DER constructed value's contents, in turn.
+struct Tlvs<'a>(&'a [u8]);
+
+impl<'a> Iterator for Tlvs<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let (tag, contents, rest) = read_tlv(self.0)?;
+        self.0 = rest;
+        Some((tag, contents))
+    }
+}
+
+/// A UTCTime (`YYMMDDHHMMSSZ`, years from 1950 to 2049) or GeneralizedTime
+/// (`YYYYMMDDHHMMSSZ`).
+fn parse_time((tag, contents): (u8, &[u8])) -> Option<DateTime<Utc>> {
+    let text = std::str::from_utf8(contents).ok()?.strip_suffix('Z')?;
+    let (year, rest) = match tag {
+        UTC_TIME => {
+            let year: i32 = text.get(..2)?.parse().ok()?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+    let number = |range: std::ops::Range<usize>| rest.get(range)?.parse::<u32>().ok();
+    NaiveDate::from_ymd_opt(year, number(0..2)?, number(2..4)?)?
+        .and_hms_opt(number(4..6)?, number(6..8)?, number(8..10)?)
+        .map(|time| time.and_utc())
+}
+
+/// A distinguished name as `C=US, O=Example, CN=Example Root CA`, in the order the
+/// certificate lists its attributes.
+fn format_name(name: &[u8]) -> String {
+    let mut attributes = Vec::new();
+    for (_, set) in Tlvs(name) {
+        for (_, attribute) in Tlvs(set) {
+            let mut parts = Tlvs(attribute);
+            let (Some((_, oid)), Some(value)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let oid = format_oid(oid);
+            let label = match oid.as_str() {
+                "2.5.4.3" => "CN",
+                "2.5.4.5" => "serialNumber",
+                "2.5.4.6" => "C",
+                "2.5.4.7" => "L",
+                "2.5.4.8" => "ST",
+                "2.5.4.10" => "O",
+                "2.5.4.11" => "OU",
+                "0.9.2342.19200300.100.1.25" => "DC",
+                "1.2.840.113549.1.9.1" => "emailAddress",
+                other => other,
+            };
+            attributes.push(format!("{}={}", label, decode_string(value)));
+        }
+    }
+    attributes.join(", ")
+}
+
+fn format_oid(oid: &[u8]) -> String {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut value = 0u64;
+    for byte in oid {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                // The first byte holds the first two arcs
+                let first = (value / 40).min(2);
+                arcs.extend([first, value - first * 40]);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    arcs.iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Decodes the string types names use: UTF-16 BMPStrings, Latin-1 T61Strings (as they
+/// are in practice) and the ASCII and UTF-8 ones.
+fn decode_string((tag, contents): (u8, &[u8])) -> String {
+    match tag {
+        // BMPString
+        0x1e => char::decode_utf16(
+            contents
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect(),
+        // T61String
+        0x14 => contents.iter().map(|byte| *byte as char).collect(),
+        _ => String::from_utf8_lossy(contents).to_string(),
+    }
+}
+
+/// The DER certificates in the machine's Root, AuthRoot and CA stores. The registry
+/// holds each as a blob of properties, one of which (id 32) is the certificate.
+fn windows_certificates() -> Vec<Vec<u8>> {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+
+        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+        let mut certificates = Vec::new();
+        for store in ["ROOT", "AuthRoot", "CA"] {
+            let Ok(store) = hklm.open_subkey(format!(
+                "SOFTWARE\\Microsoft\\SystemCertificates\\{}\\Certificates",
+                store
+            )) else {
+                continue;
+            };
+            for thumbprint in store.enum_keys().filter_map(|key| key.ok()) {
+                let blob = store
+                    .open_subkey(&thumbprint)
+                    .and_then(|key| key.get_raw_value("Blob"));
+                if let Some(der) = blob.ok().and_then(|blob| certificate_property(&blob.bytes)) {
+                    certificates.push(der);
+                }
+            }
+        }
+        certificates
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Vec::new()
+    }
+}
+
+/// The certificate in a serialized certificate store entry: a run of properties, each a
+/// little endian id, a reserved word and a length, then its value.
+#[cfg(target_os = "windows")]
+fn certificate_property(mut blob: &[u8]) -> Option<Vec<u8>> {
+    const CERT_CERT_PROP_ID: u32 = 32;
+    while blob.len() >= 12 {
+        let word = |i: usize| u32::from_le_bytes(blob[i..i + 4].try_into().expect("4 bytes"));
+        let (id, length) = (word(0), word(8) as usize);
+        let value = blob.get(12..12 + length)?;
+        if id == CERT_CERT_PROP_ID {
+            return Some(value.to_vec());
+        }
+        blob = &blob[12 + length..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pem_bundle() {
+        let bundle = include_str!("../testdata/trust_bundle.pem");
+        let certificates = parse_pem_bundle(bundle);
+        // The private key block isn't a certificate
+        assert_eq!(certificates.len(), 2);
+
+        // As /etc/ssl/certs repeats what the bundle has
+        let repeated = certificates.iter().chain(&certificates).cloned().collect();
+        let now = "2024-06-01T00:00:00Z".parse().unwrap();
+        let summaries = summaries(repeated, now);
+        assert_eq!(summaries.len(), 2);
+
+        let root = &summaries[0];
+        assert_eq!(
+            root.subject,
+            "C=US, O=Example Corp, OU=Security, CN=Example Root CA"
+        );
+        assert_eq!(root.issuer, root.subject);
+        // After 2049, so a GeneralizedTime
+        assert_eq!(root.not_after.to_rfc3339(), "2099-12-31T23:59:59+00:00");
+        assert_eq!(
+            root.sha256_fingerprint,
+            "b0c88dafbd8b4ad9043befd562c44064f0c4e6e50441b402246809e606f29a8e"
+        );
+        assert!(!root.expired);
+
+        let issuing = &summaries[1];
+        assert_eq!(
+            issuing.subject,
+            "O=Example Corp, CN=Example Issuing CA 2019"
+        );
+        assert_eq!(issuing.issuer, root.subject);
+        assert_eq!(issuing.not_after.to_rfc3339(), "2021-12-31T23:59:59+00:00");
+        assert_eq!(
+            issuing.sha256_fingerprint,
+            "32bf8687b656d43b5c146972b388112e89a5993bdb69cee40efb7c2879f4111e"
+        );
+        assert!(issuing.expired);
+    }
+}
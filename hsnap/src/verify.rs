@@ -0,0 +1,306 @@
+use crate::signing::{EcdsaSignatureFormat, SignatureEncoding, VerifyingKey};
+use crate::{msgpack, MAX_SNAPSHOT_BYTES};
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_ENCODING, CONTENT_TYPE};
+use serde::de::{DeserializeOwned, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where `hsnap verify` reads the signed snapshot from.
+pub enum Source {
+    File(PathBuf),
+    Url(String),
+}
+
+/// Reads the signed snapshot from `source` and checks its signature against the public
+/// key in `public_key`, printing who signed it and when.
+pub async fn run(
+    source: &Source,
+    public_key: &Path,
+    client: &reqwest::Client,
+) -> Result<(), String> {
+    let key = std::fs::read_to_string(public_key)
+        .map_err(|e| format!("{}: {}", public_key.display(), e))
+        .and_then(|pem| VerifyingKey::from_pem(&pem))?;
+    let envelope = match source {
+        Source::File(path) => read_file(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Source::Url(url) => fetch(client, url)
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?,
+    };
+    println!("{}", verify(&envelope, &key)?);
+    Ok(())
+}
+
+/// Reads the signed snapshot from `path`. A gzip or zstd compressed snapshot is rejected
+/// with a clear error rather than as invalid UTF-8, since hsnap doesn't decompress.
+fn read_file(path: &Path) -> Result<String, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    if data.starts_with(&[0x1f, 0x8b]) || data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        return Err(
+            "Compressed snapshots aren't supported, decompress it with gunzip or unzstd first"
+                .to_string(),
+        );
+    }
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
+
+/// Fetches the signed snapshot from `url`, as JSON or MessagePack. The body may be no
+/// more than `MAX_SNAPSHOT_BYTES`. A MessagePack snapshot must keep the signed bytes in a
+/// `signed` string, from `--no-signature-reserialize`: transcoding it back to JSON can't
+/// reproduce what was signed, as a float may be written differently or decoded as an
+/// integer.
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let mut response = client
+        .get(url)
+        .header(ACCEPT, "application/json, application/msgpack")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default()
+    };
+    let encoding = header(CONTENT_ENCODING);
+    let content_type = header(CONTENT_TYPE);
+
+    let too_large = || format!("Response is more than {} bytes", MAX_SNAPSHOT_BYTES);
+    if response.content_length().unwrap_or_default() > MAX_SNAPSHOT_BYTES as u64 {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > MAX_SNAPSHOT_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = match encoding.as_str() {
+        "" | "identity" => body,
+        other => return Err(format!("Unsupported Content-Encoding {}", other)),
+    };
+    match content_type.split(';').next().unwrap_or_default().trim() {
+        "application/msgpack" | "application/x-msgpack" => {
+            let json = msgpack::to_json(&body)?;
+            let envelope: Members =
+                serde_json::from_str(&json).map_err(|e| format!("Not a signed snapshot: {}", e))?;
+            if !envelope.has("signed") {
+                return Err(
+                    "A MessagePack snapshot can only be verified if it keeps the signed \
+                     bytes in a `signed` string, as signed with --no-signature-reserialize"
+                        .to_string(),
+                );
+            }
+            Ok(json)
+        }
+        _ => String::from_utf8(body).map_err(|e| e.to_string()),
+    }
+}
+
+/// Checks the signature of a `SignedSnapshot` against `key`, or of a snapshot signed
+/// with several keys, the signature made with `key`. Either may hold the exact bytes that
+/// were signed in a `signed` string, from `--no-signature-reserialize`.
+pub fn verify(envelope: &str, key: &VerifyingKey) -> Result<String, String> {
+    let envelope: Members =
+        serde_json::from_str(envelope).map_err(|e| format!("Not a signed snapshot: {}", e))?;
+    let verbatim = match envelope.has("signed") {
+        true => Some(envelope.get::<String>("signed")?),
+        false => None,
+    };
+    // The signed fields, which are the envelope's own unless they were kept verbatim
+    let verbatim_members = match &verbatim {
+        Some(signed) => Some(
+            serde_json::from_str::<Members>(signed)
+                .map_err(|e| format!("Not a signed snapshot: {}", e))?,
+        ),
+        None => None,
+    };
+    let members = verbatim_members.as_ref().unwrap_or(&envelope);
+    let encoding: SignatureEncoding = members.get("signature_encoding")?;
+
+    let (signature, unsigned_member) = if envelope.has("signatures") {
+        let signatures: Vec<KeySignature> = envelope.get("signatures")?;
+        let key_ids: Vec<String> = signatures.iter().map(|s| s.key_id.clone()).collect();
+        let signature = signatures
+            .into_iter()
+            .find(|signature| signature.key_id == key.key_id())
+            .ok_or_else(|| {
+                format!(
+                    "Signed by keys {}, not the given key {}",
+                    key_ids.join(", "),
+                    key.key_id()
+                )
+            })?;
+        // The signature's own key_id, algorithm and format aren't signed, the list is
+        let signers: Vec<SignerKey> = members.get("signers")?;
+        if !signers.iter().any(|signer| {
+            signer.key_id == signature.key_id
+                && signer.algorithm == signature.algorithm
+                && signer.signature_format == signature.signature_format
+        }) {
+            return Err(format!(
+                "The signature of key {} doesn't match the signed list of signers",
+                signature.key_id
+            ));
+        }
+        (signature, "signatures")
+    } else {
+        let signature = KeySignature {
+            key_id: members.get("key_id")?,
+            algorithm: members.get("algorithm")?,
+            signature_format: match members.has("signature_format") {
+                true => Some(members.get("signature_format")?),
+                false => None,
+            },
+            signature: envelope.get("signature")?,
+        };
+        if signature.key_id != key.key_id() {
+            return Err(format!(
+                "Signed by key {}, not the given key {}",
+                signature.key_id,
+                key.key_id()
+            ));
+        }
+        (signature, "signature")
+    };
+    if !["RS256", "ES256"].contains(&signature.algorithm.as_str()) {
+        return Err(format!(
+            "Unsupported signature algorithm {}",
+            signature.algorithm
+        ));
+    }
+    if signature.algorithm != key.algorithm() {
+        return Err(format!(
+            "Signed with {}, but the given key is for {}",
+            signature.algorithm,
+            key.algorithm()
+        ));
+    }
+    let signature_bytes = encoding
+        .decode(&signature.signature)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+
+    let signed = verbatim.unwrap_or_else(|| signed_bytes(&envelope, unsigned_member));
+    if !key.verify(
+        signed.as_bytes(),
+        &signature_bytes,
+        signature.signature_format,
+    ) {
+        return Err("Signature does not match the snapshot".to_string());
+    }
+    Ok(format!(
+        "Signature verified: signed by {} at {}",
+        signature.key_id,
+        members.get::<String>("signed_at")?
+    ))
+}
+
+/// One of the `signatures` of a snapshot signed with several keys.
+#[derive(Deserialize)]
+struct KeySignature {
+    key_id: String,
+    algorithm: String,
+    /// Only of ECDSA signatures.
+    #[serde(default)]
+    signature_format: Option<EcdsaSignatureFormat>,
+    signature: String,
+}
+
+/// One of the `signers` a snapshot signed with several keys lists among its signed fields.
+#[derive(Deserialize)]
+struct SignerKey {
+    key_id: String,
+    algorithm: String,
+    #[serde(default)]
+    signature_format: Option<EcdsaSignatureFormat>,
+}
+
+/// What was signed: the compact JSON of the envelope without `unsigned_member` (its
+/// signature or signatures), with the other fields in the order they were written.
+fn signed_bytes(members: &Members, unsigned_member: &str) -> String {
+    let members: Vec<String> = members
+        .0
+        .iter()
+        .filter(|(name, _)| name != unsigned_member)
+        .map(|(name, value)| {
+            format!(
+                "{}:{}",
+                serde_json::to_string(name).expect("Failed to serialize field name"),
+                compact(value.get())
+            )
+        })
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/// Removes the whitespace outside strings, undoing pretty printing.
+fn compact(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in json.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c.is_whitespace() {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A JSON object's members in the order they appear, which a `serde_json::Map` doesn't keep.
+struct Members(Vec<(String, Box<RawValue>)>);
+
+impl Members {
+    fn has(&self, name: &str) -> bool {
+        self.0.iter().any(|(member, _)| member == name)
+    }
+
+    fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, String> {
+        let (_, value) = self
+            .0
+            .iter()
+            .find(|(member, _)| member == name)
+            .ok_or_else(|| format!("Not a signed snapshot: no {}", name))?;
+        serde_json::from_str(value.get()).map_err(|e| format!("Invalid {}: {}", name, e))
+    }
+}
+
+impl<'de> Deserialize<'de> for Members {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Members, D::Error> {
+        struct MembersVisitor;
+
+        impl<'de> Visitor<'de> for MembersVisitor {
+            type Value = Members;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Members, A::Error> {
+                let mut members = Vec::new();
+                while let Some(member) = map.next_entry()? {
+                    members.push(member);
+                }
+                Ok(Members(members))
+            }
+        }
+
+        deserializer.deserialize_map(MembersVisitor)
+    }
+}